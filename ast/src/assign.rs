@@ -1,15 +1,34 @@
 use std::fmt;
 
-use crate::{formatter::Formatter, RcLocal, SideEffects, Traverse};
+use crate::{formatter::Formatter, Origin, RcLocal, SideEffects, Traverse};
 
 use super::{LValue, LocalRw, RValue};
 
+// a lua 5.4 attribute of a local variable, written after its name in the declaration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    Close,
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Attribute::Close => write!(f, "<close>"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Assign {
     pub left: Vec<LValue>,
     pub right: Vec<RValue>,
     pub prefix: bool,
+    // the attribute of each lvalue, empty if none of them have one.
+    // written after the names when the assignment declares its locals, and in a
+    // comment when the declaration was hoisted above it
+    pub attributes: Vec<Option<Attribute>>,
     pub parallel: bool,
+    pub origin: Origin,
 }
 
 impl Assign {
@@ -18,9 +37,24 @@ impl Assign {
             left,
             right,
             prefix: false,
+            attributes: Vec::new(),
             parallel: false,
+            origin: Origin::default(),
         }
     }
+
+    pub fn attribute(&self, index: usize) -> Option<Attribute> {
+        self.attributes.get(index).copied().flatten()
+    }
+
+    pub fn set_attribute(&mut self, index: usize, attribute: Attribute) {
+        self.attributes.resize(self.left.len(), None);
+        self.attributes[index] = Some(attribute);
+    }
+
+    pub fn has_attributes(&self) -> bool {
+        self.attributes.iter().any(Option::is_some)
+    }
 }
 
 impl Traverse for Assign {
@@ -75,11 +109,6 @@ impl LocalRw for Assign {
 
 impl fmt::Display for Assign {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_assign(self)
+        Formatter::new(f, Default::default(), Default::default()).format_assign(self)
     }
 }
@@ -1,6 +1,6 @@
 use std::fmt;
 
-use crate::{formatter::Formatter, has_side_effects, LocalRw, RcLocal, Traverse};
+use crate::{formatter::Formatter, has_side_effects, LocalRw, Origin, RcLocal, Traverse};
 
 use super::RValue;
 
@@ -8,6 +8,7 @@ use super::RValue;
 pub struct Call {
     pub value: Box<RValue>,
     pub arguments: Vec<RValue>,
+    pub origin: Origin,
 }
 
 impl Call {
@@ -15,6 +16,7 @@ impl Call {
         Self {
             value: Box::new(value),
             arguments,
+            origin: Origin::default(),
         }
     }
 }
@@ -63,12 +65,7 @@ impl LocalRw for Call {
 
 impl fmt::Display for Call {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_call(self)
+        Formatter::new(f, Default::default(), Default::default()).format_call(self)
     }
 }
 
@@ -78,6 +75,7 @@ pub struct MethodCall {
     pub value: Box<RValue>,
     pub method: String,
     pub arguments: Vec<RValue>,
+    pub origin: Origin,
 }
 
 impl MethodCall {
@@ -86,6 +84,7 @@ impl MethodCall {
             value: Box::new(value),
             method,
             arguments,
+            origin: Origin::default(),
         }
     }
 }
@@ -127,11 +126,6 @@ impl LocalRw for MethodCall {
 
 impl fmt::Display for MethodCall {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_method_call(self)
+        Formatter::new(f, Default::default(), Default::default()).format_method_call(self)
     }
 }
@@ -18,10 +18,18 @@ pub enum Upvalue {
 
 #[derive(Default, Debug, PartialEq, Clone)]
 pub struct Function {
+    // the front end's id for the function
+    pub id: usize,
     pub name: Option<String>,
     pub parameters: Vec<RcLocal>,
+    // empty when the parameter types are unknown
+    pub parameter_types: Vec<Type>,
     pub is_variadic: bool,
+    // `@native`, or `--!native` when set on the main function
+    pub is_native: bool,
     pub body: Block,
+    // written as a comment in place of the empty body of a function the front end couldn't read
+    pub placeholder: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -56,12 +64,7 @@ impl Infer for Closure {
 
 impl fmt::Display for Closure {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_closure(self)
+        Formatter::new(f, Default::default(), Default::default()).format_closure(self)
     }
 }
 
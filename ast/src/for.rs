@@ -1,5 +1,6 @@
 use crate::{
-    has_side_effects, Assign, Block, LValue, LocalRw, RValue, RcLocal, SideEffects, Traverse,
+    has_side_effects, Assign, Block, LValue, LocalRw, Origin, RValue, RcLocal, SideEffects,
+    Traverse,
 };
 use itertools::Itertools;
 use parking_lot::Mutex;
@@ -13,6 +14,7 @@ pub struct NumForInit {
     pub counter: (LValue, RValue),
     pub limit: (LValue, RValue),
     pub step: (LValue, RValue),
+    pub origin: Origin,
 }
 
 impl NumForInit {
@@ -21,6 +23,7 @@ impl NumForInit {
             counter: (LValue::Local(counter.clone()), RValue::Local(counter)),
             limit: (LValue::Local(limit.clone()), RValue::Local(limit)),
             step: (LValue::Local(step.clone()), RValue::Local(step)),
+            origin: Origin::default(),
         }
     }
 }
@@ -103,6 +106,7 @@ pub struct NumForNext {
     pub counter: (LValue, RValue), // RcLocal, // cant be of type RcLocal because Traverse
     pub limit: RValue,
     pub step: RValue,
+    pub origin: Origin,
 }
 
 // NumForNext can error if the types of counter, limit and step are wrong
@@ -114,6 +118,7 @@ impl NumForNext {
             counter: (LValue::Local(counter.clone()), RValue::Local(counter)),
             limit,
             step,
+            origin: Origin::default(),
         }
     }
 }
@@ -181,6 +186,7 @@ pub struct NumericFor {
     // TODO: STYLE: rename to `control`? (thats what lua calls it)
     pub counter: RcLocal,
     pub block: Arc<Mutex<Block>>,
+    pub origin: Origin,
 }
 
 impl PartialEq for NumericFor {
@@ -206,6 +212,7 @@ impl NumericFor {
             step,
             counter,
             block: Arc::new(block.into()),
+            origin: Origin::default(),
         }
     }
 }
@@ -339,6 +346,7 @@ pub struct GenericForNext {
     pub res_locals: Vec<LValue>,
     pub generator: RValue,
     pub state: RValue,
+    pub origin: Origin,
 }
 
 impl GenericForNext {
@@ -348,6 +356,7 @@ impl GenericForNext {
             res_locals: res_locals.into_iter().map(LValue::Local).collect(),
             generator,
             state: RValue::Local(state),
+            origin: Origin::default(),
         }
     }
 }
@@ -420,6 +429,7 @@ pub struct GenericFor {
     pub res_locals: Vec<RcLocal>,
     pub right: Vec<RValue>,
     pub block: Arc<Mutex<Block>>,
+    pub origin: Origin,
 }
 
 impl PartialEq for GenericFor {
@@ -435,6 +445,7 @@ impl GenericFor {
             res_locals,
             right,
             block: Arc::new(block.into()),
+            origin: Origin::default(),
         }
     }
 }
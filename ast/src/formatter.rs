@@ -8,8 +8,10 @@ use std::{
 use itertools::Itertools;
 
 use crate::{
+    source_map::{Mapping, Position, SourceMap},
     Assign, Binary, BinaryOperation, Block, Call, Closure, GenericFor, If, Index, LValue, Literal,
-    MethodCall, NumericFor, RValue, Repeat, Return, Select, Statement, Table, Unary, While,
+    MethodCall, NumericFor, RValue, Repeat, Return, Select, Statement, Table, Unary, Upvalue,
+    While,
 };
 
 pub enum IndentationMode {
@@ -42,6 +44,45 @@ impl Default for IndentationMode {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineMode {
+    #[default]
+    Compact,
+    // a `-- line N` comment before each statement from a new source line
+    Annotate,
+    // blank lines before statements so they land on their source line where possible
+    Preserve,
+}
+
+// counts the lines and columns written, starting at 1
+pub(crate) struct LineCounter<W: fmt::Write> {
+    output: W,
+    line: usize,
+    column: usize,
+}
+
+impl<W: fmt::Write> LineCounter<W> {
+    fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+impl<W: fmt::Write> fmt::Write for LineCounter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match s.rsplit_once('\n') {
+            Some((before, after)) => {
+                self.line += before.matches('\n').count() + 1;
+                self.column = after.chars().count() + 1;
+            }
+            None => self.column += s.chars().count(),
+        }
+        self.output.write_str(s)
+    }
+}
+
 pub(crate) fn format_arg_list(list: &[RValue]) -> String {
     let mut s = String::new();
     for (index, rvalue) in list.iter().enumerate() {
@@ -61,20 +102,57 @@ pub(crate) fn format_arg_list(list: &[RValue]) -> String {
 pub struct Formatter<'a, W: fmt::Write> {
     pub(crate) indentation_level: usize,
     pub(crate) indentation_mode: IndentationMode,
-    pub(crate) output: &'a mut W,
+    pub(crate) line_mode: LineMode,
+    // the line of the last `-- line N` comment
+    annotated_line: Option<usize>,
+    // the id of the function being written
+    function: usize,
+    source_map: Option<&'a mut SourceMap>,
+    pub(crate) output: LineCounter<&'a mut W>,
 }
 
 impl<'a, W: fmt::Write> Formatter<'a, W> {
+    pub(crate) fn new(
+        output: &'a mut W,
+        indentation_mode: IndentationMode,
+        line_mode: LineMode,
+    ) -> Self {
+        Self {
+            indentation_level: 0,
+            indentation_mode,
+            line_mode,
+            annotated_line: None,
+            function: 0,
+            source_map: None,
+            output: LineCounter {
+                output,
+                line: 1,
+                column: 1,
+            },
+        }
+    }
+
     pub fn format(
         main: &Block,
         output: &'a mut W,
         indentation_mode: IndentationMode,
+        line_mode: LineMode,
     ) -> fmt::Result {
-        let mut formatter = Self {
-            indentation_level: 0,
-            indentation_mode,
-            output,
-        };
+        Self::new(output, indentation_mode, line_mode).format_block_no_indent(main)
+    }
+
+    // like `format`, but also records where each statement with an origin was written
+    pub fn format_with_source_map(
+        main: &Block,
+        main_id: usize,
+        output: &'a mut W,
+        indentation_mode: IndentationMode,
+        line_mode: LineMode,
+        source_map: &'a mut SourceMap,
+    ) -> fmt::Result {
+        let mut formatter = Self::new(output, indentation_mode, line_mode);
+        formatter.function = main_id;
+        formatter.source_map = Some(source_map);
         formatter.format_block_no_indent(main)
     }
 
@@ -107,6 +185,7 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             if i != 0 {
                 writeln!(self.output)?;
             }
+            self.format_line(statement)?;
             self.format_statement(statement)?;
             if let Some(next_statement) =
                 block.iter().skip(i + 1).find(|s| s.as_comment().is_none())
@@ -128,7 +207,7 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                     Statement::Call(_) | Statement::MethodCall(_) => true,
                     Statement::Repeat(repeat) => is_ambiguous(&repeat.condition),
                     Statement::Assign(Assign { right: list, .. })
-                    | Statement::Return(Return { values: list }) => {
+                    | Statement::Return(Return { values: list, .. }) => {
                         if let Some(last) = list.last() {
                             is_ambiguous(last)
                         } else {
@@ -166,6 +245,28 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         Ok(())
     }
 
+    fn format_line(&mut self, statement: &Statement) -> fmt::Result {
+        let Some(line) = statement.line() else {
+            return Ok(());
+        };
+        match self.line_mode {
+            LineMode::Compact => {}
+            LineMode::Annotate => {
+                if self.annotated_line != Some(line) {
+                    self.annotated_line = Some(line);
+                    self.indent()?;
+                    writeln!(self.output, "-- line {}", line)?;
+                }
+            }
+            LineMode::Preserve => {
+                while self.output.line < line {
+                    writeln!(self.output)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn format_lvalue(&mut self, lvalue: &LValue) -> fmt::Result {
         match lvalue {
             LValue::Index(index) => self.format_index(index),
@@ -187,9 +288,10 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         if keys_vec.is_empty() {
             false
         } else {
-            keys_vec.iter().enumerate().all(|(i, k)| {
-                matches!(k, Some(RValue::Literal(Literal::Number(x)))
-                        if (x - 1f64) as usize == i)
+            keys_vec.iter().enumerate().all(|(i, k)| match k {
+                Some(RValue::Literal(Literal::Number(x))) => (x - 1f64) as usize == i,
+                Some(RValue::Literal(Literal::Integer(x))) => *x == i as i64 + 1,
+                _ => false,
             })
         }
     }
@@ -279,20 +381,31 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         parentheses(self, binary.right_group(), &binary.right)
     }
 
-    fn format_closure_parameters(&mut self, closure: &Closure) -> fmt::Result {
+    fn format_closure_parameters(&mut self, closure: &Closure, is_method: bool) -> fmt::Result {
         let function = closure.function.lock();
+        // methods take `self` implicitly
+        let parameters = function
+            .parameters
+            .iter()
+            .zip(
+                function
+                    .parameter_types
+                    .iter()
+                    .map(Some)
+                    .chain(std::iter::repeat(None)),
+            )
+            .skip(is_method as usize)
+            .map(|(parameter, r#type)| match r#type {
+                Some(r#type) => format!("{}: {}", parameter, r#type),
+                None => parameter.to_string(),
+            });
         write!(
             self.output,
             "{}",
             if function.is_variadic {
-                function
-                    .parameters
-                    .iter()
-                    .map(|x| x.to_string())
-                    .chain(std::iter::once("...".into()))
-                    .join(", ")
+                parameters.chain(std::iter::once("...".into())).join(", ")
             } else {
-                function.parameters.iter().join(", ")
+                parameters.join(", ")
             }
         )
     }
@@ -331,30 +444,109 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             }
             self.indentation_level -= 1;
 
+            let parent = std::mem::replace(&mut self.function, function.id);
             self.format_block(&function.body)?;
+            self.function = parent;
             writeln!(self.output)?;
             self.indent()
+        } else if let Some(placeholder) = &function.placeholder {
+            write!(self.output, " --[[ {} ]] ", placeholder)
         } else {
             write!(self.output, " ")
         }
     }
 
+    fn format_closure_attributes(&mut self, closure: &Closure) -> fmt::Result {
+        if closure.function.lock().is_native {
+            write!(self.output, "@native ")?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn format_closure(&mut self, closure: &Closure) -> fmt::Result {
+        self.format_closure_attributes(closure)?;
         write!(self.output, "function(")?;
-        self.format_closure_parameters(closure)?;
+        self.format_closure_parameters(closure, false)?;
         write!(self.output, ")")?;
         self.format_closure_body(closure)?;
         write!(self.output, "end")
     }
 
-    fn format_named_function(&mut self, name: &LValue, closure: &Closure) -> fmt::Result {
-        write!(self.output, "function {}(", name)?;
-        self.format_closure_parameters(closure)?;
+    fn format_named_function(
+        &mut self,
+        name: &LValue,
+        closure: &Closure,
+        is_method: bool,
+    ) -> fmt::Result {
+        match name {
+            LValue::Index(Index {
+                left,
+                right: box RValue::Literal(Literal::String(method)),
+            }) if is_method => write!(
+                self.output,
+                "function {}:{}(",
+                left,
+                std::str::from_utf8(method).unwrap()
+            )?,
+            _ => write!(self.output, "function {}(", name)?,
+        }
+        self.format_closure_parameters(closure, is_method)?;
         write!(self.output, ")")?;
         self.format_closure_body(closure)?;
         write!(self.output, "end")
     }
 
+    // returns whether a closure assigned to `left` can be written as a function declaration
+    // and if so, whether it is a method declaration.
+    // most bytecode formats don't name functions, so any closure that can be is declared.
+    // when the function is named, it's only declared under that name
+    fn function_declaration(left: &LValue, closure: &Closure, prefix: bool) -> Option<bool> {
+        let function = closure.function.lock();
+        let named = |name: &[u8]| {
+            function
+                .name
+                .as_ref()
+                .is_none_or(|function_name| function_name.as_bytes() == name)
+        };
+        match left {
+            // recursive local functions must be declared with `local function`
+            // so that they can reference themselves
+            LValue::Local(local) if prefix => (named(local.to_string().as_bytes())
+                || closure.upvalues.iter().any(|u| match u {
+                    Upvalue::Copy(l) | Upvalue::Ref(l) => l == local,
+                }))
+            .then_some(false),
+            LValue::Global(global) if !prefix => named(&global.0).then_some(false),
+            LValue::Index(index) if !prefix => {
+                match &index.right {
+                    box RValue::Literal(Literal::String(key)) if named(key) => {}
+                    _ => return None,
+                }
+                let mut index = index;
+                loop {
+                    match &index.right {
+                        box RValue::Literal(Literal::String(key)) if Self::is_valid_name(key) => {}
+                        _ => return None,
+                    }
+                    match &index.left {
+                        box RValue::Index(i) => index = i,
+                        box RValue::Global(_) | box RValue::Local(_) => break,
+                        _ => return None,
+                    }
+                }
+                // the name is what tells `function a:b()` apart from a function taking `self`
+                Some(
+                    function.name.is_some()
+                        && function
+                            .parameters
+                            .first()
+                            .is_some_and(|p| p.to_string() == "self"),
+                )
+            }
+            _ => None,
+        }
+    }
+
     fn format_rvalue(&mut self, rvalue: &RValue) -> fmt::Result {
         match rvalue {
             RValue::Select(Select::Call(call)) | RValue::Call(call) => self.format_call(call),
@@ -410,7 +602,10 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         }
         Ok(())
     }
-    pub(crate) fn is_valid_name(name: &[u8]) -> bool {
+    pub fn is_valid_name(name: &[u8]) -> bool {
+        if name.is_empty() {
+            return false;
+        }
         if !(name
             .iter()
             .enumerate()
@@ -564,50 +759,36 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
     }
 
     pub(crate) fn format_assign(&mut self, assign: &Assign) -> fmt::Result {
-        if assign.prefix {
-            write!(self.output, "local ")?;
-        }
-
         if assign.left.len() == 1
             && assign.right.len() == 1
+            && !assign.has_attributes()
             && let RValue::Closure(closure) = &assign.right[0]
         {
             let left = &assign.left[0];
-            if assign.prefix || left.as_global().is_some() || {
-                if let LValue::Index(ref index) = left {
-                    let mut index = index;
-                    let mut valid = true;
-                    loop {
-                        if let box RValue::Literal(Literal::String(ref key)) = &index.right
-                            && Self::is_valid_name(key)
-                        {
-                            match index.left {
-                                box RValue::Index(ref i) => {
-                                    index = i;
-                                    continue;
-                                }
-                                box RValue::Global(_) | box RValue::Local(_) => {}
-                                _ => valid = false,
-                            }
-                        } else {
-                            valid = false;
-                        }
-                        break;
-                    }
-                    valid
-                } else {
-                    false
+            if let Some(is_method) = Self::function_declaration(left, closure, assign.prefix) {
+                // attributes come before `local`
+                self.format_closure_attributes(closure)?;
+                if assign.prefix {
+                    write!(self.output, "local ")?;
                 }
-            } {
-                return self.format_named_function(left, closure);
+                return self.format_named_function(left, closure, is_method);
             }
         }
 
+        if assign.prefix {
+            write!(self.output, "local ")?;
+        }
+
         for (i, lvalue) in assign.left.iter().enumerate() {
             if i != 0 {
                 write!(self.output, ", ")?;
             }
             self.format_lvalue(lvalue)?;
+            if assign.prefix
+                && let Some(attribute) = assign.attribute(i)
+            {
+                write!(self.output, " {}", attribute)?;
+            }
         }
 
         if !assign.right.is_empty() {
@@ -624,6 +805,25 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             self.format_rvalue(rvalue)?;
         }
 
+        // the declaration was hoisted, and a `<close>` local can't be assigned after it
+        if !assign.prefix && assign.has_attributes() {
+            write!(self.output, " --")?;
+            for (i, (lvalue, attribute)) in assign
+                .left
+                .iter()
+                .enumerate()
+                .filter_map(|(i, l)| Some((l, assign.attribute(i)?)))
+                .enumerate()
+            {
+                if i != 0 {
+                    write!(self.output, ",")?;
+                }
+                write!(self.output, " ")?;
+                self.format_lvalue(lvalue)?;
+                write!(self.output, " {}", attribute)?;
+            }
+        }
+
         if assign.parallel {
             write!(self.output, " -- parallel")?;
         }
@@ -660,10 +860,10 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         self.format_rvalue(&numeric_for.initial)?;
         write!(self.output, ", ")?;
         self.format_rvalue(&numeric_for.limit)?;
-        let skip_step = if let RValue::Literal(Literal::Number(n)) = numeric_for.step {
-            n == 1.0
-        } else {
-            false
+        let skip_step = match numeric_for.step {
+            RValue::Literal(Literal::Number(n)) => n == 1.0,
+            RValue::Literal(Literal::Integer(n)) => n == 1,
+            _ => false,
         };
         if !skip_step {
             write!(self.output, ", ")?;
@@ -723,6 +923,34 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
     fn format_statement(&mut self, statement: &Statement) -> fmt::Result {
         self.indent()?;
 
+        // reserve the mappings before writing so they stay in the order the statements start
+        let mappings = match (&mut self.source_map, statement.origin()) {
+            (Some(source_map), Some(origin)) => {
+                let start = source_map.mappings.len();
+                let position = self.output.position();
+                source_map
+                    .mappings
+                    .extend(origin.pcs.iter().map(|pcs| Mapping {
+                        function: self.function,
+                        pcs: pcs.clone(),
+                        start: position,
+                        end: position,
+                    }));
+                start..source_map.mappings.len()
+            }
+            _ => 0..0,
+        };
+        self.format_statement_inner(statement)?;
+        if let Some(source_map) = &mut self.source_map {
+            let position = self.output.position();
+            for mapping in &mut source_map.mappings[mappings] {
+                mapping.end = position;
+            }
+        }
+        Ok(())
+    }
+
+    fn format_statement_inner(&mut self, statement: &Statement) -> fmt::Result {
         match statement {
             Statement::Assign(assign) => self.format_assign(assign),
             Statement::If(r#if) => self.format_if(r#if),
@@ -737,3 +965,147 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use by_address::ByAddress;
+    use parking_lot::Mutex;
+    use triomphe::Arc;
+
+    use crate::{Function, Global, Local, Origin, RcLocal};
+
+    use super::*;
+
+    fn local(name: &str) -> RcLocal {
+        RcLocal::new(Local::new(Some(name.to_string())))
+    }
+
+    fn closure(name: Option<&str>, parameters: Vec<RcLocal>, upvalues: Vec<Upvalue>) -> RValue {
+        Closure {
+            function: ByAddress(Arc::new(Mutex::new(Function {
+                name: name.map(str::to_string),
+                parameters,
+                ..Default::default()
+            }))),
+            upvalues,
+        }
+        .into()
+    }
+
+    fn format(left: LValue, right: RValue, prefix: bool) -> String {
+        let mut assign = Assign::new(vec![left], vec![right]);
+        assign.prefix = prefix;
+        assign.to_string()
+    }
+
+    fn field(table: &str, key: &str) -> LValue {
+        Index::new(
+            Global::new(table.into()).into(),
+            Literal::String(key.into()).into(),
+        )
+        .into()
+    }
+
+    // lua 5.x, luajit and stripped luau don't name functions
+    #[test]
+    fn declares_unnamed_closures() {
+        let f = local("f");
+        assert_eq!(
+            format(f.into(), closure(None, vec![], vec![]), true),
+            "local function f() end"
+        );
+        assert_eq!(
+            format(
+                Global::new(b"g".to_vec()).into(),
+                closure(None, vec![], vec![]),
+                false
+            ),
+            "function g() end"
+        );
+        assert_eq!(
+            format(
+                field("a", "b"),
+                closure(None, vec![local("self")], vec![]),
+                false
+            ),
+            "function a.b(self) end"
+        );
+    }
+
+    #[test]
+    fn declares_named_closures_under_their_name() {
+        assert_eq!(
+            format(
+                field("a", "b"),
+                closure(Some("b"), vec![local("self")], vec![]),
+                false
+            ),
+            "function a:b() end"
+        );
+        assert_eq!(
+            format(field("a", "b"), closure(Some("c"), vec![], vec![]), false),
+            "a.b = function() end"
+        );
+        assert_eq!(
+            format(local("f").into(), closure(Some("g"), vec![], vec![]), true),
+            "local f = function() end"
+        );
+    }
+
+    // a recursive function refers to itself, so it has to be declared whatever its name is
+    #[test]
+    fn declares_recursive_local_closures() {
+        let f = local("f");
+        assert_eq!(
+            format(
+                f.clone().into(),
+                closure(Some("g"), vec![], vec![Upvalue::Ref(f)]),
+                true
+            ),
+            "local function f() end"
+        );
+    }
+
+    // assignments on lines 2, 2 and 6, the last in an `if`, then one without a line
+    fn lines(line_mode: LineMode) -> String {
+        let assign = |name: &str, line| {
+            let mut assign = Assign::new(
+                vec![Global::new(name.into()).into()],
+                vec![Literal::Number(1.0).into()],
+            );
+            assign.origin = Origin::new(line, 0..1);
+            Statement::from(assign)
+        };
+        let block = Block(vec![
+            assign("a", Some(2)),
+            assign("b", Some(2)),
+            If::new(
+                Global::new(b"c".to_vec()).into(),
+                Block(vec![assign("c", Some(6))]),
+                Block::default(),
+            )
+            .into(),
+            assign("d", None),
+        ]);
+        let mut output = String::new();
+        Formatter::format(&block, &mut output, Default::default(), line_mode).unwrap();
+        output
+    }
+
+    #[test]
+    fn annotates_lines() {
+        assert_eq!(
+            lines(LineMode::Annotate),
+            "-- line 2\na = 1\nb = 1\nif c then\n\t-- line 6\n\tc = 1\nend\nd = 1"
+        );
+    }
+
+    // statements that are behind their line are padded down to it, the rest follow on
+    #[test]
+    fn preserves_lines() {
+        assert_eq!(
+            lines(LineMode::Preserve),
+            "\na = 1\nb = 1\nif c then\n\n\tc = 1\nend\nd = 1"
+        );
+    }
+}
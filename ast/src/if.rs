@@ -1,7 +1,7 @@
 use parking_lot::Mutex;
 use triomphe::Arc;
 
-use crate::{formatter::Formatter, LocalRw, RcLocal, SideEffects, Traverse};
+use crate::{formatter::Formatter, LocalRw, Origin, RcLocal, SideEffects, Traverse};
 
 use super::{Block, RValue};
 
@@ -12,6 +12,7 @@ pub struct If {
     pub condition: RValue,
     pub then_block: Arc<Mutex<Block>>,
     pub else_block: Arc<Mutex<Block>>,
+    pub origin: Origin,
 }
 
 impl PartialEq for If {
@@ -27,6 +28,7 @@ impl If {
             condition,
             then_block: Arc::new(then_block.into()),
             else_block: Arc::new(else_block.into()),
+            origin: Origin::default(),
         }
     }
 }
@@ -60,11 +62,6 @@ impl LocalRw for If {
 
 impl fmt::Display for If {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_if(self)
+        Formatter::new(f, Default::default(), Default::default()).format_if(self)
     }
 }
@@ -27,6 +27,7 @@ mod r#if;
 mod index;
 mod literal;
 mod local;
+mod origin;
 //mod name_gen;
 pub mod local_declarations;
 pub mod name_locals;
@@ -35,6 +36,7 @@ pub mod replace_locals;
 mod r#return;
 mod set_list;
 mod side_effects;
+pub mod source_map;
 mod table;
 mod traverse;
 pub mod type_system;
@@ -52,6 +54,7 @@ pub use goto::*;
 pub use index::*;
 pub use literal::*;
 pub use local::*;
+pub use origin::*;
 pub use r#break::*;
 pub use r#continue::*;
 pub use r#for::*;
@@ -153,10 +156,19 @@ impl RValue {
         match self {
             Self::Binary(binary) => binary.precedence(),
             Self::Unary(unary) => unary.precedence(),
-            RValue::Literal(Literal::Number(n)) if n.is_finite() && n.is_sign_negative() => {
-                return 7;
+            RValue::Literal(Literal::Number(n) | Literal::Float(n))
+                if n.is_finite() && n.is_sign_negative() =>
+            {
+                11
             }
-            _ => 9,
+            RValue::Literal(Literal::Integer(n) | Literal::Int64(n))
+                if *n < 0 && *n != i64::MIN =>
+            {
+                11
+            }
+            RValue::Literal(Literal::Complex(real, _)) if *real != 0.0 => 9,
+            RValue::Literal(Literal::Complex(_, imaginary)) if imaginary.is_sign_negative() => 11,
+            _ => 13,
         }
     }
 
@@ -305,6 +317,62 @@ impl fmt::Display for Comment {
     }
 }
 
+impl Statement {
+    pub fn origin(&self) -> Option<&Origin> {
+        match self {
+            Statement::Assign(Assign { origin, .. })
+            | Statement::Call(Call { origin, .. })
+            | Statement::MethodCall(MethodCall { origin, .. })
+            | Statement::Return(Return { origin, .. })
+            | Statement::If(If { origin, .. })
+            | Statement::While(While { origin, .. })
+            | Statement::Repeat(Repeat { origin, .. })
+            | Statement::NumForInit(NumForInit { origin, .. })
+            | Statement::NumForNext(NumForNext { origin, .. })
+            | Statement::GenericForInit(GenericForInit(Assign { origin, .. }))
+            | Statement::GenericForNext(GenericForNext { origin, .. })
+            | Statement::NumericFor(NumericFor { origin, .. })
+            | Statement::GenericFor(GenericFor { origin, .. })
+            | Statement::SetList(SetList { origin, .. }) => Some(origin),
+            _ => None,
+        }
+    }
+
+    // `None` for statements that don't record where they came from
+    pub fn origin_mut(&mut self) -> Option<&mut Origin> {
+        match self {
+            Statement::Assign(Assign { origin, .. })
+            | Statement::Call(Call { origin, .. })
+            | Statement::MethodCall(MethodCall { origin, .. })
+            | Statement::Return(Return { origin, .. })
+            | Statement::If(If { origin, .. })
+            | Statement::While(While { origin, .. })
+            | Statement::Repeat(Repeat { origin, .. })
+            | Statement::NumForInit(NumForInit { origin, .. })
+            | Statement::NumForNext(NumForNext { origin, .. })
+            | Statement::GenericForInit(GenericForInit(Assign { origin, .. }))
+            | Statement::GenericForNext(GenericForNext { origin, .. })
+            | Statement::NumericFor(NumericFor { origin, .. })
+            | Statement::GenericFor(GenericFor { origin, .. })
+            | Statement::SetList(SetList { origin, .. }) => Some(origin),
+            _ => None,
+        }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        self.origin().and_then(|origin| origin.line)
+    }
+
+    // merges the origin of a statement that was folded into this one
+    pub fn merge_origin(&mut self, other: &Statement) {
+        if let Some(origin) = other.origin()
+            && let Some(this) = self.origin_mut()
+        {
+            this.merge(origin.clone());
+        }
+    }
+}
+
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -355,6 +423,6 @@ impl DerefMut for Block {
 
 impl fmt::Display for Block {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter::format(self, f, Default::default())
+        Formatter::format(self, f, Default::default(), Default::default())
     }
 }
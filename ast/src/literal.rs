@@ -12,6 +12,18 @@ pub enum Literal {
     Nil,
     Boolean(bool),
     Number(f64),
+    // lua 5.3 and later tell integers and floats apart, these are their finite constants
+    Integer(i64),
+    #[from(ignore)]
+    Float(f64),
+    // luajit's 64-bit integer cdata, written with an `LL` or `ULL` suffix
+    #[from(ignore)]
+    Int64(i64),
+    #[from(ignore)]
+    UInt64(u64),
+    // luajit's complex cdata, the real and imaginary part. only imaginary literals like `2i` exist
+    #[from(ignore)]
+    Complex(f64, f64),
     String(Vec<u8>),
     Vector(f32, f32, f32),
 }
@@ -26,6 +38,11 @@ impl Reduce for Literal {
             Literal::Boolean(false) | Literal::Nil => false,
             Literal::Boolean(true)
             | Literal::Number(_)
+            | Literal::Integer(_)
+            | Literal::Float(_)
+            | Literal::Int64(_)
+            | Literal::UInt64(_)
+            | Literal::Complex(..)
             | Literal::String(_)
             | Literal::Vector(..) => true,
         })
@@ -38,9 +55,10 @@ impl Infer for Literal {
         match self {
             Literal::Nil => Type::Nil,
             Literal::Boolean(_) => Type::Boolean,
-            Literal::Number(_) => Type::Number,
+            Literal::Number(_) | Literal::Integer(_) | Literal::Float(_) => Type::Number,
             Literal::String(_) => Type::String,
             Literal::Vector(..) => Type::Vector,
+            Literal::Int64(_) | Literal::UInt64(_) | Literal::Complex(..) => Type::Userdata,
         }
     }
 }
@@ -71,6 +89,31 @@ impl fmt::Display for Literal {
                 let printed = buffer.format_finite(value);
                 write!(f, "{}", printed.strip_suffix(".0").unwrap_or(printed))
             }
+            // the decimal form would overflow into a float, hexadecimal wraps around
+            Literal::Integer(i64::MIN) => write!(f, "0x8000000000000000"),
+            Literal::Integer(value) => write!(f, "{}", value),
+            // keeps the ".0" so it's read back as a float
+            &Literal::Float(value) => {
+                debug_assert!(value.is_finite());
+                write!(f, "{}", ryu::Buffer::new().format_finite(value))
+            }
+            Literal::Int64(i64::MIN) => write!(f, "0x8000000000000000LL"),
+            Literal::Int64(value) => write!(f, "{}LL", value),
+            Literal::UInt64(value) => write!(f, "{}ULL", value),
+            // written as a sum when the real part isn't 0, see `RValue::precedence`
+            &Literal::Complex(real, imaginary) => {
+                if real != 0.0 {
+                    write!(f, "{} ", format_part(real))?;
+                    if imaginary.is_sign_negative() {
+                        write!(f, "- ")?;
+                    } else {
+                        write!(f, "+ ")?;
+                    }
+                    write!(f, "{}i", format_part(imaginary.abs()))
+                } else {
+                    write!(f, "{}i", format_part(imaginary))
+                }
+            }
             Literal::String(value) => {
                 write!(
                     f,
@@ -82,3 +125,19 @@ impl fmt::Display for Literal {
         }
     }
 }
+
+// a part of a complex literal, infinity is written as a number that overflows to it
+fn format_part(value: f64) -> String {
+    if value.is_infinite() {
+        if value.is_sign_negative() {
+            "-1e999".to_string()
+        } else {
+            "1e999".to_string()
+        }
+    } else {
+        debug_assert!(value.is_finite());
+        let mut buffer = ryu::Buffer::new();
+        let printed = buffer.format_finite(value);
+        printed.strip_suffix(".0").unwrap_or(printed).to_string()
+    }
+}
@@ -58,6 +58,21 @@ impl RcLocal {
     pub fn new(local: Local) -> Self {
         Self(ByAddress(Arc::new(Mutex::new(local))))
     }
+
+    pub fn name(&self) -> Option<String> {
+        self.0 .0.lock().0.clone()
+    }
+
+    pub fn is_named(&self) -> bool {
+        self.0 .0.lock().0.is_some()
+    }
+
+    // gives this local the name of `other` if it doesn't have one already
+    pub fn inherit_name(&self, other: &RcLocal) {
+        if self != other && !self.is_named() {
+            self.0 .0.lock().0 = other.name();
+        }
+    }
 }
 
 impl LocalRw for RcLocal {
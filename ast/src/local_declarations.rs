@@ -173,3 +173,38 @@ impl LocalDeclarer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Attribute, Call, Global, If, Literal, Local};
+
+    use super::*;
+
+    #[test]
+    fn keeps_attributes_of_hoisted_declarations() {
+        let f = RcLocal::new(Local::new(Some("f".to_string())));
+        let mut close = Assign::new(
+            vec![f.clone().into()],
+            vec![Call::new(Global::new(b"g".to_vec()).into(), vec![]).into()],
+        );
+        close.set_attribute(0, Attribute::Close);
+        let block = Arc::new(Mutex::new(Block(vec![
+            If::new(
+                Global::new(b"c".to_vec()).into(),
+                Block(vec![close.into()]),
+                Block(vec![Assign::new(
+                    vec![f.clone().into()],
+                    vec![Literal::Nil.into()],
+                )
+                .into()]),
+            )
+            .into(),
+            Call::new(Global::new(b"print".to_vec()).into(), vec![f.into()]).into(),
+        ])));
+        LocalDeclarer::default().declare_locals(block.clone(), &FxHashSet::default());
+        assert_eq!(
+            block.lock().to_string(),
+            "local f\nif c then\n\tf = g() -- f <close>\nelse\n\tf = nil\nend\nprint(f)"
+        );
+    }
+}
@@ -1,12 +1,13 @@
 use rustc_hash::FxHashSet;
 use triomphe::Arc;
 
-use crate::{Block, RValue, RcLocal, Statement, Traverse, Upvalue};
+use crate::{formatter::Formatter, Block, RValue, RcLocal, Statement, Traverse, Upvalue};
 
 struct Namer {
     rename: bool,
     counter: usize,
     upvalues: FxHashSet<RcLocal>,
+    function_names: FxHashSet<String>,
 }
 
 impl Namer {
@@ -29,6 +30,21 @@ impl Namer {
         }
     }
 
+    // names a local after the function assigned to it, each function name is only used once
+    // so that locals with the same name never shadow each other
+    fn name_local_after_function(&mut self, local: &RcLocal, function_name: &str) -> bool {
+        let mut lock = local.0 .0.lock();
+        if (self.rename || lock.0.is_none())
+            && Formatter::<String>::is_valid_name(function_name.as_bytes())
+            && self.function_names.insert(function_name.to_string())
+        {
+            lock.0 = Some(function_name.to_string());
+            true
+        } else {
+            false
+        }
+    }
+
     fn name_locals(&mut self, block: &mut Block) {
         for statement in &mut block.0 {
             // TODO: traverse_rvalues
@@ -44,6 +60,14 @@ impl Namer {
             });
             match statement {
                 Statement::Assign(assign) if assign.prefix => {
+                    if let ([lvalue], [RValue::Closure(closure)]) =
+                        (&assign.left[..], &assign.right[..])
+                        && let Some(function_name) = closure.function.lock().name.clone()
+                        && self
+                            .name_local_after_function(lvalue.as_local().unwrap(), &function_name)
+                    {
+                        continue;
+                    }
                     for lvalue in &assign.left {
                         self.name_local("v", lvalue.as_local().unwrap());
                     }
@@ -121,6 +145,7 @@ pub fn name_locals(block: &mut Block, rename: bool) {
         rename,
         counter: 1,
         upvalues: FxHashSet::default(),
+        function_names: FxHashSet::default(),
     };
     namer.find_upvalues(block);
     namer.name_locals(block);
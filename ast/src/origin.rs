@@ -0,0 +1,34 @@
+use std::ops::Range;
+
+// where in the bytecode a statement was lifted from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Origin {
+    // the source line, if the chunk has line info
+    pub line: Option<usize>,
+    // the instructions the statement was lifted from, sorted and disjoint
+    pub pcs: Vec<Range<usize>>,
+}
+
+impl Origin {
+    pub fn new(line: Option<usize>, pcs: Range<usize>) -> Self {
+        Self {
+            line,
+            pcs: vec![pcs],
+        }
+    }
+
+    // adds the instructions of a statement that was folded into this one
+    pub fn merge(&mut self, other: Origin) {
+        self.line = self.line.or(other.line);
+        self.pcs.extend(other.pcs);
+        self.pcs.sort_by_key(|pcs| pcs.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(self.pcs.len());
+        for pcs in self.pcs.drain(..) {
+            match merged.last_mut() {
+                Some(last) if pcs.start <= last.end => last.end = last.end.max(pcs.end),
+                _ => merged.push(pcs),
+            }
+        }
+        self.pcs = merged;
+    }
+}
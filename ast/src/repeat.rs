@@ -1,7 +1,9 @@
 use parking_lot::Mutex;
 use triomphe::Arc;
 
-use crate::{formatter::Formatter, has_side_effects, Block, LocalRw, RValue, RcLocal, Traverse};
+use crate::{
+    formatter::Formatter, has_side_effects, Block, LocalRw, Origin, RValue, RcLocal, Traverse,
+};
 use std::fmt;
 
 // TODO: move condition after block
@@ -9,6 +11,8 @@ use std::fmt;
 pub struct Repeat {
     pub condition: RValue,
     pub block: Arc<Mutex<Block>>,
+    // the instructions of the condition
+    pub origin: Origin,
 }
 
 impl PartialEq for Repeat {
@@ -25,6 +29,7 @@ impl Repeat {
         Self {
             condition,
             block: Arc::new(block.into()),
+            origin: Origin::default(),
         }
     }
 }
@@ -51,11 +56,6 @@ impl LocalRw for Repeat {
 
 impl fmt::Display for Repeat {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_repeat(self)
+        Formatter::new(f, Default::default(), Default::default()).format_repeat(self)
     }
 }
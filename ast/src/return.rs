@@ -1,19 +1,23 @@
 use std::fmt;
 
-use crate::{formatter::Formatter, has_side_effects, LocalRw, RcLocal, Traverse};
+use crate::{formatter::Formatter, has_side_effects, LocalRw, Origin, RcLocal, Traverse};
 
 use super::RValue;
 
 #[derive(Debug, PartialEq, Clone, Default)]
 pub struct Return {
     pub values: Vec<RValue>,
+    pub origin: Origin,
 }
 
 has_side_effects!(Return);
 
 impl Return {
     pub fn new(values: Vec<RValue>) -> Self {
-        Self { values }
+        Self {
+            values,
+            origin: Origin::default(),
+        }
     }
 }
 
@@ -42,11 +46,6 @@ impl LocalRw for Return {
 
 impl fmt::Display for Return {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_return(self)
+        Formatter::new(f, Default::default(), Default::default()).format_return(self)
     }
 }
@@ -1,4 +1,4 @@
-use crate::{formatter, LocalRw, RValue, RcLocal, SideEffects, Traverse};
+use crate::{formatter, LocalRw, Origin, RValue, RcLocal, SideEffects, Traverse};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct SetList {
@@ -6,6 +6,7 @@ pub struct SetList {
     pub index: usize,
     pub values: Vec<RValue>,
     pub tail: Option<RValue>,
+    pub origin: Origin,
 }
 
 impl SetList {
@@ -20,6 +21,7 @@ impl SetList {
             index,
             values,
             tail,
+            origin: Origin::default(),
         }
     }
 }
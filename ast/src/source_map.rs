@@ -0,0 +1,86 @@
+use std::ops::Range;
+
+use serde_json::json;
+
+// a position in the output, both 1-based and counted in chars
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    // the front end's id for the function the instructions belong to
+    pub function: usize,
+    pub pcs: Range<usize>,
+    pub start: Position,
+    // exclusive
+    pub end: Position,
+}
+
+// maps the output of the formatter back to the instructions each statement was lifted from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    // in the order the statements were written
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    pub fn to_json(&self) -> String {
+        let mappings = self
+            .mappings
+            .iter()
+            .map(|mapping| {
+                json!({
+                    "function": mapping.function,
+                    "pcs": [mapping.pcs.start, mapping.pcs.end],
+                    "start": [mapping.start.line, mapping.start.column],
+                    "end": [mapping.end.line, mapping.end.column],
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({ "version": 1, "mappings": mappings }).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_json() {
+        let position = |line, column| Position { line, column };
+        let source_map = SourceMap {
+            mappings: vec![
+                Mapping {
+                    function: 0,
+                    pcs: 0..2,
+                    start: position(1, 1),
+                    end: position(3, 4),
+                },
+                Mapping {
+                    function: 1,
+                    pcs: 4..5,
+                    start: position(2, 2),
+                    end: position(2, 10),
+                },
+            ],
+        };
+        let parse = |json: String| serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(
+            parse(source_map.to_json()),
+            json!({
+                "version": 1,
+                "mappings": [
+                    { "function": 0, "pcs": [0, 2], "start": [1, 1], "end": [3, 4] },
+                    { "function": 1, "pcs": [4, 5], "start": [2, 2], "end": [2, 10] },
+                ],
+            })
+        );
+        assert_eq!(
+            parse(SourceMap::default().to_json()),
+            json!({ "version": 1, "mappings": [] })
+        );
+    }
+}
@@ -117,11 +117,6 @@ impl SideEffects for Table {
 
 impl fmt::Display for Table {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_table(self)
+        Formatter::new(f, Default::default(), Default::default()).format_table(self)
     }
 }
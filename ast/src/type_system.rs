@@ -23,6 +23,11 @@ pub enum Type {
     Intersection(BTreeSet<Type>),
     VarArg,
     Vector,
+    Thread,
+    Buffer,
+    Userdata,
+    // a userdata type registered by the host, e.g. `Instance`
+    Named(String),
 }
 
 impl Type {
@@ -72,6 +77,10 @@ impl Type {
             Self::Intersection(_) => 2,
             Self::VarArg => 0,
             Self::Vector => 0,
+            Self::Thread => 0,
+            Self::Buffer => 0,
+            Self::Userdata => 0,
+            Self::Named(_) => 0,
         }
     }
 }
@@ -115,6 +124,12 @@ impl Display for Type {
                         codomain.iter().join(", ")
                     }
                 )),
+                Type::Optional(r#type)
+                    if matches!(
+                        **r#type,
+                        Type::Function(..) | Type::Union(_) | Type::Intersection(_)
+                    ) =>
+                    Cow::Owned(format!("({})?", r#type)),
                 Type::Optional(r#type) => Cow::Owned(format!("{}?", r#type)),
                 Type::Union(types) => {
                     Cow::Owned(types.iter().join(" | "))
@@ -122,8 +137,12 @@ impl Display for Type {
                 Type::Intersection(types) => {
                     Cow::Owned(types.iter().join(" & "))
                 }
-                Type::VarArg => Cow::Borrowed("..."),
+                Type::VarArg => Cow::Borrowed("...any"),
                 Type::Vector => Cow::Borrowed("vector"),
+                Type::Thread => Cow::Borrowed("thread"),
+                Type::Buffer => Cow::Borrowed("buffer"),
+                Type::Userdata => Cow::Borrowed("userdata"),
+                Type::Named(name) => Cow::Borrowed(name.as_str()),
             }
         )
     }
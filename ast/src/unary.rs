@@ -9,6 +9,7 @@ pub enum UnaryOperation {
     Not,
     Negate,
     Length,
+    BitNot,
 }
 
 impl fmt::Display for UnaryOperation {
@@ -17,6 +18,7 @@ impl fmt::Display for UnaryOperation {
             Self::Not => write!(f, "not "),
             Self::Negate => write!(f, "-"),
             Self::Length => write!(f, "#"),
+            Self::BitNot => write!(f, "~"),
         }
     }
 }
@@ -32,7 +34,7 @@ impl SideEffects for Unary {
         // TODO: do this properly
         matches!(
             self.operation,
-            UnaryOperation::Negate | UnaryOperation::Length
+            UnaryOperation::Negate | UnaryOperation::Length | UnaryOperation::BitNot
         ) || self.value.has_side_effects()
     }
 }
@@ -93,6 +95,12 @@ impl Reduce for Unary {
             (RValue::Literal(Literal::Number(value)), UnaryOperation::Negate) => {
                 RValue::Literal(Literal::Number(-value))
             }
+            (RValue::Literal(Literal::Integer(value)), UnaryOperation::Negate) => {
+                RValue::Literal(Literal::Integer(value.wrapping_neg()))
+            }
+            (RValue::Literal(Literal::Float(value)), UnaryOperation::Negate) => {
+                RValue::Literal(Literal::Float(-value))
+            }
             (RValue::Literal(Literal::String(value)), UnaryOperation::Length) => {
                 // TODO: is this accurate w/ unicode in Luau?
                 RValue::Literal(Literal::Number(value.len() as f64))
@@ -243,6 +251,12 @@ impl Reduce for Unary {
             (RValue::Literal(Literal::Number(value)), UnaryOperation::Negate) => {
                 RValue::Literal(Literal::Number(-value))
             }
+            (RValue::Literal(Literal::Integer(value)), UnaryOperation::Negate) => {
+                RValue::Literal(Literal::Integer(value.wrapping_neg()))
+            }
+            (RValue::Literal(Literal::Float(value)), UnaryOperation::Negate) => {
+                RValue::Literal(Literal::Float(-value))
+            }
             // __len has to return number, numbers are always truthy
             (_, UnaryOperation::Length) => RValue::Literal(Literal::Boolean(true)),
             (
@@ -381,7 +395,7 @@ impl Unary {
     }
 
     pub fn precedence(&self) -> usize {
-        7
+        11
     }
 
     pub fn group(&self) -> bool {
@@ -395,8 +409,11 @@ impl Unary {
                     })
                 ) || matches!(
                     *self.value,
-                    RValue::Literal(Literal::Number(value))
+                    RValue::Literal(Literal::Number(value) | Literal::Float(value))
                         if value.is_finite() && value.is_sign_negative()
+                ) || matches!(
+                    *self.value,
+                    RValue::Literal(Literal::Integer(value)) if value < 0
                 )))
     }
 }
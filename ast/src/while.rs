@@ -1,13 +1,17 @@
 use parking_lot::Mutex;
 use triomphe::Arc;
 
-use crate::{formatter::Formatter, has_side_effects, Block, LocalRw, RValue, RcLocal, Traverse};
+use crate::{
+    formatter::Formatter, has_side_effects, Block, LocalRw, Origin, RValue, RcLocal, Traverse,
+};
 use std::fmt;
 
 #[derive(Debug, Clone)]
 pub struct While {
     pub condition: RValue,
     pub block: Arc<Mutex<Block>>,
+    // the instructions of the condition
+    pub origin: Origin,
 }
 
 impl PartialEq for While {
@@ -24,6 +28,7 @@ impl While {
         Self {
             condition,
             block: Arc::new(block.into()),
+            origin: Origin::default(),
         }
     }
 }
@@ -50,11 +55,6 @@ impl LocalRw for While {
 
 impl fmt::Display for While {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_while(self)
+        Formatter::new(f, Default::default(), Default::default()).format_while(self)
     }
 }
@@ -7,6 +7,8 @@ use petgraph::{
     Direction,
 };
 
+use rustc_hash::FxHashMap;
+
 use crate::block::{BlockEdge, BranchType};
 
 #[derive(Debug, Clone, Default)]
@@ -14,7 +16,13 @@ pub struct Function {
     pub id: usize,
     pub name: Option<String>,
     pub parameters: Vec<RcLocal>,
+    pub parameter_types: Vec<ast::type_system::Type>,
     pub is_variadic: bool,
+    // compiled to native code, see `ast::Function::is_native`
+    pub is_native: bool,
+    // names of the locals written by the statement at (block, statement index),
+    // taken from debug info. consumed by ssa construction
+    pub local_names: FxHashMap<(NodeIndex, usize, RcLocal), String>,
     graph: StableDiGraph<ast::Block, BlockEdge>,
     entry: Option<NodeIndex>,
 }
@@ -25,7 +33,10 @@ impl Function {
             id,
             name: None,
             parameters: Vec::new(),
+            parameter_types: Vec::new(),
             is_variadic: false,
+            is_native: false,
+            local_names: FxHashMap::default(),
             graph: StableDiGraph::new(),
             entry: None,
         }
@@ -148,6 +148,7 @@ pub fn remove_unnecessary_params(
             while let Some(arg_to) = local_map.get(arg) {
                 arg = arg_to;
             }
+            arg.inherit_name(&param);
             local_map.insert(param, arg.clone());
             changed = true;
         }
@@ -240,6 +241,22 @@ pub fn apply_local_map(function: &mut Function, local_map: FxHashMap<RcLocal, Rc
 
 // based on "Simple and Efficient Construction of Static Single Assignment Form" (https://pp.info.uni-karlsruhe.de/uploads/publikationen/braun13cc.pdf)
 impl<'a> SsaConstructor<'a> {
+    fn new_written_local(
+        &mut self,
+        node: NodeIndex,
+        stat_index: usize,
+        local: &RcLocal,
+    ) -> RcLocal {
+        match self
+            .function
+            .local_names
+            .remove(&(node, stat_index, local.clone()))
+        {
+            Some(name) => RcLocal::new(ast::Local::new(Some(name))),
+            None => RcLocal::default(),
+        }
+    }
+
     fn write_local(&mut self, node: NodeIndex, local: &RcLocal, new_local: &RcLocal) {
         self.all_definitions
             .entry(local.clone())
@@ -406,6 +423,7 @@ impl<'a> SsaConstructor<'a> {
                 let assign = block[index].as_assign().unwrap();
                 if assign.left.len() == 1
                     && assign.right.len() == 1
+                    && !assign.has_attributes()
                     && let Some(from) = assign.left[0].as_local()
                     && let from_old = &self.old_locals[from]
                     && !self.new_upvalues_in.contains_key(from_old)
@@ -417,9 +435,12 @@ impl<'a> SsaConstructor<'a> {
                         to = to_to;
                     }
                     let to_old = &self.old_locals[to];
+                    // keep copies between two named locals, they were separate locals in the source
                     if !self.new_upvalues_in.contains_key(to_old)
                         && !self.upvalues_passed.contains_key(to_old)
+                        && !(from.is_named() && to.is_named())
                     {
+                        to.inherit_name(from);
                         self.local_map.insert(from.clone(), to.clone());
                         block[index] = ast::Empty {}.into();
                     }
@@ -523,7 +544,7 @@ impl<'a> SsaConstructor<'a> {
                     && let Some(local) = assign.left[0].as_local().cloned()
                     && assign.right[0].as_closure().is_some()
                 {
-                    let new_local = RcLocal::default();
+                    let new_local = self.new_written_local(node, stat_index, &local);
                     self.old_locals.insert(new_local.clone(), local.clone());
                     if let Some(upvalues) = self.new_upvalues_in.get_mut(&local) {
                         upvalues.insert(new_local.clone());
@@ -549,7 +570,7 @@ impl<'a> SsaConstructor<'a> {
                     self.read(node, stat_index);
                     // write
                     for (local_index, local) in written.iter().enumerate() {
-                        let new_local = RcLocal::default();
+                        let new_local = self.new_written_local(node, stat_index, local);
                         self.old_locals.insert(new_local.clone(), local.clone());
                         if let Some(upvalues) = self.new_upvalues_in.get_mut(local) {
                             upvalues.insert(new_local.clone());
@@ -606,10 +627,13 @@ impl<'a> SsaConstructor<'a> {
         // TODO: this is a bit meh, maybe we should have an argument rvalue
         if let Some(mut incomplete_params) = self.incomplete_params.remove(&entry) {
             for param in &mut self.function.parameters {
-                *param = incomplete_params.remove(param).unwrap_or_default();
+                let new_param = incomplete_params.remove(param).unwrap_or_default();
+                new_param.inherit_name(param);
+                *param = new_param;
             }
         }
         assert!(self.incomplete_params.is_empty());
+        self.function.local_names.clear();
 
         // TODO: irreducible control flow (see the paper this algorithm is from)
         // TODO: apply_local_map unnecessary number of calls
@@ -241,7 +241,11 @@ impl<'a> Destructor<'a> {
         let mut map = FxHashMap::default();
         for (local, con_class) in &self.congruence_classes {
             let con_class = con_class.borrow();
-            let new_local = con_class.iter().next().unwrap().1;
+            // prefer a named local so names from debug info survive
+            let new_local = con_class
+                .values()
+                .find(|l| l.is_named())
+                .unwrap_or_else(|| con_class.values().next().unwrap());
             // TODO: see apply_local_map TODO,
             // we dont want to handle this here
             if local != new_local {
@@ -404,6 +408,8 @@ impl<'a> Destructor<'a> {
                     .left
                     .iter()
                     .enumerate()
+                    // locals with an attribute keep their declaration
+                    .filter(|&(i, _)| assign.attribute(i).is_none())
                     .filter_map(|(i, l)| Some((i, l.as_local()?.clone())))
                     .collect::<Vec<_>>();
                 for (i, left, right) in left
@@ -425,6 +431,15 @@ impl<'a> Destructor<'a> {
                         continue;
                     }
 
+                    // copies between differently named locals are kept,
+                    // they were separate locals in the source
+                    if let Some(left_name) = self.congruence_class_name(&left)
+                        && let Some(right_name) = self.congruence_class_name(&right)
+                        && left_name != right_name
+                    {
+                        continue;
+                    }
+
                     if self.try_coalesce_copy_by_value(right.clone(), left.clone())
                         || self.try_coalesce_copy_by_sharing(&right, &left)
                     {
@@ -437,6 +452,9 @@ impl<'a> Destructor<'a> {
                 for i in to_remove.into_iter().rev() {
                     assign.left.remove(i);
                     assign.right.remove(i);
+                    if i < assign.attributes.len() {
+                        assign.attributes.remove(i);
+                    }
                 }
                 assign.left.is_empty()
             } else {
@@ -455,6 +473,13 @@ impl<'a> Destructor<'a> {
         block.retain(|s| s.as_empty().is_none());
     }
 
+    fn congruence_class_name(&mut self, local: &RcLocal) -> Option<String> {
+        self.get_congruence_class(local.clone())
+            .borrow()
+            .values()
+            .find_map(|l| l.name())
+    }
+
     fn coalesce_copies(&mut self) {
         let mut dominator_dfs = Dfs::new(&self.dominator_tree, self.function.entry().unwrap());
         while let Some(node) = dominator_dfs.next(self.function.graph()) {
@@ -811,7 +836,9 @@ impl<'a> Destructor<'a> {
                     left: param_map.keys().map(|k| k.clone().into()).collect(),
                     right: param_map.values().map(|v| v.clone().into()).collect(),
                     prefix: false,
+                    attributes: Vec::new(),
                     parallel: true,
+                    origin: ast::Origin::default(),
                 }
                 .into(),
             );
@@ -851,7 +878,9 @@ impl<'a> Destructor<'a> {
                     left: Vec::with_capacity(args.len()),
                     right: Vec::with_capacity(args.len()),
                     prefix: false,
+                    attributes: Vec::new(),
                     parallel: true,
+                    origin: ast::Origin::default(),
                 };
 
                 for (param, arg) in args {
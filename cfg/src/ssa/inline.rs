@@ -128,7 +128,9 @@ impl<'a> Inliner<'a> {
                     stat.values_read()
                         .into_iter()
                         .filter(|&l| {
-                            self.local_usages[l] == 1 && !self.upvalue_to_group.contains_key(l)
+                            self.local_usages[l] == 1
+                                && !self.upvalue_to_group.contains_key(l)
+                                && !l.is_named()
                         })
                         .cloned()
                         .map(Some)
@@ -184,6 +186,7 @@ impl<'a> Inliner<'a> {
                     }
 
                     if let ast::Statement::Assign(assign) = &block[stat_index]
+                        && !assign.has_attributes()
                         && let Ok(new_rvalue) = assign.right.iter().exactly_one()
                     {
                         let new_rvalue_has_side_effects = new_rvalue.has_side_effects()
@@ -229,7 +232,11 @@ impl<'a> Inliner<'a> {
                                     }
                                     // we dont need to update local usages because tracking usages for a local
                                     // with no declarations serves no purpose
-                                    block[stat_index] = ast::Empty {}.into();
+                                    let inlined = std::mem::replace(
+                                        &mut block[stat_index],
+                                        ast::Empty {}.into(),
+                                    );
+                                    block[index].merge_origin(&inlined);
                                     *read = None;
                                     continue 'w;
                                 } else {
@@ -296,7 +303,11 @@ impl<'a> Inliner<'a> {
                                     }
                                     // we dont need to update local usages because tracking usages for a local
                                     // with no declarations serves no purpose
-                                    block[stat_index] = ast::Empty {}.into();
+                                    let inlined = std::mem::replace(
+                                        &mut block[stat_index],
+                                        ast::Empty {}.into(),
+                                    );
+                                    block[index].merge_origin(&inlined);
                                     for old_local in old_locals {
                                         *stat_to_values_read[index]
                                             .iter_mut()
@@ -385,6 +396,7 @@ impl<'a> Inliner<'a> {
                         }
 
                         if let ast::Statement::Assign(assign) = &block[stat_index]
+                            && !assign.has_attributes()
                             && let Ok(new_rvalue) = assign.right.iter().exactly_one()
                         {
                             let new_rvalue_has_side_effects = new_rvalue.has_side_effects()
@@ -422,6 +434,11 @@ impl<'a> Inliner<'a> {
                                     new_rvalue_has_side_effects,
                                 ) {
                                     assert!(new_rvalue.is_none());
+                                    // the param now holds the value of the inlined local
+                                    let (param, _) =
+                                        &self.function.graph().edge_weight(edge).unwrap().arguments
+                                            [index];
+                                    param.inherit_name(read.as_ref().unwrap());
                                     let block = self.function.block_mut(node).unwrap();
 
                                     // TODO: PERF: remove `local_usages[l] == 1` filter in stat_to_values_read
@@ -494,6 +511,7 @@ pub fn inline(
                 if let ast::Statement::Assign(assign) = &block[stat_index]
                     && assign.left.len() == 1
                     && assign.right.len() == 1
+                    && !assign.has_attributes()
                     && let ast::LValue::Local(local) = &assign.left[0]
                 {
                     let rvalue = &assign.right[0];
@@ -504,7 +522,7 @@ pub fn inline(
                     {
                         if has_side_effects {
                             // TODO: PERF: dont clone
-                            let new_stat = match rvalue {
+                            let new_stat: Option<ast::Statement> = match rvalue {
                                 ast::RValue::Call(call)
                                 | ast::RValue::Select(ast::Select::Call(call)) => {
                                     Some(call.clone().into())
@@ -515,7 +533,8 @@ pub fn inline(
                                 }
                                 _ => None,
                             };
-                            if let Some(new_stat) = new_stat {
+                            if let Some(mut new_stat) = new_stat {
+                                new_stat.merge_origin(&block[stat_index]);
                                 block[stat_index] = new_stat;
                                 changed = true;
                             }
@@ -565,23 +584,21 @@ pub fn inline(
                         let field_assign = std::mem::replace(&mut block[i], ast::Empty {}.into())
                             .into_assign()
                             .unwrap();
-                        block[table_index].as_assign_mut().unwrap().right[0]
-                            .as_table_mut()
-                            .unwrap()
-                            .0
-                            .push((
-                                Some(Box::into_inner(
-                                    field_assign
-                                        .left
-                                        .into_iter()
-                                        .next()
-                                        .unwrap()
-                                        .into_index()
-                                        .unwrap()
-                                        .right,
-                                )),
-                                field_assign.right.into_iter().next().unwrap(),
-                            ));
+                        let table_assign = block[table_index].as_assign_mut().unwrap();
+                        table_assign.origin.merge(field_assign.origin);
+                        table_assign.right[0].as_table_mut().unwrap().0.push((
+                            Some(Box::into_inner(
+                                field_assign
+                                    .left
+                                    .into_iter()
+                                    .next()
+                                    .unwrap()
+                                    .into_index()
+                                    .unwrap()
+                                    .right,
+                            )),
+                            field_assign.right.into_iter().next().unwrap(),
+                        ));
                         changed = true;
                         i += 1;
                     }
@@ -602,6 +619,7 @@ pub fn inline(
                             .unwrap();
                         *local_usages.get_mut(&set_list.object_local).unwrap() -= 1;
                         let assign = block.get_mut(i - 1).unwrap().as_assign_mut().unwrap();
+                        assign.origin.merge(set_list.origin);
                         let table = assign.right[0].as_table_mut().unwrap();
                         assert!(
                             table.0.iter().filter(|(k, _)| k.is_none()).count()
@@ -354,7 +354,14 @@ fn is_truthy(rvalue: ast::RValue) -> Option<bool> {
             ..
         }) => Some(true),
         ast::RValue::Literal(
-            ast::Literal::Boolean(true) | ast::Literal::Number(_) | ast::Literal::String(_),
+            ast::Literal::Boolean(true)
+            | ast::Literal::Number(_)
+            | ast::Literal::Integer(_)
+            | ast::Literal::Float(_)
+            | ast::Literal::Int64(_)
+            | ast::Literal::UInt64(_)
+            | ast::Literal::Complex(..)
+            | ast::Literal::String(_),
         )
         | ast::RValue::Table(_)
         | ast::RValue::Closure(_) => Some(true),
@@ -689,10 +696,12 @@ fn structure_bool_conditional(function: &mut Function, node: NodeIndex) -> bool
             && function.successor_blocks(else_target).next().is_none()
             && let Ok(ast::Statement::Return(ast::Return {
                 values: then_values,
+                ..
             })) = function.block(then_target).unwrap().iter().exactly_one()
             && let Ok(then_value) = then_values.iter().exactly_one()
             && let Ok(ast::Statement::Return(ast::Return {
                 values: else_values,
+                ..
             })) = function.block(else_target).unwrap().iter().exactly_one()
             && let Ok(else_value) = else_values.iter().exactly_one()
         {
@@ -851,15 +860,18 @@ fn try_remove_unnecessary_condition(function: &mut Function, node: NodeIndex) ->
     {
         let target = then_edge.target();
         // TODO: check if this works (+ restructuring/src/jump.rs)
-        let cond = function
+        let ast::If {
+            condition: cond,
+            origin,
+            ..
+        } = function
             .block_mut(node)
             .unwrap()
             .pop()
             .unwrap()
             .into_if()
-            .unwrap()
-            .condition;
-        let new_stat = match cond {
+            .unwrap();
+        let mut new_stat: Option<ast::Statement> = match cond {
             ast::RValue::Call(call) => Some(call.into()),
             ast::RValue::MethodCall(method_call) => Some(method_call.into()),
             cond if cond.has_side_effects() => Some(
@@ -867,12 +879,18 @@ fn try_remove_unnecessary_condition(function: &mut Function, node: NodeIndex) ->
                     left: vec![ast::RcLocal::default().into()],
                     right: vec![cond],
                     prefix: true,
+                    attributes: Vec::new(),
                     parallel: false,
+                    origin: ast::Origin::default(),
                 }
                 .into(),
             ),
             _ => None,
         };
+        // what remains of the condition
+        if let Some(new_origin) = new_stat.as_mut().and_then(|s| s.origin_mut()) {
+            *new_origin = origin;
+        }
         function.block_mut(node).unwrap().extend(new_stat);
         let arguments = function
             .remove_edges(node)
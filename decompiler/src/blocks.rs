@@ -0,0 +1,180 @@
+use std::hash::Hash;
+
+use ast::{RcLocal, Statement};
+use cfg::{
+    block::{BlockEdge, BranchType},
+    function::Function,
+};
+use petgraph::{stable_graph::NodeIndex, visit::EdgeRef, Direction};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::names::RegisterWrites;
+
+// the control flow between the instructions of a function, which is all that's needed to split
+// it into blocks
+pub trait Code {
+    // where execution can continue after the instruction at `pc`, which may be outside of the
+    // function if the bytecode was tampered with
+    fn successors(&self, pc: usize) -> Vec<(isize, BranchType)>;
+
+    // whether `pc` is the start of an instruction that can be executed
+    fn is_valid_pc(&self, pc: isize) -> bool;
+
+    // the instruction after the one at `pc`, skipping over the words that are its operands
+    fn next_pc(&self, pc: usize) -> usize {
+        pc + 1
+    }
+}
+
+// the block each leader starts, blocks are only created for reachable instructions
+pub struct BlockMap {
+    nodes: FxHashMap<usize, NodeIndex>,
+    // blocks holding only a comment, for jumps to pcs that can't be executed
+    invalid_nodes: FxHashMap<isize, NodeIndex>,
+}
+
+impl BlockMap {
+    // obfuscators put junk between reachable instructions and jump into the middle of what would
+    // otherwise be one block
+    pub fn new(code: &impl Code, function: &mut Function) -> Self {
+        let mut nodes = FxHashMap::default();
+        let mut invalid_nodes = FxHashMap::default();
+        let mut visited = FxHashSet::default();
+        let mut leaders = FxHashSet::default();
+        leaders.insert(0);
+        let mut stack = vec![0isize];
+        while let Some(pc) = stack.pop() {
+            if !code.is_valid_pc(pc) {
+                invalid_nodes.entry(pc).or_insert_with(|| {
+                    let node = function.new_block();
+                    function
+                        .block_mut(node)
+                        .unwrap()
+                        .push(ast::Comment::new(format!("jump to invalid pc {}", pc)).into());
+                    node
+                });
+                continue;
+            }
+            let pc = pc as usize;
+            if !visited.insert(pc) {
+                continue;
+            }
+            let successors = code.successors(pc);
+            let falls_through = matches!(
+                successors[..],
+                [(next, BranchType::Unconditional)] if next == code.next_pc(pc) as isize
+            );
+            for &(successor, _) in &successors {
+                if !falls_through {
+                    leaders.insert(successor);
+                }
+                stack.push(successor);
+            }
+        }
+        for leader in leaders {
+            if code.is_valid_pc(leader) {
+                nodes.insert(leader as usize, function.new_block());
+            }
+        }
+        Self {
+            nodes,
+            invalid_nodes,
+        }
+    }
+
+    // the block starting at `pc`, which must be a leader
+    pub fn node(&self, pc: usize) -> NodeIndex {
+        self.nodes[&pc]
+    }
+
+    // the block starting at `pc`, or the block standing in for an invalid destination
+    pub fn target(&self, pc: isize) -> NodeIndex {
+        match usize::try_from(pc).ok().and_then(|pc| self.nodes.get(&pc)) {
+            Some(&node) => node,
+            None => self.invalid_nodes[&pc],
+        }
+    }
+
+    // the first and last pc of each block, blocks end at branches or before another block starts
+    pub fn ranges(&self, code: &impl Code) -> Vec<(usize, usize)> {
+        let mut starts = self.nodes.keys().cloned().collect::<Vec<_>>();
+        starts.sort_unstable();
+        starts
+            .into_iter()
+            .map(|start| {
+                let mut end = start;
+                loop {
+                    let next = code.next_pc(end);
+                    let falls_through = matches!(
+                        code.successors(end)[..],
+                        [(successor, BranchType::Unconditional)] if successor == next as isize
+                    );
+                    if !falls_through
+                        || !code.is_valid_pc(next as isize)
+                        || self.nodes.contains_key(&next)
+                    {
+                        break;
+                    }
+                    end = next;
+                }
+                (start, end)
+            })
+            .collect()
+    }
+
+    // the edges out of the block ending at `end`
+    pub fn edges(&self, code: &impl Code, end: usize) -> Vec<(NodeIndex, BlockEdge)> {
+        code.successors(end)
+            .into_iter()
+            .map(|(successor, branch_type)| (self.target(successor), BlockEdge::new(branch_type)))
+            .collect()
+    }
+
+    // each statement is inserted on the edge from a block to its successor, at the start of the
+    // successor if that's its only predecessor or in a new block between them otherwise.
+    // they assign the loop variables at the start of a loop body
+    pub fn insert_between<R: Copy + Eq + Hash>(
+        &self,
+        function: &mut Function,
+        insert_between: FxHashMap<NodeIndex, (NodeIndex, Statement)>,
+        register_writes: &mut RegisterWrites<R>,
+        locals: &FxHashMap<R, RcLocal>,
+    ) {
+        let node_pcs = self
+            .nodes
+            .iter()
+            .map(|(&pc, &node)| (node, pc))
+            .collect::<FxHashMap<_, _>>();
+        for (node, (successor, stat)) in insert_between {
+            // the body is missing if the loop jumps to an invalid pc
+            let body_pc = node_pcs.get(&successor).copied();
+            if function.predecessor_blocks(successor).count() == 1 {
+                register_writes.shift(successor);
+                if let Some(body_pc) = body_pc {
+                    register_writes.record(locals, successor, 0, &stat, body_pc);
+                }
+                function.block_mut(successor).unwrap().insert(0, stat);
+            } else {
+                let between_node = function.new_block();
+                if let Some(body_pc) = body_pc {
+                    register_writes.record(locals, between_node, 0, &stat, body_pc);
+                }
+                function.block_mut(between_node).unwrap().push(stat);
+                function.set_edges(
+                    between_node,
+                    vec![(successor, BlockEdge::new(BranchType::Unconditional))],
+                );
+                for edge in function
+                    .graph()
+                    .edges_directed(node, Direction::Outgoing)
+                    .filter(|e| e.target() == successor)
+                    .map(|e| e.id())
+                    .collect::<Vec<_>>()
+                {
+                    let edge = function.graph_mut().remove_edge(edge).unwrap();
+                    function.graph_mut().add_edge(node, between_node, edge);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use ast::LocalRw;
+use cfg::function::Function;
+use petgraph::visit::{Dfs, EdgeRef};
+use rustc_hash::FxHashSet;
+
+fn label(node: petgraph::stable_graph::NodeIndex) -> ast::Label {
+    ast::Label(format!("l{}", node.index()))
+}
+
+// renders a lifted function as is, one labelled block after another with gotos between them.
+// this doesn't depend on ssa or structuring succeeding, so it's used when they panic
+pub(crate) fn unstructured(function: &Function, upvalues: &[ast::RcLocal]) -> ast::Block {
+    let entry = function.entry().unwrap();
+    let mut order = Vec::new();
+    let mut dfs = Dfs::new(function.graph(), entry);
+    while let Some(node) = dfs.next(function.graph()) {
+        order.push(node);
+    }
+    let targets = order
+        .iter()
+        .flat_map(|&node| function.successor_blocks(node))
+        .collect::<FxHashSet<_>>();
+
+    // declare every local up front so that no goto jumps into the scope of a local
+    let mut declared = function
+        .parameters
+        .iter()
+        .chain(upvalues)
+        .cloned()
+        .collect::<FxHashSet<_>>();
+    let locals = order
+        .iter()
+        .flat_map(|&node| function.block(node).unwrap().0.iter())
+        .flat_map(|statement| statement.values_written())
+        .filter(|&local| declared.insert(local.clone()))
+        .cloned()
+        .map(ast::LValue::Local)
+        .collect::<Vec<_>>();
+
+    let mut body = ast::Block::default();
+    if !locals.is_empty() {
+        let mut declaration = ast::Assign::new(locals, Vec::new());
+        declaration.prefix = true;
+        body.push(declaration.into());
+    }
+    for (i, &node) in order.iter().enumerate() {
+        if targets.contains(&node) {
+            body.push(label(node).into());
+        }
+        let mut block = function.block(node).unwrap().clone();
+        let next = order.get(i + 1).copied();
+        if let Some((then_edge, else_edge)) = function.conditional_edges(node) {
+            let then_label = label(then_edge.target());
+            let else_label = label(else_edge.target());
+            match block.pop() {
+                Some(ast::Statement::If(r#if)) => {
+                    body.extend(block.0);
+                    body.push(
+                        ast::If::new(
+                            r#if.condition,
+                            vec![ast::Goto::new(then_label).into()].into(),
+                            vec![ast::Goto::new(else_label).into()].into(),
+                        )
+                        .into(),
+                    );
+                }
+                // loop terminators don't have a lua equivalent, show where they branch to instead
+                statement => {
+                    body.extend(block.0.into_iter().chain(statement));
+                    body.push(ast::Comment::new(format!("then goto {}", then_label.0)).into());
+                    body.push(ast::Comment::new(format!("else goto {}", else_label.0)).into());
+                }
+            }
+        } else if let Some(edge) = function.unconditional_edge(node) {
+            body.extend(block.0);
+            if next != Some(edge.target()) {
+                body.push(ast::Goto::new(label(edge.target())).into());
+            }
+        } else {
+            body.extend(block.0);
+        }
+    }
+    body
+}
+
+// the lines of a comment explaining why a function failed to decompile
+pub(crate) fn failure_message(function_id: usize, panic_information: &str) -> Vec<String> {
+    let mut lines = vec!["failed to decompile".to_string()];
+    lines.extend(
+        format!(
+            "function {} panicked at '{}'",
+            function_id, panic_information
+        )
+        .split('\n')
+        .map(|s| s.to_string()),
+    );
+    lines
+}
@@ -0,0 +1,498 @@
+use ast::{
+    local_declarations::LocalDeclarer, name_locals::name_locals, replace_locals::replace_locals,
+    Traverse,
+};
+use by_address::ByAddress;
+use cfg::{
+    function::Function,
+    ssa::{
+        self,
+        structuring::{structure_conditionals, structure_jumps, structure_method_calls},
+    },
+};
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use petgraph::algo::dominators::simple_fast;
+use rustc_hash::FxHashMap;
+use triomphe::Arc;
+// std's instant panics on wasm
+use web_time::Instant;
+
+mod blocks;
+mod fallback;
+mod limits;
+mod names;
+mod output;
+
+pub use ast::formatter::LineMode;
+pub use blocks::{BlockMap, Code};
+pub use limits::Limits;
+pub use names::{debug_name, function_count, local_variables, RegisterWrites};
+pub use output::{DecompileOutput, Fallback, FunctionReport, FunctionStatus, Timings};
+
+pub struct LiftedFunction<P> {
+    pub function: Function,
+    pub upvalues: Vec<ast::RcLocal>,
+    // the closures created by this function and the prototypes they instantiate
+    pub child_functions: FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, P>,
+}
+
+// a front end for a bytecode format, lifts one function at a time into the cfg
+pub trait Lifter<'a> {
+    type Chunk: 'a;
+    type Prototype: Clone;
+
+    // whether `a.b(a, ...)` can be structured into `a:b(...)`. this isn't the case for
+    // formats with a dedicated namecall instruction since __namecall can observe the difference
+    const STRUCTURE_METHOD_CALLS: bool;
+
+    fn main(chunk: &'a Self::Chunk) -> Self::Prototype;
+
+    fn id(prototype: &Self::Prototype) -> usize;
+
+    // a listing of the prototype's instructions, shown in place of functions that can't be lifted
+    fn disassemble(chunk: &'a Self::Chunk, prototype: &Self::Prototype) -> Vec<String>;
+
+    // whether the prototype was lost to corruption, see `FunctionStatus::Missing`
+    fn is_missing(_chunk: &'a Self::Chunk, _prototype: &Self::Prototype) -> bool {
+        false
+    }
+
+    // prototypes that were recovered but can't be reached from main because their parent is
+    // missing, they're declared at the end of the main function instead
+    fn orphans(_chunk: &'a Self::Chunk) -> Vec<Self::Prototype> {
+        Vec::new()
+    }
+
+    fn lift(chunk: &'a Self::Chunk, prototype: Self::Prototype) -> LiftedFunction<Self::Prototype>;
+}
+
+pub fn decompile<'a, L: Lifter<'a>>(chunk: &'a L::Chunk, limits: &Limits) -> DecompileOutput {
+    let chunk_deadline = limits.chunk_timeout.map(|t| Instant::now() + t);
+    let mut timings = Timings::default();
+    let mut functions = Vec::new();
+    let mut upvalues = FxHashMap::default();
+
+    let now = Instant::now();
+    let main = Arc::<Mutex<ast::Function>>::default();
+    let mut lifted = Vec::new();
+    let mut stack = vec![(main.clone(), L::main(chunk))];
+    let orphans = L::orphans(chunk)
+        .into_iter()
+        .map(|prototype| {
+            let ast_function = Arc::<Mutex<ast::Function>>::default();
+            stack.push((ast_function.clone(), prototype));
+            ast_function
+        })
+        .collect::<Vec<_>>();
+    while let Some((ast_function, prototype)) = stack.pop() {
+        let function_id = L::id(&prototype);
+        ast_function.lock().id = function_id;
+        if L::is_missing(chunk, &prototype) {
+            let placeholder = format!("missing proto #{}", function_id);
+            if Arc::ptr_eq(&ast_function, &main) {
+                set_comment(&ast_function, vec![placeholder]);
+            } else {
+                let mut ast_function = ast_function.lock();
+                ast_function.is_variadic = true;
+                ast_function.placeholder = Some(placeholder);
+            }
+            functions.push(FunctionReport {
+                id: function_id,
+                name: None,
+                status: FunctionStatus::Missing,
+                fallback: None,
+            });
+            upvalues.insert(ByAddress(ast_function), Vec::new());
+            continue;
+        }
+        // lifting can be slow too, the remaining prototypes are only disassembled
+        if chunk_deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            let mut comment = vec![format!(
+                "function {} timed out before it was lifted",
+                function_id
+            )];
+            comment.extend(L::disassemble(chunk, &prototype));
+            set_comment(&ast_function, comment);
+            functions.push(FunctionReport {
+                id: function_id,
+                name: None,
+                status: FunctionStatus::TimedOut,
+                fallback: Some(Fallback::Disassembly),
+            });
+            upvalues.insert(ByAddress(ast_function), Vec::new());
+            continue;
+        }
+        match catch_panic(|| L::lift(chunk, prototype.clone())) {
+            Ok(LiftedFunction {
+                function,
+                upvalues: upvalues_in,
+                child_functions,
+            }) => {
+                lifted.push((ast_function, prototype, function, upvalues_in));
+                stack.extend(child_functions.into_iter().map(|(a, p)| (a.0, p)));
+            }
+            Err((message, backtrace)) => {
+                let mut comment = fallback::failure_message(function_id, &message);
+                comment.extend(L::disassemble(chunk, &prototype));
+                set_comment(&ast_function, comment);
+                functions.push(FunctionReport {
+                    id: function_id,
+                    name: None,
+                    status: FunctionStatus::Panicked { message, backtrace },
+                    fallback: Some(Fallback::Disassembly),
+                });
+                upvalues.insert(ByAddress(ast_function), Vec::new());
+            }
+        }
+    }
+    timings.lift = now.elapsed();
+
+    for (ast_function, prototype, function, upvalues_in) in lifted {
+        let function_id = function.id;
+        let function_name = function.name.clone();
+        // the function as lifted, shown unstructured if the rest of the pipeline panics
+        let lifted_function = function.clone();
+        let lifted_upvalues = upvalues_in.clone();
+        let deadline = limits
+            .function_timeout
+            .map(|t| Instant::now() + t)
+            .into_iter()
+            .chain(chunk_deadline)
+            .min();
+        let result = catch_panic(|| {
+            decompile_function(
+                ast_function.clone(),
+                function,
+                upvalues_in,
+                L::STRUCTURE_METHOD_CALLS,
+                limits.max_iterations,
+                deadline,
+                &mut timings,
+            )
+        });
+        match result {
+            Ok((ast_function, upvalues_in, timed_out)) => {
+                functions.push(FunctionReport {
+                    id: function_id,
+                    name: function_name,
+                    status: if timed_out {
+                        FunctionStatus::TimedOut
+                    } else {
+                        FunctionStatus::Ok
+                    },
+                    fallback: None,
+                });
+                upvalues.insert(ast_function, upvalues_in);
+            }
+            Err((message, backtrace)) => {
+                let comment = fallback::failure_message(function_id, &message);
+                let fallback = match catch_panic(|| {
+                    fallback::unstructured(&lifted_function, &lifted_upvalues)
+                }) {
+                    Ok(body) => {
+                        set_comment(&ast_function, comment);
+                        {
+                            let mut ast_function = ast_function.lock();
+                            ast_function.body.extend(body.0);
+                            ast_function.name = function_name.clone();
+                            ast_function.parameters = lifted_function.parameters;
+                            ast_function.parameter_types = lifted_function.parameter_types;
+                            ast_function.is_variadic = lifted_function.is_variadic;
+                            ast_function.is_native = lifted_function.is_native;
+                        }
+                        upvalues.insert(ByAddress(ast_function), lifted_upvalues);
+                        Fallback::Unstructured
+                    }
+                    Err(_) => {
+                        let mut comment = comment;
+                        comment.extend(L::disassemble(chunk, &prototype));
+                        set_comment(&ast_function, comment);
+                        upvalues.insert(ByAddress(ast_function), Vec::new());
+                        Fallback::Disassembly
+                    }
+                };
+                functions.push(FunctionReport {
+                    id: function_id,
+                    name: function_name,
+                    status: FunctionStatus::Panicked { message, backtrace },
+                    fallback: Some(fallback),
+                });
+            }
+        }
+    }
+
+    let now = Instant::now();
+    let main = ByAddress(main);
+    upvalues.remove(&main);
+    let main = Arc::try_unwrap(main.0).unwrap().into_inner();
+    let mut body = main.body;
+    for ast_function in orphans {
+        let function_id = ast_function.lock().id;
+        body.push(
+            ast::Comment::new(format!(
+                "recovered proto #{}, its parent is missing",
+                function_id
+            ))
+            .into(),
+        );
+        let local = ast::RcLocal::new(ast::Local::new(Some(format!("proto_{}", function_id))));
+        let closure = ast::Closure {
+            function: ByAddress(ast_function),
+            upvalues: Vec::new(),
+        };
+        let mut declaration = ast::Assign::new(vec![local.into()], vec![closure.into()]);
+        declaration.prefix = true;
+        body.push(declaration.into());
+    }
+    link_upvalues(&mut body, &mut upvalues);
+    name_locals(&mut body, false);
+    timings.name = now.elapsed();
+
+    let now = Instant::now();
+    let mut output = DecompileOutput {
+        source: String::new(),
+        block: body,
+        main_id: main.id,
+        is_native: main.is_native,
+        functions,
+        timings,
+    };
+    output.source = output.render(LineMode::Compact);
+    output.timings.render = now.elapsed();
+    output
+}
+
+// replaces the body of a function with a comment
+fn set_comment(ast_function: &Arc<Mutex<ast::Function>>, lines: Vec<String>) {
+    ast_function.lock().body = lines
+        .into_iter()
+        .map(|s| ast::Comment::new(s).into())
+        .collect::<Vec<_>>()
+        .into();
+}
+
+// runs `f`, returning the panic message and backtrace if it panics
+fn catch_panic<R>(f: impl FnOnce() -> R) -> Result<R, (String, Option<String>)> {
+    use std::{
+        backtrace::Backtrace,
+        cell::{Cell, RefCell},
+        panic,
+        sync::Once,
+    };
+
+    thread_local! {
+        static CATCHING: Cell<bool> = const { Cell::new(false) };
+        static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+    }
+    static INSTALL_HOOK: Once = Once::new();
+
+    // the hook is global and functions are decompiled in parallel, so it's installed once and
+    // only captures panics on threads that are inside `catch_panic`
+    INSTALL_HOOK.call_once(|| {
+        let prev_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CATCHING.with(Cell::get) {
+                let trace = Backtrace::capture();
+                BACKTRACE.with(move |b| b.borrow_mut().replace(trace));
+            } else {
+                prev_hook(info);
+            }
+        }));
+    });
+
+    let was_catching = CATCHING.with(|c| c.replace(true));
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(f));
+    CATCHING.with(|c| c.set(was_catching));
+
+    result.map_err(|e| {
+        let panic_information = match e.downcast::<String>() {
+            Ok(v) => *v,
+            Err(e) => match e.downcast::<&str>() {
+                Ok(v) => v.to_string(),
+                _ => "Unknown Source of Error".to_owned(),
+            },
+        };
+        let backtrace = BACKTRACE
+            .with(|b| b.borrow_mut().take())
+            .map(|b| b.to_string());
+        (panic_information, backtrace)
+    })
+}
+
+fn decompile_function(
+    ast_function: Arc<Mutex<ast::Function>>,
+    mut function: Function,
+    upvalues_in: Vec<ast::RcLocal>,
+    structure_method_calls_enabled: bool,
+    max_iterations: Option<usize>,
+    deadline: Option<Instant>,
+    timings: &mut Timings,
+) -> (
+    ByAddress<Arc<Mutex<ast::Function>>>,
+    Vec<ast::RcLocal>,
+    bool,
+) {
+    let now = Instant::now();
+    let (local_count, local_groups, upvalue_in_groups, upvalue_passed_groups) =
+        cfg::ssa::construct(&mut function, &upvalues_in);
+    let upvalue_to_group = upvalue_in_groups
+        .into_iter()
+        .chain(
+            upvalue_passed_groups
+                .into_iter()
+                .map(|m| (ast::RcLocal::default(), m)),
+        )
+        .flat_map(|(i, g)| g.into_iter().map(move |u| (u, i.clone())))
+        .collect::<IndexMap<_, _>>();
+    // TODO: do we even need this?
+    let local_to_group = local_groups
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, g)| g.into_iter().map(move |l| (l, i)))
+        .collect::<FxHashMap<_, _>>();
+    timings.construct += now.elapsed();
+
+    let now = Instant::now();
+    // TODO: REFACTOR: some way to write a macro that states
+    // if cfg::ssa::inline results in change then structure_jumps, structure_compound_conditionals,
+    // structure_for_loops and remove_unnecessary_params must run again.
+    // if structure_compound_conditionals results in change then dominators and post dominators
+    // must be recalculated.
+    // etc.
+    // the macro could also maybe generate an optimal ordering?
+    let out_of_time = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+    let mut iterations = 0;
+    let mut timed_out = false;
+    let mut changed = true;
+    // the function is valid between passes, so we can stop after any of them and still emit it
+    while changed {
+        if max_iterations.is_some_and(|max| iterations >= max) || out_of_time() {
+            timed_out = true;
+            break;
+        }
+        iterations += 1;
+        changed = false;
+
+        let dominators = simple_fast(function.graph(), function.entry().unwrap());
+        changed |= structure_jumps(&mut function, &dominators);
+        if out_of_time() {
+            timed_out = true;
+            break;
+        }
+
+        ssa::inline::inline(&mut function, &local_to_group, &upvalue_to_group);
+        if out_of_time() {
+            timed_out = true;
+            break;
+        }
+
+        if structure_conditionals(&mut function)
+        // || {
+        //     let post_dominators = post_dominators(function.graph_mut());
+        //     structure_for_loops(&mut function, &dominators, &post_dominators)
+        // }
+            || (structure_method_calls_enabled && structure_method_calls(&mut function))
+        {
+            changed = true;
+        }
+        if out_of_time() {
+            timed_out = true;
+            break;
+        }
+
+        let mut local_map = FxHashMap::default();
+        // TODO: loop until returns false?
+        if ssa::construct::remove_unnecessary_params(&mut function, &mut local_map) {
+            changed = true;
+        }
+        ssa::construct::apply_local_map(&mut function, local_map);
+    }
+    timings.structure += now.elapsed();
+
+    // cfg::dot::render_to(&function, &mut std::io::stdout()).unwrap();
+    let now = Instant::now();
+    ssa::Destructor::new(
+        &mut function,
+        upvalue_to_group,
+        upvalues_in.iter().cloned().collect(),
+        local_count,
+    )
+    .destruct();
+    timings.destruct += now.elapsed();
+
+    let now = Instant::now();
+    let name = function.name.take();
+    let params = std::mem::take(&mut function.parameters);
+    let parameter_types = std::mem::take(&mut function.parameter_types);
+    let is_variadic = function.is_variadic;
+    let is_native = function.is_native;
+    let block = Arc::new(restructure::lift(function).into());
+    LocalDeclarer::default().declare_locals(
+        // TODO: why does block.clone() not work?
+        Arc::clone(&block),
+        &upvalues_in.iter().chain(params.iter()).cloned().collect(),
+    );
+
+    {
+        let mut ast_function = ast_function.lock();
+        ast_function.body = Arc::try_unwrap(block).unwrap().into_inner();
+        ast_function.name = name;
+        ast_function.parameters = params;
+        ast_function.parameter_types = parameter_types;
+        ast_function.is_variadic = is_variadic;
+        ast_function.is_native = is_native;
+    }
+    timings.restructure += now.elapsed();
+    (ByAddress(ast_function), upvalues_in, timed_out)
+}
+
+fn link_upvalues(
+    body: &mut ast::Block,
+    upvalues: &mut FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, Vec<ast::RcLocal>>,
+) {
+    for stat in &mut body.0 {
+        stat.traverse_rvalues(&mut |rvalue| {
+            if let ast::RValue::Closure(closure) = rvalue {
+                let old_upvalues = &upvalues[&closure.function];
+                let mut function = closure.function.lock();
+                // TODO: inefficient, try constructing a map of all up -> new up first
+                // and then call replace_locals on main body
+                let mut local_map =
+                    FxHashMap::with_capacity_and_hasher(old_upvalues.len(), Default::default());
+                for (old, new) in
+                    old_upvalues
+                        .iter()
+                        .zip(closure.upvalues.iter().map(|u| match u {
+                            ast::Upvalue::Copy(l) | ast::Upvalue::Ref(l) => l,
+                        }))
+                {
+                    // println!("{} -> {}", old, new);
+                    new.inherit_name(old);
+                    local_map.insert(old.clone(), new.clone());
+                }
+                link_upvalues(&mut function.body, upvalues);
+                replace_locals(&mut function.body, &local_map);
+            }
+        });
+        match stat {
+            ast::Statement::If(r#if) => {
+                link_upvalues(&mut r#if.then_block.lock(), upvalues);
+                link_upvalues(&mut r#if.else_block.lock(), upvalues);
+            }
+            ast::Statement::While(r#while) => {
+                link_upvalues(&mut r#while.block.lock(), upvalues);
+            }
+            ast::Statement::Repeat(repeat) => {
+                link_upvalues(&mut repeat.block.lock(), upvalues);
+            }
+            ast::Statement::NumericFor(numeric_for) => {
+                link_upvalues(&mut numeric_for.block.lock(), upvalues);
+            }
+            ast::Statement::GenericFor(generic_for) => {
+                link_upvalues(&mut generic_for.block.lock(), upvalues);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use std::time::Duration;
+
+// bounds on the structuring fixpoint, `None` means unbounded.
+// a function that exceeds a limit is emitted as structured as it got and reported as timed out
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    // the maximum number of structuring passes over a single function
+    pub max_iterations: Option<usize>,
+    // the time each function may spend being structured
+    pub function_timeout: Option<Duration>,
+    // the time the whole chunk may take, functions not lifted by the deadline are only disassembled
+    pub chunk_timeout: Option<Duration>,
+}
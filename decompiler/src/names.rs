@@ -0,0 +1,128 @@
+use std::{hash::Hash, ops::Range};
+
+use ast::{formatter::Formatter, LocalRw, RcLocal, Statement};
+use cfg::function::Function;
+use petgraph::stable_graph::NodeIndex;
+use rustc_hash::FxHashMap;
+
+// a name from the debug info, if it can be written in source. tampered bytecode can have names
+// that are empty or keywords, which are left to be generated instead
+pub fn debug_name(bytes: &[u8]) -> Option<String> {
+    Formatter::<String>::is_valid_name(bytes).then(|| String::from_utf8_lossy(bytes).into_owned())
+}
+
+// the local variables with a name that can be written in source, and the register and pcs each
+// one is in scope for. the register isn't stored, but a local takes the first register not held
+// by a local that is still in scope where it starts
+pub fn local_variables<'a, R: From<u8>>(
+    locals: impl IntoIterator<Item = (&'a [u8], Range<u32>)>,
+) -> Vec<(R, Range<usize>, &'a [u8])> {
+    let locals = locals.into_iter().collect::<Vec<_>>();
+    locals
+        .iter()
+        .enumerate()
+        .map(|(index, (_, range))| {
+            locals[..index]
+                .iter()
+                .filter(|(_, r)| r.contains(&range.start))
+                .count() as u8
+        })
+        .zip(&locals)
+        // including the for loop state, which has names like `(for state)`
+        .filter(|(_, (name, _))| Formatter::<String>::is_valid_name(name))
+        .map(|(register, (name, range))| {
+            (
+                R::from(register),
+                range.start as usize..range.end as usize,
+                *name,
+            )
+        })
+        .collect()
+}
+
+// the writes to registers holding locals, which are named after the local variables in the debug
+// info once the function is lifted
+pub struct RegisterWrites<R> {
+    // (register, pc, block, statement index, local)
+    writes: Vec<(R, usize, NodeIndex, usize, RcLocal)>,
+}
+
+impl<R> Default for RegisterWrites<R> {
+    fn default() -> Self {
+        Self { writes: Vec::new() }
+    }
+}
+
+impl<R: Copy + Eq + Hash> RegisterWrites<R> {
+    pub fn record(
+        &mut self,
+        locals: &FxHashMap<R, RcLocal>,
+        node: NodeIndex,
+        statement_index: usize,
+        statement: &Statement,
+        written_pc: usize,
+    ) {
+        for local in statement.values_written() {
+            if let Some((&register, _)) = locals.iter().find(|(_, l)| *l == local) {
+                self.writes
+                    .push((register, written_pc, node, statement_index, local.clone()));
+            }
+        }
+    }
+
+    // a statement was inserted at the start of `node`
+    pub(crate) fn shift(&mut self, node: NodeIndex) {
+        for write in &mut self.writes {
+            if write.2 == node {
+                write.3 += 1;
+            }
+        }
+    }
+
+    // a register write belongs to the local variable whose scope it is in,
+    // or to the next local variable in that register if it is the last write before its scope
+    pub fn name(self, local_variables: &[(R, Range<usize>, &[u8])], function: &mut Function) {
+        if local_variables.is_empty() {
+            return;
+        }
+        let mut write_pcs = FxHashMap::<R, Vec<usize>>::default();
+        for &(register, pc, ..) in &self.writes {
+            write_pcs.entry(register).or_default().push(pc);
+        }
+        for pcs in write_pcs.values_mut() {
+            pcs.sort_unstable();
+        }
+        for (register, pc, node, statement_index, local) in self.writes {
+            let pcs = &write_pcs[&register];
+            let next_write_pc = pcs.get(pcs.partition_point(|&p| p <= pc)).copied();
+            let local_variable = local_variables
+                .iter()
+                .filter(|(r, range, _)| *r == register && range.contains(&pc))
+                .max_by_key(|(_, range, _)| range.start)
+                .or_else(|| {
+                    local_variables
+                        .iter()
+                        .filter(|(r, range, _)| {
+                            *r == register
+                                && pc < range.start
+                                && !matches!(next_write_pc, Some(n) if n <= range.start)
+                        })
+                        .min_by_key(|(_, range, _)| range.start)
+                });
+            if let Some(&(_, _, name)) = local_variable {
+                function.local_names.insert(
+                    (node, statement_index, local),
+                    String::from_utf8_lossy(name).into_owned(),
+                );
+            }
+        }
+    }
+}
+
+// the number of functions in the tree rooted at `function`, which front ends number in preorder
+pub fn function_count<F>(function: &F, closures: fn(&F) -> &[F]) -> usize {
+    1 + closures(function)
+        .iter()
+        .map(|closure| function_count(closure, closures))
+        .sum::<usize>()
+}
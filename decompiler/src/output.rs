@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use ast::{
+    formatter::{Formatter, LineMode},
+    source_map::SourceMap,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FunctionStatus {
+    Ok,
+    Panicked {
+        message: String,
+        backtrace: Option<String>,
+    },
+    TimedOut,
+    // the front end couldn't read the function, a placeholder was emitted in its place
+    Missing,
+}
+
+// what was emitted in place of a function that failed to decompile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fallback {
+    // the lifted blocks joined with gotos, used when lifting succeeded
+    Unstructured,
+    // the function's instructions as comments
+    Disassembly,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionReport {
+    // the front end's id for the function, for luau this is the index in the chunk's function list
+    pub id: usize,
+    pub name: Option<String>,
+    pub status: FunctionStatus,
+    pub fallback: Option<Fallback>,
+}
+
+// time spent in each phase of the pipeline, summed over all functions
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    pub deserialize: Duration,
+    pub lift: Duration,
+    pub construct: Duration,
+    pub structure: Duration,
+    pub destruct: Duration,
+    pub restructure: Duration,
+    pub name: Duration,
+    pub render: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.deserialize
+            + self.lift
+            + self.construct
+            + self.structure
+            + self.destruct
+            + self.restructure
+            + self.name
+            + self.render
+    }
+}
+
+#[derive(Debug)]
+pub struct DecompileOutput {
+    pub source: String,
+    pub block: ast::Block,
+    // the front end's id for the main function
+    pub main_id: usize,
+    // the main function is native, rendered as a `--!native` directive. only main needs to be:
+    // the directive marks main alone, and the vm compiles every function of such a module
+    // natively whether or not it is marked `@native` itself
+    pub is_native: bool,
+    // one report per function, the main function first
+    pub functions: Vec<FunctionReport>,
+    pub timings: Timings,
+}
+
+impl DecompileOutput {
+    pub fn is_ok(&self) -> bool {
+        self.functions
+            .iter()
+            .all(|f| f.status == FunctionStatus::Ok)
+    }
+
+    // renders the block again, `source` is rendered with `LineMode::Compact`
+    pub fn render(&self, line_mode: LineMode) -> String {
+        let mut source = String::new();
+        Formatter::format(&self.block, &mut source, Default::default(), line_mode).unwrap();
+        self.add_directives(&mut source, line_mode);
+        source
+    }
+
+    // renders the block along with a map from the output back to the instructions
+    pub fn render_with_source_map(&self, line_mode: LineMode) -> (String, SourceMap) {
+        let mut source = String::new();
+        let mut source_map = SourceMap::default();
+        Formatter::format_with_source_map(
+            &self.block,
+            self.main_id,
+            &mut source,
+            Default::default(),
+            line_mode,
+            &mut source_map,
+        )
+        .unwrap();
+        if self.add_directives(&mut source, line_mode) {
+            for mapping in &mut source_map.mappings {
+                mapping.start.line += 1;
+                mapping.end.line += 1;
+            }
+        }
+        (source, source_map)
+    }
+
+    // returns whether the rest of the source moved down a line
+    fn add_directives(&self, source: &mut String, line_mode: LineMode) -> bool {
+        if !self.is_native {
+            return false;
+        }
+        source.insert_str(0, "--!native\n");
+        // the directive was on the first line, which the layout left blank
+        if line_mode == LineMode::Preserve && source[10..].starts_with('\n') {
+            source.remove(10);
+            false
+        } else {
+            true
+        }
+    }
+
+    pub fn failed_functions(&self) -> impl Iterator<Item = &FunctionReport> {
+        self.functions
+            .iter()
+            .filter(|f| f.status != FunctionStatus::Ok)
+    }
+}
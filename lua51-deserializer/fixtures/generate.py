@@ -0,0 +1,145 @@
+# regenerates the header fixtures from the one compiled by a stock 64-bit luac:
+#   luac5.1 -o header_le_int4_size8_f64.luac header.lua && python3 generate.py
+# each fixture holds the same function re-encoded for one combination of endianness,
+# int width, size_t width and number format
+import itertools
+import struct
+
+NUMBER_FORMATS = {"f64": (8, False), "f32": (4, False), "i32": (4, True), "i64": (8, True)}
+
+
+class Reader:
+    def __init__(self, data):
+        self.data = data
+        self.offset = 0
+        header = data[:12]
+        assert header[:6] == b"\x1bLuaQ\x00"
+        self.little = header[6] == 1
+        self.int_width, self.size_t_width = header[7], header[8]
+        self.number_width, self.integral = header[10], header[11] == 1
+        self.offset = 12
+
+    def take(self, length):
+        value = self.data[self.offset : self.offset + length]
+        self.offset += length
+        return value
+
+    def unsigned(self, width):
+        return int.from_bytes(self.take(width), "little" if self.little else "big")
+
+    def byte(self):
+        return self.take(1)[0]
+
+    def int(self):
+        return self.unsigned(self.int_width)
+
+    def string(self):
+        return self.take(self.unsigned(self.size_t_width))
+
+    def number(self):
+        assert not self.integral and self.number_width == 8
+        return struct.unpack("<d" if self.little else ">d", self.take(8))[0]
+
+    def function(self):
+        function = {
+            "source": self.string(),
+            "lines": (self.int(), self.int()),
+            "sizes": self.take(4),
+            "code": [self.unsigned(4) for _ in range(self.int())],
+        }
+        constants = []
+        for _ in range(self.int()):
+            kind = self.byte()
+            if kind == 0:
+                constants.append((kind, None))
+            elif kind == 1:
+                constants.append((kind, self.byte()))
+            elif kind == 3:
+                constants.append((kind, self.number()))
+            else:
+                constants.append((kind, self.string()))
+        function["constants"] = constants
+        function["closures"] = [self.function() for _ in range(self.int())]
+        function["positions"] = [self.int() for _ in range(self.int())]
+        function["locals"] = [(self.string(), self.int(), self.int()) for _ in range(self.int())]
+        function["upvalues"] = [self.string() for _ in range(self.int())]
+        return function
+
+
+class Writer:
+    def __init__(self, little, int_width, size_t_width, number_format):
+        self.little = little
+        self.int_width, self.size_t_width = int_width, size_t_width
+        self.number_width, self.integral = NUMBER_FORMATS[number_format]
+        self.output = bytearray(b"\x1bLuaQ\x00")
+        self.output += bytes(
+            [little, int_width, size_t_width, 4, self.number_width, self.integral]
+        )
+
+    def unsigned(self, value, width):
+        self.output += value.to_bytes(width, "little" if self.little else "big")
+
+    def int(self, value):
+        self.unsigned(value, self.int_width)
+
+    def string(self, value):
+        self.unsigned(len(value), self.size_t_width)
+        self.output += value
+
+    def number(self, value):
+        if self.integral:
+            assert value == int(value)
+            self.output += int(value).to_bytes(
+                self.number_width, "little" if self.little else "big", signed=True
+            )
+        else:
+            format = {4: "f", 8: "d"}[self.number_width]
+            packed = struct.pack(("<" if self.little else ">") + format, value)
+            assert struct.unpack(("<" if self.little else ">") + format, packed)[0] == value
+            self.output += packed
+
+    def list(self, items, write):
+        self.int(len(items))
+        for item in items:
+            write(item)
+
+    def function(self, function):
+        self.string(function["source"])
+        self.int(function["lines"][0])
+        self.int(function["lines"][1])
+        self.output += function["sizes"]
+        self.list(function["code"], lambda i: self.unsigned(i, 4))
+
+        def constant(constant):
+            kind, value = constant
+            self.output.append(kind)
+            if kind == 1:
+                self.output.append(value)
+            elif kind == 3:
+                self.number(value)
+            elif kind == 4:
+                self.string(value)
+
+        self.list(function["constants"], constant)
+        self.list(function["closures"], self.function)
+        self.list(function["positions"], self.int)
+
+        def local(local):
+            self.string(local[0])
+            self.int(local[1])
+            self.int(local[2])
+
+        self.list(function["locals"], local)
+        self.list(function["upvalues"], self.string)
+
+
+function = Reader(open("header_le_int4_size8_f64.luac", "rb").read()).function()
+for little, int_width, size_t_width, number_format in itertools.product(
+    [True, False], [4, 8], [4, 8], NUMBER_FORMATS
+):
+    writer = Writer(little, int_width, size_t_width, number_format)
+    writer.function(function)
+    name = "header_{}_int{}_size{}_{}.luac".format(
+        "le" if little else "be", int_width, size_t_width, number_format
+    )
+    open(name, "wb").write(writer.output)
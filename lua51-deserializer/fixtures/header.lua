@@ -0,0 +1,19 @@
+local greeting = "hello"
+local function add(a, b)
+	return a + b
+end
+local t = { 1, 2, 3, name = "t" }
+for i = 1, 10, 2 do
+	t[#t + 1] = add(i, 100)
+end
+local function counter()
+	local count = 0
+	return function()
+		count = count + 1
+		return count
+	end
+end
+if t.name == greeting then
+	print(greeting, -7, 65536)
+end
+return counter, add, t
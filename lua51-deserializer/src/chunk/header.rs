@@ -1,31 +1,36 @@
 use nom::{
-    bytes::complete::tag,
+    bytes::complete::{tag, take},
     error::{Error, ErrorKind, ParseError},
     number::complete::le_u8,
     Err, IResult,
 };
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Endianness {
     Big,
     Little,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
     Official,
 }
 
-#[derive(Debug)]
+// describes how the rest of the chunk is encoded, every parser after the header takes it
+#[derive(Debug, Clone, Copy)]
 pub struct Header {
-    pub(crate) version_number: u8,
-    pub(crate) format: Format,
-    pub(crate) endianness: Endianness,
-    pub(crate) int_width: u8,
-    pub(crate) size_t_width: u8,
-    pub(crate) instr_width: u8,
-    pub(crate) number_width: u8,
-    pub(crate) number_is_integral: bool,
+    pub version_number: u8,
+    pub format: Format,
+    pub endianness: Endianness,
+    pub int_width: u8,
+    pub size_t_width: u8,
+    pub instr_width: u8,
+    pub number_width: u8,
+    pub number_is_integral: bool,
+}
+
+fn fail<T>(input: &[u8], kind: ErrorKind) -> IResult<&[u8], T> {
+    Err(Err::Failure(Error::from_error_kind(input, kind)))
 }
 
 impl Header {
@@ -34,19 +39,13 @@ impl Header {
         let (input, version_number) = le_u8(input)?;
         let (input, format) = match le_u8(input)? {
             (input, 0) => Ok((input, Format::Official)),
-            _ => Err(Err::Failure(Error::from_error_kind(
-                input,
-                ErrorKind::Switch,
-            ))),
+            _ => fail(input, ErrorKind::Switch),
         }?;
         // TODO: try_into instead
         let (input, endianness) = match le_u8(input)? {
             (input, 0) => Ok((input, Endianness::Big)),
             (input, 1) => Ok((input, Endianness::Little)),
-            _ => Err(Err::Failure(Error::from_error_kind(
-                input,
-                ErrorKind::Switch,
-            ))),
+            _ => fail(input, ErrorKind::Switch),
         }?;
         let (input, int_width) = le_u8(input)?;
         let (input, size_t_width) = le_u8(input)?;
@@ -55,24 +54,82 @@ impl Header {
         let (input, number_is_integral) = match le_u8(input)? {
             (input, 0) => Ok((input, false)),
             (input, 1) => Ok((input, true)),
-            _ => Err(Err::Failure(Error::from_error_kind(
-                input,
-                ErrorKind::Switch,
-            ))),
+            _ => fail(input, ErrorKind::Switch),
         }?;
 
-        Ok((
-            input,
-            Self {
-                version_number,
-                format,
-                endianness,
-                int_width,
-                size_t_width,
-                instr_width,
-                number_width,
-                number_is_integral,
-            },
-        ))
+        let header = Self {
+            version_number,
+            format,
+            endianness,
+            int_width,
+            size_t_width,
+            instr_width,
+            number_width,
+            number_is_integral,
+        };
+        if !header.is_supported() {
+            return fail(input, ErrorKind::Verify);
+        }
+        Ok((input, header))
+    }
+
+    // integers wider than 8 bytes don't exist, and floats are either a float or a double
+    fn is_supported(&self) -> bool {
+        let is_integer_width = |width| matches!(width, 1 | 2 | 4 | 8);
+        self.version_number == 0x51
+            && is_integer_width(self.int_width)
+            && is_integer_width(self.size_t_width)
+            && self.instr_width == 4
+            && if self.number_is_integral {
+                is_integer_width(self.number_width)
+            } else {
+                matches!(self.number_width, 4 | 8)
+            }
+    }
+
+    fn parse_unsigned<'a>(&self, input: &'a [u8], width: u8) -> IResult<&'a [u8], u64> {
+        let (input, bytes) = take(width)(input)?;
+        let push = |value: u64, &byte: &u8| value << 8 | byte as u64;
+        let value = match self.endianness {
+            Endianness::Big => bytes.iter().fold(0, push),
+            Endianness::Little => bytes.iter().rev().fold(0, push),
+        };
+        Ok((input, value))
+    }
+
+    fn parse_u32<'a>(&self, input: &'a [u8], width: u8) -> IResult<&'a [u8], u32> {
+        let (rest, value) = self.parse_unsigned(input, width)?;
+        match u32::try_from(value) {
+            Ok(value) => Ok((rest, value)),
+            Err(_) => fail(input, ErrorKind::TooLarge),
+        }
+    }
+
+    // an `int`, used for counts, line numbers and pcs
+    pub(crate) fn parse_int<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], u32> {
+        self.parse_u32(input, self.int_width)
+    }
+
+    // a `size_t`, used for string lengths
+    pub(crate) fn parse_size_t<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], u32> {
+        self.parse_u32(input, self.size_t_width)
+    }
+
+    pub(crate) fn parse_instruction<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], u32> {
+        self.parse_u32(input, self.instr_width)
+    }
+
+    pub(crate) fn parse_number<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], f64> {
+        let (input, value) = self.parse_unsigned(input, self.number_width)?;
+        let value = match (self.number_is_integral, self.number_width) {
+            // sign extended from the number's width
+            (true, width) => {
+                let shift = 64 - width as u32 * 8;
+                ((value << shift) as i64 >> shift) as f64
+            }
+            (false, 4) => f32::from_bits(value as u32) as f64,
+            (false, _) => f64::from_bits(value),
+        };
+        Ok((input, value))
     }
 }
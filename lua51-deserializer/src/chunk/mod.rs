@@ -1,35 +1,53 @@
-use std::mem;
-
 use nom::IResult;
 
 pub use header::Header;
 
-use crate::{
-    chunk::header::{Endianness, Format},
-    function::Function,
-};
+use crate::function::Function;
 
 pub mod header;
 
 #[derive(Debug)]
 pub struct Chunk<'a> {
+    pub header: Header,
     pub function: Function<'a>,
 }
 
 impl<'a> Chunk<'a> {
-    pub fn parse(input: &'a [u8]) -> IResult<&[u8], Self> {
+    pub fn parse(input: &'a [u8]) -> IResult<&'a [u8], Self> {
         let (input, header) = Header::parse(input)?;
-        // TODO: pass header to Function::parse
-        assert_eq!(header.version_number, 0x51);
-        assert_eq!(header.format, Format::Official);
-        assert_eq!(header.endianness, Endianness::Little);
-        assert_eq!(header.int_width as usize, mem::size_of::<i32>());
-        assert_eq!(header.size_t_width as usize, mem::size_of::<u32>());
-        assert_eq!(header.instr_width as usize, mem::size_of::<u32>());
-        assert_eq!(header.number_width as usize, mem::size_of::<f64>());
-        assert!(!header.number_is_integral);
-        let (input, function) = Function::parse(input)?;
-
-        Ok((input, Self { function }))
+        let (input, function) = Function::parse(input, &header)?;
+
+        Ok((input, Self { header, function }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    // every fixture is the same chunk, re-encoded with a different header
+    #[test]
+    fn parses_every_header() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let expected = fs::read(fixtures.join("header_le_int4_size8_f64.luac")).unwrap();
+        let (_, expected) = Chunk::parse(&expected).unwrap();
+        let expected = format!("{:?}", expected.function);
+
+        let mut parsed = 0;
+        for entry in fs::read_dir(&fixtures).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            if !name.starts_with("header_") || path.extension().unwrap() != "luac" {
+                continue;
+            }
+            let input = fs::read(&path).unwrap();
+            let (rest, chunk) = Chunk::parse(&input).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert!(rest.is_empty(), "{}: trailing bytes", name);
+            assert_eq!(format!("{:?}", chunk.function), expected, "{}", name);
+            parsed += 1;
+        }
+        assert_eq!(parsed, 32);
     }
 }
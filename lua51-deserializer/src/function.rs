@@ -1,11 +1,7 @@
-use nom::{
-    combinator::opt,
-    multi::count,
-    number::complete::{le_u32, le_u8},
-    IResult,
-};
+use nom::{combinator::opt, multi::count, number::complete::le_u8, IResult};
 
 use crate::{
+    chunk::Header,
     instruction::{position::Position, Instruction},
     local::Local,
     value::{self, Value},
@@ -28,24 +24,46 @@ pub struct Function<'a> {
     pub number_of_parameters: u8,
 }
 
+fn parse_code<'a>(
+    mut input: &'a [u8],
+    header: &Header,
+    length: usize,
+) -> IResult<&'a [u8], Vec<Instruction>> {
+    let mut code = Vec::new();
+    while code.len() < length {
+        let (rest, instruction) = match code.last_mut() {
+            Some(Instruction::SetList { block_number, .. }) if *block_number == 0 => {
+                let (rest, data) = header.parse_instruction(input)?;
+                *block_number = data;
+                (rest, Instruction::Data(data))
+            }
+            _ => Instruction::parse(input, header)?,
+        };
+        code.push(instruction);
+        input = rest;
+    }
+    Ok((input, code))
+}
+
 impl<'a> Function<'a> {
-    pub fn parse(input: &'a [u8]) -> IResult<&'a [u8], Self> {
-        let (input, name) = value::parse_string(input)?;
-        let (input, line_defined) = le_u32(input)?;
-        let (input, last_line_defined) = le_u32(input)?;
+    pub fn parse(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Self> {
+        let (input, name) = value::parse_string(input, header)?;
+        let (input, line_defined) = header.parse_int(input)?;
+        let (input, last_line_defined) = header.parse_int(input)?;
         let (input, number_of_upvalues) = le_u8(input)?;
         let (input, number_of_parameters) = le_u8(input)?;
         let (input, vararg_flag) = le_u8(input)?;
         let (input, maximum_stack_size) = le_u8(input)?;
-        let (input, code_length) = le_u32(input)?;
-        let (input, code) = count(Instruction::parse, code_length as usize)(input)?;
-        let (input, constants_length) = le_u32(input)?;
-        let (input, constants) = count(Value::parse, constants_length as usize)(input)?;
-        let (input, closures_length) = le_u32(input)?;
-        let (input, closures) = count(Self::parse, closures_length as usize)(input)?;
-        let (input, positions) = opt(Position::parse)(input)?;
-        let (input, locals) = opt(Local::parse_list)(input)?;
-        let (input, upvalues) = opt(value::parse_strings)(input)?;
+        let (input, code_length) = header.parse_int(input)?;
+        let (input, code) = parse_code(input, header, code_length as usize)?;
+        let (input, constants_length) = header.parse_int(input)?;
+        let (input, constants) =
+            count(|i| Value::parse(i, header), constants_length as usize)(input)?;
+        let (input, closures_length) = header.parse_int(input)?;
+        let (input, closures) = count(|i| Self::parse(i, header), closures_length as usize)(input)?;
+        let (input, positions) = opt(|i| Position::parse(i, header))(input)?;
+        let (input, locals) = opt(|i| Local::parse_list(i, header))(input)?;
+        let (input, upvalues) = opt(|i| value::parse_strings(i, header))(input)?;
 
         Ok((
             input,
@@ -2,12 +2,14 @@ use nom::{
     error::{Error, ErrorKind, ParseError},
     Err, IResult,
 };
-use num_traits::ToPrimitive;
+use num_traits::FromPrimitive;
 
 use argument::{Constant, Function, Register, RegisterOrConstant, Upvalue};
 use layout::Layout;
 use operation_code::OperationCode;
 
+use crate::chunk::Header;
+
 pub mod argument;
 mod layout;
 mod operation_code;
@@ -17,14 +19,18 @@ pub mod position;
 struct RawInstruction(OperationCode, Layout);
 
 impl RawInstruction {
-    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
-        // TODO: we read the operation code and then the instruction including the operation code
-        // while parsing the layout.
-        // we should instead read the instruction here and pass it to Layout::parse
-        let operation_code = OperationCode::parse(input).map(|r| r.1)?;
-        let (input, layout) = Layout::parse(input, operation_code.to_u8().unwrap())?;
+    pub fn parse<'a>(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Self> {
+        let (rest, instruction) = header.parse_instruction(input)?;
+        // the operation code is in the low bits, which aren't the first byte in big endian chunks
+        let Some(operation_code) = OperationCode::from_u32(instruction & 0x3F) else {
+            return Err(Err::Failure(Error::from_error_kind(
+                input,
+                ErrorKind::Switch,
+            )));
+        };
+        let layout = Layout::decode(instruction, operation_code.instruction_layout());
 
-        Ok((input, Self(operation_code, layout)))
+        Ok((rest, Self(operation_code, layout)))
     }
 }
 
@@ -190,7 +196,7 @@ pub enum Instruction {
     SetList {
         table: Register,
         number_of_elements: u8,
-        block_number: u8,
+        block_number: u32,
     },
     Close(Register),
     Closure {
@@ -198,11 +204,14 @@ pub enum Instruction {
         function: Function,
     },
     VarArg(Register, u8),
+    // the word after a SETLIST with a block number too large for its C operand, which holds the
+    // block number instead of an instruction
+    Data(u32),
 }
 
 impl Instruction {
-    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
-        let (input, instruction) = RawInstruction::parse(input)?;
+    pub fn parse<'a>(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Self> {
+        let (input, instruction) = RawInstruction::parse(input, header)?;
         let instruction = match instruction {
             RawInstruction(OperationCode::Move, Layout::BC { a, b, .. }) => Self::Move {
                 destination: Register(a),
@@ -217,8 +226,8 @@ impl Instruction {
             RawInstruction(OperationCode::LoadBoolean, Layout::BC { a, b, c }) => {
                 Self::LoadBoolean {
                     destination: Register(a),
-                    value: b == 1,
-                    skip_next: c == 1,
+                    value: b != 0,
+                    skip_next: c != 0,
                 }
             }
             RawInstruction(OperationCode::LoadNil, Layout::BC { a, b, .. }) => {
@@ -383,7 +392,7 @@ impl Instruction {
             RawInstruction(OperationCode::SetList, Layout::BC { a, b, c }) => Self::SetList {
                 table: Register(a),
                 number_of_elements: b as u8,
-                block_number: c as u8,
+                block_number: c as u32,
             },
             RawInstruction(OperationCode::Close, Layout::BC { a, .. }) => Self::Close(Register(a)),
             RawInstruction(OperationCode::Closure, Layout::BX { a, b_x }) => Self::Closure {
@@ -1,11 +1,5 @@
 use crate::instruction::layout::LayoutDiscriminants;
-use nom::{
-    error::{Error, ErrorKind, ParseError},
-    number::complete::le_u8,
-    Err, IResult,
-};
 use num_derive::{FromPrimitive, ToPrimitive};
-use num_traits::FromPrimitive;
 
 #[derive(Debug, FromPrimitive, ToPrimitive)]
 pub enum OperationCode {
@@ -50,24 +44,6 @@ pub enum OperationCode {
 }
 
 impl OperationCode {
-    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
-        let (input, operation_code) = le_u8(input)?;
-        let operation_code = operation_code & 0x3F;
-
-        Ok((
-            input,
-            match FromPrimitive::from_u8(operation_code) {
-                None => {
-                    return Err(Err::Failure(Error::from_error_kind(
-                        input,
-                        ErrorKind::Switch,
-                    )))
-                }
-                Some(operation_code) => operation_code,
-            },
-        ))
-    }
-
     pub fn instruction_layout(&self) -> LayoutDiscriminants {
         /*
            0 = BC
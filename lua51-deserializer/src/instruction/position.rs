@@ -1,4 +1,6 @@
-use nom::{multi::count, number::complete::le_u32, IResult};
+use nom::{multi::count, IResult};
+
+use crate::chunk::Header;
 
 #[derive(Debug)]
 pub struct Position {
@@ -7,9 +9,10 @@ pub struct Position {
 }
 
 impl Position {
-    pub fn parse(input: &[u8]) -> IResult<&[u8], Vec<Self>> {
-        let (input, positions_length) = le_u32(input)?;
-        let (input, source_positions) = count(le_u32, positions_length as usize)(input)?;
+    pub fn parse<'a>(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Vec<Self>> {
+        let (input, positions_length) = header.parse_int(input)?;
+        let (input, source_positions) =
+            count(|i| header.parse_int(i), positions_length as usize)(input)?;
 
         Ok((
             input,
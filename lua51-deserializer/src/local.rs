@@ -1,8 +1,8 @@
 use std::ops::Range;
 
-use nom::{multi::count, number::complete::le_u32, IResult};
+use nom::{multi::count, IResult};
 
-use crate::value::parse_string;
+use crate::{chunk::Header, value::parse_string};
 
 #[derive(Debug)]
 pub struct Local<'a> {
@@ -11,16 +11,16 @@ pub struct Local<'a> {
 }
 
 impl<'a> Local<'a> {
-    pub fn parse_list(input: &'a [u8]) -> IResult<&'a [u8], Vec<Self>> {
-        let (input, length) = le_u32(input)?;
+    pub fn parse_list(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Vec<Self>> {
+        let (input, length) = header.parse_int(input)?;
 
-        count(Self::parse, length as usize)(input)
+        count(|i| Self::parse(i, header), length as usize)(input)
     }
 
-    fn parse(input: &'a [u8]) -> IResult<&'a [u8], Self> {
-        let (input, name) = parse_string(input)?;
-        let (input, start) = le_u32(input)?;
-        let (input, end) = le_u32(input)?;
+    fn parse(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Self> {
+        let (input, name) = parse_string(input, header)?;
+        let (input, start) = header.parse_int(input)?;
+        let (input, end) = header.parse_int(input)?;
 
         Ok((
             input,
@@ -0,0 +1,242 @@
+#![feature(let_chains)]
+
+mod lifter;
+
+use std::time::Instant;
+
+use lifter::Lifter;
+use lua51_deserializer::chunk::Chunk;
+use nom::error::ErrorKind;
+use thiserror::Error;
+
+pub use decompiler::{
+    DecompileOutput, Fallback, FunctionReport, FunctionStatus, Limits, LineMode, Timings,
+};
+
+#[cfg(feature = "dhat-heap")]
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+#[derive(Debug, Error)]
+pub enum DecompileError {
+    #[error("malformed bytecode at offset {offset}: {}", kind.description())]
+    Malformed { offset: usize, kind: ErrorKind },
+    #[error("unexpected end of bytecode")]
+    UnexpectedEnd,
+}
+
+impl DecompileError {
+    // `err` is from parsing `bytecode`, its input is what was left
+    pub fn new(bytecode: &[u8], err: nom::Err<nom::error::Error<&[u8]>>) -> Self {
+        match err {
+            nom::Err::Error(err) | nom::Err::Failure(err) => Self::Malformed {
+                offset: bytecode.len() - err.input.len(),
+                kind: err.code,
+            },
+            nom::Err::Incomplete(_) => Self::UnexpectedEnd,
+        }
+    }
+}
+
+pub fn decompile_bytecode(
+    bytecode: &[u8],
+    limits: &Limits,
+) -> Result<DecompileOutput, DecompileError> {
+    let now = Instant::now();
+    let (_, chunk) = Chunk::parse(bytecode).map_err(|e| DecompileError::new(bytecode, e))?;
+    let deserialize_time = now.elapsed();
+
+    let mut output = decompiler::decompile::<Lifter>(&chunk, limits);
+    output.timings.deserialize = deserialize_time;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BYTECODE: &[u8] =
+        include_bytes!("../../lua51-deserializer/fixtures/header_le_int4_size8_f64.luac");
+
+    #[test]
+    fn reports_malformed_bytecode() {
+        let mut bytecode = BYTECODE.to_vec();
+        bytecode[0] = b'#';
+        assert!(matches!(
+            decompile_bytecode(&bytecode, &Limits::default()),
+            Err(DecompileError::Malformed { offset: 0, .. })
+        ));
+        assert!(decompile_bytecode(&BYTECODE[..40], &Limits::default()).is_err());
+    }
+
+    #[test]
+    fn names_locals_from_debug_info() {
+        let source = decompile_bytecode(BYTECODE, &Limits::default())
+            .unwrap()
+            .source;
+        for name in [
+            "local greeting",
+            "function add(a, b)",
+            "for i = 1, 10, 2 do",
+            "local count = 0",
+            "-- upvalues: (ref) count",
+        ] {
+            assert!(source.contains(name), "missing {:?} in:\n{}", name, source);
+        }
+    }
+
+    fn replace(bytecode: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut rest = bytecode;
+        while let Some(i) = rest.windows(from.len()).position(|w| w == from) {
+            output.extend_from_slice(&rest[..i]);
+            output.extend_from_slice(to);
+            rest = &rest[i + from.len()..];
+        }
+        output.extend_from_slice(rest);
+        output
+    }
+
+    // names are stored with their size and null terminator, tampered ones that can't be written
+    // in source are replaced with generated names
+    #[test]
+    fn ignores_invalid_debug_names() {
+        let bytecode = replace(BYTECODE, b"greeting\0", b"function\0");
+        let bytecode = replace(&bytecode, b"count\0", b"until\0");
+        let bytecode = replace(&bytecode, b"\x02\0\0\0\0\0\0\0a\0", b"\x01\0\0\0\0\0\0\0\0");
+        let source = decompile_bytecode(&bytecode, &Limits::default())
+            .unwrap()
+            .source;
+        for invalid in ["local function =", "until", "function(, b)"] {
+            assert!(!source.contains(invalid), "{:?} in:\n{}", invalid, source);
+        }
+        assert!(source.contains("function add(p"), "{}", source);
+    }
+
+    fn abc(op: u32, a: u32, b: u32, c: u32) -> u32 {
+        op | a << 6 | c << 14 | b << 23
+    }
+
+    fn asbx(op: u32, a: u32, sbx: i32) -> u32 {
+        op | a << 6 | ((sbx + 131071) as u32) << 14
+    }
+
+    // a function without debug info whose only constant is the number 1
+    fn function(code: &[u32], closures: &[Vec<u8>], number_of_upvalues: u8) -> Vec<u8> {
+        let mut output = 0u64.to_le_bytes().to_vec();
+        output.extend_from_slice(&[0; 8]);
+        output.extend_from_slice(&[number_of_upvalues, 0, 2, 8]);
+        output.extend_from_slice(&(code.len() as u32).to_le_bytes());
+        for instruction in code {
+            output.extend_from_slice(&instruction.to_le_bytes());
+        }
+        output.extend_from_slice(&1u32.to_le_bytes());
+        output.push(3);
+        output.extend_from_slice(&1f64.to_le_bytes());
+        output.extend_from_slice(&(closures.len() as u32).to_le_bytes());
+        for closure in closures {
+            output.extend_from_slice(closure);
+        }
+        output.extend_from_slice(&[0; 12]);
+        output
+    }
+
+    fn decompile(code: &[u32], closures: &[Vec<u8>]) -> String {
+        let mut chunk = b"\x1bLuaQ\x00\x01\x04\x08\x04\x08\x00".to_vec();
+        chunk.extend(function(code, closures, 0));
+        let output = decompile_bytecode(&chunk, &Limits::default()).unwrap();
+        for function in &output.functions {
+            assert_eq!(function.status, FunctionStatus::Ok, "{}", output.source);
+        }
+        output.source
+    }
+
+    #[test]
+    fn lifts_tampered_control_flow() {
+        const MOVE: u32 = 0;
+        const LOADK: u32 = 1;
+        const LOADBOOL: u32 = 2;
+        const GETUPVAL: u32 = 4;
+        const NEWTABLE: u32 = 10;
+        const JMP: u32 = 22;
+        const TEST: u32 = 26;
+        const RETURN: u32 = 30;
+        const SETLIST: u32 = 34;
+        const CLOSURE: u32 = 36;
+
+        // a jump past the end of the function
+        let source = decompile(
+            &[
+                abc(LOADK, 0, 0, 0),
+                abc(TEST, 0, 0, 1),
+                asbx(JMP, 0, 100),
+                abc(RETURN, 0, 2, 0),
+            ],
+            &[],
+        );
+        assert!(source.contains("-- jump to invalid pc 103"), "{}", source);
+
+        // a LOADBOOL skipping a jump that is never taken, followed by junk
+        let source = decompile(
+            &[
+                abc(LOADBOOL, 0, 0, 1),
+                asbx(JMP, 0, 1),
+                abc(LOADBOOL, 0, 1, 0),
+                abc(RETURN, 0, 2, 0),
+                abc(RETURN, 0, 0, 0),
+            ],
+            &[],
+        );
+        assert_eq!(source.trim(), "return true");
+
+        // a jump back into the MOVE that captures an upvalue for the closure
+        let child = function(&[abc(GETUPVAL, 0, 0, 0), abc(RETURN, 0, 2, 0)], &[], 1);
+        decompile(
+            &[
+                abc(LOADK, 0, 0, 0),
+                abc(LOADBOOL, 3, 0, 0),
+                abc(CLOSURE, 1, 0, 0),
+                abc(MOVE, 2, 0, 0),
+                abc(TEST, 3, 0, 1),
+                asbx(JMP, 0, -3),
+                abc(RETURN, 1, 2, 0),
+            ],
+            &[child],
+        );
+
+        // a SETLIST whose block number is in the next word
+        let source = decompile(
+            &[
+                abc(NEWTABLE, 0, 0, 0),
+                abc(LOADK, 1, 0, 0),
+                abc(SETLIST, 0, 1, 0),
+                1,
+                abc(RETURN, 0, 2, 0),
+            ],
+            &[],
+        );
+        assert!(source.contains("return { 1 }"), "{}", source);
+    }
+
+    #[test]
+    fn lifts_tampered_closures() {
+        const LOADK: u32 = 1;
+        const GETUPVAL: u32 = 4;
+        const RETURN: u32 = 30;
+        const CLOSURE: u32 = 36;
+
+        // a prototype that doesn't exist
+        let source = decompile(&[abc(CLOSURE, 0, 0, 5), abc(RETURN, 0, 2, 0)], &[]);
+        assert!(source.contains("-- invalid prototype 5"), "{}", source);
+
+        // captures that are neither a MOVE nor a GETUPVAL of an upvalue that exists
+        let child = function(&[abc(GETUPVAL, 0, 0, 0), abc(RETURN, 0, 2, 0)], &[], 1);
+        for capture in [abc(LOADK, 0, 0, 0), abc(GETUPVAL, 0, 3, 0)] {
+            let source = decompile(
+                &[abc(CLOSURE, 1, 0, 0), capture, abc(RETURN, 1, 2, 0)],
+                std::slice::from_ref(&child),
+            );
+            assert!(source.contains("-- invalid upvalue capture"), "{}", source);
+        }
+    }
+}
@@ -8,127 +8,130 @@ use rustc_hash::FxHashMap;
 
 use ast::{RcLocal, Statement};
 use cfg::function::Function;
+use decompiler::{
+    debug_name, function_count, local_variables, BlockMap, LiftedFunction, RegisterWrites,
+};
 
 use lua51_deserializer::{
     argument::{Constant, Register, RegisterOrConstant},
+    chunk::Chunk,
     Function as BytecodeFunction, Instruction, Value,
 };
 
-use petgraph::{stable_graph::NodeIndex, visit::EdgeRef, Direction};
+use petgraph::stable_graph::NodeIndex;
 
 use triomphe::Arc;
 
-pub struct Lifter<'a, 'b> {
+pub struct Lifter<'a> {
     bytecode: &'a BytecodeFunction<'a>,
-    nodes: FxHashMap<usize, NodeIndex>,
+    blocks: BlockMap,
     insert_between: FxHashMap<NodeIndex, (NodeIndex, Statement)>,
     locals: FxHashMap<Register, RcLocal>,
     constants: FxHashMap<usize, ast::Literal>,
     function: Function,
     upvalues: Vec<RcLocal>,
-    lifted_functions: &'b mut Vec<(Arc<Mutex<ast::Function>>, Function, Vec<RcLocal>)>,
+    // the prototypes are paired with their id, which is their index in a preorder traversal of
+    // the function tree. this matches the order luac lists them in
+    child_functions:
+        FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, (usize, &'a BytecodeFunction<'a>)>,
+    register_writes: RegisterWrites<Register>,
 }
 
-impl<'a, 'b> Lifter<'a, 'b> {
+// the control flow of the bytecode, which `BlockMap` splits into blocks
+struct Code<'a>(&'a BytecodeFunction<'a>);
+
+impl decompiler::Code for Code<'_> {
+    fn successors(&self, pc: usize) -> Vec<(isize, BranchType)> {
+        let next = pc as isize + 1;
+        match self.0.code[pc] {
+            Instruction::Equal { .. }
+            | Instruction::LessThan { .. }
+            | Instruction::LessThanOrEqual { .. }
+            | Instruction::Test { .. }
+            | Instruction::TestSet { .. }
+            | Instruction::IterateGenericForLoop { .. } => {
+                vec![(next, BranchType::Then), (next + 1, BranchType::Else)]
+            }
+            Instruction::IterateNumericForLoop { skip, .. } => vec![
+                (next + skip as isize, BranchType::Then),
+                (next, BranchType::Else),
+            ],
+            Instruction::Jump(skip) | Instruction::InitNumericForLoop { skip, .. } => {
+                vec![(next + skip as isize, BranchType::Unconditional)]
+            }
+            Instruction::LoadBoolean { skip_next, .. } => {
+                vec![(next + skip_next as isize, BranchType::Unconditional)]
+            }
+            Instruction::Return(..) => Vec::new(),
+            _ => vec![(self.next_pc(pc) as isize, BranchType::Unconditional)],
+        }
+    }
+
+    // jumping to the operand of a SETLIST executes it as an instruction, which we can't lift
+    fn is_valid_pc(&self, pc: isize) -> bool {
+        usize::try_from(pc).is_ok_and(|pc| {
+            self.0
+                .code
+                .get(pc)
+                .is_some_and(|i| !matches!(i, Instruction::Data(_)))
+        })
+    }
+
+    // a closure is followed by the instructions capturing its upvalues
+    fn next_pc(&self, pc: usize) -> usize {
+        match self.0.code[pc] {
+            Instruction::Closure { ref function, .. } => {
+                pc + 1
+                    + self
+                        .0
+                        .closures
+                        .get(function.0 as usize)
+                        .map_or(0, |c| c.number_of_upvalues as usize)
+            }
+            Instruction::SetList { .. }
+                if matches!(self.0.code.get(pc + 1), Some(Instruction::Data(_))) =>
+            {
+                pc + 2
+            }
+            _ => pc + 1,
+        }
+    }
+}
+
+impl<'a> Lifter<'a> {
     fn allocate_locals(&mut self) {
         self.upvalues
             .reserve(self.bytecode.number_of_upvalues as usize);
-        for _ in 0..self.bytecode.number_of_upvalues {
-            self.upvalues.push(RcLocal::default());
+        for i in 0..self.bytecode.number_of_upvalues {
+            // unlike the names of locals, these still end in the null terminator
+            let name = self
+                .bytecode
+                .upvalues
+                .get(i as usize)
+                .and_then(|&n| debug_name(n.strip_suffix(b"\0").unwrap_or(n)));
+            self.upvalues.push(RcLocal::new(ast::Local::new(name)));
         }
 
         self.locals
             .reserve(self.bytecode.maximum_stack_size as usize);
         for i in 0..self.bytecode.maximum_stack_size {
-            let local = RcLocal::default();
-            if i < self.bytecode.number_of_parameters {
+            let local = if i < self.bytecode.number_of_parameters {
+                // the parameters are the first locals
+                let name = self
+                    .bytecode
+                    .locals
+                    .get(i as usize)
+                    .and_then(|l| debug_name(l.name));
+                let local = RcLocal::new(ast::Local::new(name));
                 self.function.parameters.push(local.clone());
-            }
+                local
+            } else {
+                RcLocal::default()
+            };
             self.locals.insert(Register(i), local);
         }
     }
 
-    // TODO: support jumps to invalid destinations
-    // including cases where there is usize::MAX instructions and the last instruction
-    // skips forward, overflowing
-    fn create_block_map(&mut self) {
-        self.nodes.insert(0, self.function.new_block());
-        for (insn_index, insn) in self.bytecode.code.iter().enumerate() {
-            match *insn {
-                Instruction::SetList {
-                    block_number: 0, ..
-                } => {
-                    // TODO: skip next instruction
-                    todo!();
-                }
-                Instruction::LoadBoolean {
-                    skip_next: true, ..
-                } => {
-                    self.nodes
-                        .entry(insn_index + 1)
-                        .or_insert_with(|| self.function.new_block());
-                    self.nodes
-                        .entry(insn_index + 2)
-                        .or_insert_with(|| self.function.new_block());
-                }
-                Instruction::Equal { .. }
-                | Instruction::LessThan { .. }
-                | Instruction::LessThanOrEqual { .. }
-                | Instruction::Test { .. }
-                | Instruction::TestSet { .. }
-                | Instruction::IterateGenericForLoop { .. } => {
-                    self.nodes
-                        .entry(insn_index + 1)
-                        .or_insert_with(|| self.function.new_block());
-                    self.nodes
-                        .entry(insn_index + 2)
-                        .or_insert_with(|| self.function.new_block());
-                }
-                Instruction::Jump(skip) => {
-                    let dest_index = (insn_index + 1)
-                        .checked_add_signed(skip.try_into().unwrap())
-                        .unwrap();
-                    self.nodes
-                        .entry(dest_index)
-                        .or_insert_with(|| self.function.new_block());
-                    self.nodes
-                        .entry(insn_index + 1)
-                        .or_insert_with(|| self.function.new_block());
-                }
-                Instruction::IterateNumericForLoop { skip, .. }
-                | Instruction::InitNumericForLoop { skip, .. } => {
-                    self.nodes
-                        .entry(
-                            (insn_index + 1)
-                                .checked_add_signed(skip.try_into().unwrap())
-                                .unwrap(),
-                        )
-                        .or_insert_with(|| self.function.new_block());
-                    self.nodes
-                        .entry(insn_index + 1)
-                        .or_insert_with(|| self.function.new_block());
-                }
-                Instruction::Return(..) => {
-                    self.nodes
-                        .entry(insn_index + 1)
-                        .or_insert_with(|| self.function.new_block());
-                }
-                _ => {}
-            }
-        }
-    }
-
-    fn code_ranges(&self) -> Vec<(usize, usize)> {
-        let mut nodes = self.nodes.keys().cloned().collect::<Vec<_>>();
-        nodes.sort_unstable();
-        let ends = nodes
-            .iter()
-            .skip(1)
-            .map(|&s| s - 1)
-            .chain(std::iter::once(self.bytecode.code.len() - 1));
-        nodes.iter().cloned().zip(ends).collect()
-    }
-
     fn constant(&mut self, constant: Constant) -> ast::Literal {
         self.constants
             .entry(constant.0 as usize)
@@ -150,6 +153,59 @@ impl<'a, 'b> Lifter<'a, 'b> {
         }
     }
 
+    // a closure of prototype `function`, which is queued to be lifted.
+    // the instructions after the closure at `pc` capture its upvalues
+    fn closure(
+        &mut self,
+        pc: usize,
+        function: usize,
+        statements: &mut Vec<Statement>,
+    ) -> ast::RValue {
+        let Some(closure) = self.bytecode.closures.get(function) else {
+            statements.push(ast::Comment::new(format!("invalid prototype {}", function)).into());
+            return ast::Literal::Nil.into();
+        };
+
+        let mut upvalues_passed = Vec::with_capacity(closure.number_of_upvalues.into());
+        // the captures are read from the code rather than the block since they can
+        // be jumped to, making them the start of another block
+        for capture in pc + 1..pc + 1 + closure.number_of_upvalues as usize {
+            let local = match self.bytecode.code.get(capture) {
+                Some(Instruction::Move {
+                    destination: _,
+                    source,
+                }) => self.locals.get(source),
+                Some(Instruction::GetUpvalue {
+                    destination: _,
+                    upvalue,
+                }) => self.upvalues.get(upvalue.0 as usize),
+                _ => None,
+            };
+            // an invalid capture is passed a local that's never assigned
+            upvalues_passed.push(local.cloned().unwrap_or_else(|| {
+                statements.push(ast::Comment::new("invalid upvalue capture".to_string()).into());
+                RcLocal::default()
+            }));
+        }
+
+        let ast_function = Arc::<Mutex<_>>::default();
+
+        let closure_id = self.function.id
+            + 1
+            + self.bytecode.closures[..function]
+                .iter()
+                .map(|closure| function_count(closure, |f| &f.closures))
+                .sum::<usize>();
+        self.child_functions
+            .insert(ByAddress(ast_function.clone()), (closure_id, closure));
+
+        ast::Closure {
+            function: ByAddress(ast_function),
+            upvalues: upvalues_passed.into_iter().map(ast::Upvalue::Ref).collect(),
+        }
+        .into()
+    }
+
     // TODO: rename to one of: lift_instructions, lift_range, lift_instruction_range, lift_block?
     fn lift_instruction(&mut self, start: usize, end: usize, statements: &mut Vec<Statement>) {
         if end > start {
@@ -158,7 +214,11 @@ impl<'a, 'b> Lifter<'a, 'b> {
         let mut top: Option<(ast::RValue, u8)> = None;
         // TODO: we should consume the instructions, reducing clones
         let mut iter = self.bytecode.code[start..=end].iter();
+        // the first instruction that isn't part of a statement yet
+        let mut first_pc = start;
         while let Some(instruction) = iter.next() {
+            let pc = end - iter.len();
+            let statement_count = statements.len();
             match instruction {
                 Instruction::Move {
                     destination,
@@ -320,7 +380,7 @@ impl<'a, 'b> Lifter<'a, 'b> {
                     };
                     statements.push(ast::Return::new(values).into());
                 }
-                Instruction::Jump(..) => {}
+                Instruction::Jump(..) | Instruction::Data(..) => {}
                 &Instruction::Add {
                     destination,
                     lhs,
@@ -473,7 +533,7 @@ impl<'a, 'b> Lifter<'a, 'b> {
                     );
 
                     self.function
-                        .block_mut(self.nodes[&(end + 1)])
+                        .block_mut(self.blocks.target(end as isize + 1))
                         .unwrap()
                         .push(assign.into());
                 }
@@ -588,41 +648,15 @@ impl<'a, 'b> Lifter<'a, 'b> {
                     destination,
                     function,
                 } => {
-                    let closure = &self.bytecode.closures[function.0 as usize];
-
-                    let mut upvalues_passed = Vec::with_capacity(closure.number_of_upvalues.into());
-                    for _ in 0..closure.number_of_upvalues {
-                        let local = match iter.next().as_ref().unwrap() {
-                            Instruction::Move {
-                                destination: _,
-                                source,
-                            } => self.locals[source].clone(),
-                            Instruction::GetUpvalue {
-                                destination: _,
-                                upvalue,
-                            } => self.upvalues[upvalue.0 as usize].clone(),
-                            _ => panic!(),
-                        };
-                        upvalues_passed.push(local);
+                    let value = self.closure(pc, function.0 as usize, statements);
+                    // the captures were lifted with the closure
+                    for _ in pc + 1..decompiler::Code::next_pc(&Code(self.bytecode), pc) {
+                        iter.next();
                     }
-
-                    let ast_function = Arc::<Mutex<_>>::default();
-
-                    let (function, upvalues) = Lifter::lift(closure, self.lifted_functions);
-                    self.lifted_functions
-                        .push((ast_function.clone(), function, upvalues));
-
                     statements.push(
                         ast::Assign::new(
                             vec![self.locals[destination].clone().into()],
-                            vec![ast::Closure {
-                                function: ByAddress(ast_function),
-                                upvalues: upvalues_passed
-                                    .into_iter()
-                                    .map(ast::Upvalue::Ref)
-                                    .collect(),
-                            }
-                            .into()],
+                            vec![value],
                         )
                         .into(),
                     );
@@ -646,7 +680,7 @@ impl<'a, 'b> Lifter<'a, 'b> {
                     let setlist = if number_of_elements != 0 {
                         ast::SetList::new(
                             self.locals[&table].clone(),
-                            (block_number - 1) as usize * FIELDS_PER_FLUSH + 1,
+                            (block_number as usize).saturating_sub(1) * FIELDS_PER_FLUSH + 1,
                             (table.0 + 1..table.0 + 1 + number_of_elements)
                                 .map(|r| self.locals[&Register(r)].clone().into())
                                 .collect(),
@@ -656,7 +690,7 @@ impl<'a, 'b> Lifter<'a, 'b> {
                         let top = top.take().unwrap();
                         ast::SetList::new(
                             self.locals[&table].clone(),
-                            (block_number - 1) as usize * FIELDS_PER_FLUSH + 1,
+                            (block_number as usize).saturating_sub(1) * FIELDS_PER_FLUSH + 1,
                             (table.0 + 1..top.1)
                                 .map(|r| self.locals[&Register(r)].clone().into())
                                 .collect(),
@@ -708,15 +742,11 @@ impl<'a, 'b> Lifter<'a, 'b> {
                             .into(),
                     );
 
-                    let body_node = self.get_node(
-                        &((end + 1)
-                            .checked_add_signed(skip.try_into().unwrap())
-                            .unwrap()),
-                    );
+                    let body_node = self.blocks.target(end as isize + 1 + skip as isize);
                     assert!(self
                         .insert_between
                         .insert(
-                            self.nodes[&start],
+                            self.blocks.node(start),
                             (
                                 body_node,
                                 ast::Assign::new(
@@ -767,11 +797,11 @@ impl<'a, 'b> Lifter<'a, 'b> {
                         .into(),
                     );
 
-                    let body_node = self.get_node(&(end + 1));
+                    let body_node = self.blocks.target(end as isize + 1);
                     assert!(self
                         .insert_between
                         .insert(
-                            self.nodes[&start],
+                            self.blocks.node(start),
                             (
                                 body_node,
                                 ast::Assign::new(
@@ -785,111 +815,105 @@ impl<'a, 'b> Lifter<'a, 'b> {
                 }
             }
 
+            // the loop variables are written by the loop instruction, but only in scope at the
+            // start of the body, which the jump after a generic for loop instruction goes to
+            let written_pc = match (instruction, self.bytecode.code.get(pc + 1)) {
+                (Instruction::IterateGenericForLoop { .. }, Some(&Instruction::Jump(skip))) => {
+                    (pc + 2).checked_add_signed(skip as isize).unwrap_or(pc)
+                }
+                _ => pc,
+            };
+            let node = self.blocks.node(start);
+            for (statement_index, statement) in statements.iter().enumerate().skip(statement_count)
+            {
+                self.register_writes.record(
+                    &self.locals,
+                    node,
+                    statement_index,
+                    statement,
+                    written_pc,
+                );
+            }
+
+            if statements.len() > statement_count {
+                // closures consume the instructions that capture their upvalues
+                let end_pc = end + 1 - iter.len();
+                let origin = ast::Origin::new(
+                    self.bytecode
+                        .positions
+                        .get(pc)
+                        .map(|position| position.source as usize),
+                    first_pc..end_pc,
+                );
+                for statement in &mut statements[statement_count..] {
+                    if let Some(statement_origin) = statement.origin_mut() {
+                        *statement_origin = origin.clone();
+                    }
+                }
+                first_pc = end_pc;
+            }
+
             if matches!(instruction, Instruction::Return { .. }) {
                 break;
             }
         }
     }
 
-    // TODO: REFACTOR: this function doesnt need to exist
-    fn get_node(&'a self, index: &'a usize) -> NodeIndex {
-        self.nodes[index]
-    }
-
     fn lift_blocks(&mut self) {
-        let ranges = self.code_ranges();
-        for (start, end) in ranges {
+        let code = Code(self.bytecode);
+        for (start, end) in self.blocks.ranges(&code) {
             // TODO: gotta be a better way
             // we need to do this in case that the body of a for loop is after the for loop instruction
             // see: IterateNumericForLoop
             let mut statements =
-                std::mem::take(self.function.block_mut(self.nodes[&start]).unwrap());
+                std::mem::take(self.function.block_mut(self.blocks.node(start)).unwrap());
             self.lift_instruction(start, end, &mut statements);
-            *self.function.block_mut(self.nodes[&start]).unwrap() = statements;
-
-            match self.bytecode.code[end] {
-                Instruction::Equal { .. }
-                | Instruction::LessThan { .. }
-                | Instruction::LessThanOrEqual { .. }
-                | Instruction::Test { .. }
-                | Instruction::TestSet { .. }
-                | Instruction::IterateGenericForLoop { .. } => {
-                    self.function.set_edges(
-                        self.nodes[&start],
-                        vec![
-                            (self.get_node(&(end + 1)), BlockEdge::new(BranchType::Then)),
-                            (self.get_node(&(end + 2)), BlockEdge::new(BranchType::Else)),
-                        ],
-                    );
-                }
-                Instruction::IterateNumericForLoop { skip, .. } => {
-                    self.function.set_edges(
-                        self.nodes[&start],
-                        vec![
-                            (
-                                self.get_node(
-                                    &((end + 1)
-                                        .checked_add_signed(skip.try_into().unwrap())
-                                        .unwrap()),
-                                ),
-                                BlockEdge::new(BranchType::Then),
-                            ),
-                            (self.get_node(&(end + 1)), BlockEdge::new(BranchType::Else)),
-                        ],
-                    );
-                }
-                Instruction::Jump(skip) | Instruction::InitNumericForLoop { skip, .. } => {
-                    self.function.set_edges(
-                        self.nodes[&start],
-                        vec![(
-                            self.get_node(
-                                &((end + 1)
-                                    .checked_add_signed(skip.try_into().unwrap())
-                                    .unwrap()),
-                            ),
-                            BlockEdge::new(BranchType::Unconditional),
-                        )],
-                    );
-                }
-                Instruction::Return { .. } => {}
-                Instruction::LoadBoolean { skip_next, .. } => {
-                    let successor = self.get_node(&(end + 1 + skip_next as usize));
-                    self.function.set_edges(
-                        self.nodes[&start],
-                        vec![(successor, BlockEdge::new(BranchType::Unconditional))],
-                    );
-                }
-                _ => {
-                    if end + 1 != self.bytecode.code.len() {
-                        self.function.set_edges(
-                            self.nodes[&start],
-                            vec![(
-                                self.get_node(&(end + 1)),
-                                BlockEdge::new(BranchType::Unconditional),
-                            )],
-                        );
-                    }
-                }
-            }
+            *self.function.block_mut(self.blocks.node(start)).unwrap() = statements;
+
+            let edges = self.blocks.edges(&code, end);
+            self.function.set_edges(self.blocks.node(start), edges);
         }
     }
+}
+
+impl<'a> decompiler::Lifter<'a> for Lifter<'a> {
+    type Chunk = Chunk<'a>;
+    type Prototype = (usize, &'a BytecodeFunction<'a>);
+
+    const STRUCTURE_METHOD_CALLS: bool = true;
+
+    fn main(chunk: &'a Chunk<'a>) -> Self::Prototype {
+        (0, &chunk.function)
+    }
+
+    fn id(&(id, _): &Self::Prototype) -> usize {
+        id
+    }
 
-    pub fn lift(
-        bytecode: &'a BytecodeFunction,
-        lifted_functions: &'b mut Vec<(Arc<Mutex<ast::Function>>, Function, Vec<RcLocal>)>,
-    ) -> (Function, Vec<RcLocal>) {
+    fn disassemble(_: &'a Chunk<'a>, (_, bytecode): &Self::Prototype) -> Vec<String> {
+        bytecode
+            .code
+            .iter()
+            .enumerate()
+            .map(|(pc, instruction)| format!("{:>4} {:?}", pc, instruction))
+            .collect()
+    }
+
+    fn lift(_: &'a Chunk<'a>, (id, bytecode): Self::Prototype) -> LiftedFunction<Self::Prototype> {
+        let mut function = Function::new(id);
+        let blocks = BlockMap::new(&Code(bytecode), &mut function);
         let mut context = Self {
             bytecode,
-            nodes: FxHashMap::default(),
+            blocks,
             insert_between: FxHashMap::default(),
             locals: FxHashMap::default(),
             constants: FxHashMap::default(),
-            function: Function::new(0),
+            function,
             upvalues: Vec::new(),
-            lifted_functions,
+            child_functions: FxHashMap::default(),
+            register_writes: RegisterWrites::default(),
         };
 
-        context.create_block_map();
         context.allocate_locals();
         context.lift_blocks();
 
@@ -898,51 +922,45 @@ impl<'a, 'b> Lifter<'a, 'b> {
         let stack_init_node = context.function.new_block();
         let stack_init_block = context.function.block_mut(stack_init_node).unwrap();
         stack_init_block.reserve(context.locals.len());
-        for (_, local) in context.locals {
-            if !context.function.parameters.contains(&local) {
+        for local in context.locals.values() {
+            if !context.function.parameters.contains(local) {
                 let stack_init_block = context.function.block_mut(stack_init_node).unwrap();
                 stack_init_block.push(
-                    ast::Assign::new(vec![local.into()], vec![ast::Literal::Nil.into()]).into(),
+                    ast::Assign::new(vec![local.clone().into()], vec![ast::Literal::Nil.into()])
+                        .into(),
                 )
             }
         }
         context.function.set_edges(
             stack_init_node,
-            vec![(context.nodes[&0], BlockEdge::new(BranchType::Unconditional))],
+            vec![(
+                context.blocks.target(0),
+                BlockEdge::new(BranchType::Unconditional),
+            )],
         );
         context.function.set_entry(stack_init_node);
 
-        for (node, (successor, stat)) in context.insert_between {
-            if context.function.predecessor_blocks(successor).count() == 1 {
-                context
-                    .function
-                    .block_mut(successor)
-                    .unwrap()
-                    .insert(0, stat);
-            } else {
-                let between_node = context.function.new_block();
-                context.function.block_mut(between_node).unwrap().push(stat);
-                context.function.set_edges(
-                    between_node,
-                    vec![(successor, BlockEdge::new(BranchType::Unconditional))],
-                );
-                for edge in context
-                    .function
-                    .graph()
-                    .edges_directed(node, Direction::Outgoing)
-                    .filter(|e| e.target() == successor)
-                    .map(|e| e.id())
-                    .collect::<Vec<_>>()
-                {
-                    let edge = context.function.graph_mut().remove_edge(edge).unwrap();
-                    context
-                        .function
-                        .graph_mut()
-                        .add_edge(node, between_node, edge);
-                }
-            }
+        context.blocks.insert_between(
+            &mut context.function,
+            std::mem::take(&mut context.insert_between),
+            &mut context.register_writes,
+            &context.locals,
+        );
+        let local_variables = local_variables(
+            context
+                .bytecode
+                .locals
+                .iter()
+                .map(|l| (l.name, l.range.clone())),
+        );
+        context
+            .register_writes
+            .name(&local_variables, &mut context.function);
+
+        LiftedFunction {
+            function: context.function,
+            upvalues: context.upvalues,
+            child_functions: context.child_functions,
         }
-
-        (context.function, context.upvalues)
     }
 }
@@ -0,0 +1,11 @@
+local a, b = ...
+print(a // b, a & b, a | b, a ~ b, ~a, a << 2, 1 << a, a >> 1, a - 1, 2 + a, 1.5, 3)
+do
+	local f <close> = setmetatable({}, { __close = print })
+	print(f)
+end
+do
+	local g <close> = a and setmetatable({}, { __close = print }) or nil
+	print(g)
+end
+return a > 5
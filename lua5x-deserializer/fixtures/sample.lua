@@ -0,0 +1,20 @@
+local greeting = "hello"
+local t = { 1, 2, 3, name = "t" }
+for i = 10, 1, -2 do
+	t[#t + 1] = i
+end
+for k, v in pairs(t) do
+	print(k, v)
+end
+local function counter()
+	local count = 0
+	return function(...)
+		count = count + select("#", ...)
+		return count
+	end
+end
+if t.name ~= greeting then
+	shared = counter()
+end
+_ENV["not a name"] = 1
+return counter, t
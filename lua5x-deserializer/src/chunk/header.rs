@@ -0,0 +1,241 @@
+use nom::{
+    bytes::complete::{tag, take},
+    error::{Error, ErrorKind, ParseError},
+    number::complete::le_u8,
+    Err, IResult,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    Lua52,
+    Lua53,
+    Lua54,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Big,
+    Little,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Official,
+}
+
+// follows the signature of 5.2 and later chunks, to catch corruption by newline conversion
+const LUAC_DATA: &[u8] = b"\x19\x93\r\n\x1a\n";
+
+// describes how the rest of the chunk is encoded, every parser after the header takes it
+#[derive(Debug, Clone, Copy)]
+pub struct Header {
+    pub version: Version,
+    pub format: Format,
+    pub endianness: Endianness,
+    // 5.4 writes ints and sizes as variable length integers, these are 0 there
+    pub int_width: u8,
+    pub size_t_width: u8,
+    pub instr_width: u8,
+    // 0 in 5.2, which doesn't have an integer subtype
+    pub integer_width: u8,
+    pub number_width: u8,
+    pub number_is_integral: bool,
+}
+
+fn fail<T>(input: &[u8], kind: ErrorKind) -> IResult<&[u8], T> {
+    Err(Err::Failure(Error::from_error_kind(input, kind)))
+}
+
+impl Header {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, _) = tag("\x1BLua")(input)?;
+        let (input, version) = match le_u8(input)? {
+            (input, 0x52) => Ok((input, Version::Lua52)),
+            (input, 0x53) => Ok((input, Version::Lua53)),
+            (input, 0x54) => Ok((input, Version::Lua54)),
+            _ => fail(input, ErrorKind::Switch),
+        }?;
+        let (input, format) = match le_u8(input)? {
+            (input, 0) => Ok((input, Format::Official)),
+            _ => fail(input, ErrorKind::Switch),
+        }?;
+        let (input, header) = match version {
+            Version::Lua52 => Self::parse_52(input)?,
+            Version::Lua53 | Version::Lua54 => Self::parse_53(input, version)?,
+        };
+        let header = Self { format, ..header };
+
+        if !header.is_supported() {
+            return fail(input, ErrorKind::Verify);
+        }
+        Ok((input, header))
+    }
+
+    fn parse_52(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, endianness) = match le_u8(input)? {
+            (input, 0) => Ok((input, Endianness::Big)),
+            (input, 1) => Ok((input, Endianness::Little)),
+            _ => fail(input, ErrorKind::Switch),
+        }?;
+        let (input, int_width) = le_u8(input)?;
+        let (input, size_t_width) = le_u8(input)?;
+        let (input, instr_width) = le_u8(input)?;
+        let (input, number_width) = le_u8(input)?;
+        let (input, number_is_integral) = match le_u8(input)? {
+            (input, 0) => Ok((input, false)),
+            (input, 1) => Ok((input, true)),
+            _ => fail(input, ErrorKind::Switch),
+        }?;
+        let (input, _) = tag(LUAC_DATA)(input)?;
+
+        Ok((
+            input,
+            Self {
+                version: Version::Lua52,
+                format: Format::Official,
+                endianness,
+                int_width,
+                size_t_width,
+                instr_width,
+                integer_width: 0,
+                number_width,
+                number_is_integral,
+            },
+        ))
+    }
+
+    // the endianness isn't written down, it's found from how the test integer was stored
+    fn parse_53(input: &[u8], version: Version) -> IResult<&[u8], Self> {
+        let (input, _) = tag(LUAC_DATA)(input)?;
+        let (input, (int_width, size_t_width)) = match version {
+            Version::Lua53 => {
+                let (input, int_width) = le_u8(input)?;
+                let (input, size_t_width) = le_u8(input)?;
+                (input, (int_width, size_t_width))
+            }
+            _ => (input, (0, 0)),
+        };
+        let (input, instr_width) = le_u8(input)?;
+        let (input, integer_width) = le_u8(input)?;
+        let (input, number_width) = le_u8(input)?;
+        let (rest, test_integer) = take(integer_width)(input)?;
+        let endianness = match test_integer {
+            [0x78, 0x56, zeroes @ ..] if zeroes.iter().all(|&b| b == 0) => Endianness::Little,
+            [zeroes @ .., 0x56, 0x78] if zeroes.iter().all(|&b| b == 0) => Endianness::Big,
+            _ => return fail(input, ErrorKind::Verify),
+        };
+
+        let header = Self {
+            version,
+            format: Format::Official,
+            endianness,
+            int_width,
+            size_t_width,
+            instr_width,
+            integer_width,
+            number_width,
+            number_is_integral: false,
+        };
+        if !matches!(number_width, 4 | 8) {
+            return fail(rest, ErrorKind::Verify);
+        }
+        let (input, test_number) = header.parse_number(rest)?;
+        if test_number != 370.5 {
+            return fail(rest, ErrorKind::Verify);
+        }
+        Ok((input, header))
+    }
+
+    // integers wider than 8 bytes don't exist, and floats are either a float or a double
+    fn is_supported(&self) -> bool {
+        let is_integer_width = |width| matches!(width, 1 | 2 | 4 | 8);
+        let sizes_are_supported = match self.version {
+            Version::Lua54 => true,
+            _ => is_integer_width(self.int_width) && is_integer_width(self.size_t_width),
+        };
+        sizes_are_supported
+            && self.instr_width == 4
+            && (self.version == Version::Lua52 || is_integer_width(self.integer_width))
+            && if self.number_is_integral {
+                is_integer_width(self.number_width)
+            } else {
+                matches!(self.number_width, 4 | 8)
+            }
+    }
+
+    fn parse_unsigned<'a>(&self, input: &'a [u8], width: u8) -> IResult<&'a [u8], u64> {
+        let (input, bytes) = take(width)(input)?;
+        let push = |value: u64, &byte: &u8| value << 8 | byte as u64;
+        let value = match self.endianness {
+            Endianness::Big => bytes.iter().fold(0, push),
+            Endianness::Little => bytes.iter().rev().fold(0, push),
+        };
+        Ok((input, value))
+    }
+
+    fn parse_u32<'a>(&self, input: &'a [u8], width: u8) -> IResult<&'a [u8], u32> {
+        let (rest, value) = self.parse_unsigned(input, width)?;
+        match u32::try_from(value) {
+            Ok(value) => Ok((rest, value)),
+            Err(_) => fail(input, ErrorKind::TooLarge),
+        }
+    }
+
+    // most significant group of 7 bits first, the last byte has its high bit set
+    fn parse_varint<'a>(&self, mut input: &'a [u8]) -> IResult<&'a [u8], u32> {
+        let start = input;
+        let mut value = 0u32;
+        loop {
+            let (rest, byte) = le_u8(input)?;
+            input = rest;
+            value = match value.checked_mul(1 << 7) {
+                Some(value) => value | (byte & 0x7F) as u32,
+                None => return fail(start, ErrorKind::TooLarge),
+            };
+            if byte & 0x80 != 0 {
+                return Ok((input, value));
+            }
+        }
+    }
+
+    // an `int`, used for counts, line numbers and pcs
+    pub(crate) fn parse_int<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], u32> {
+        match self.version {
+            Version::Lua54 => self.parse_varint(input),
+            _ => self.parse_u32(input, self.int_width),
+        }
+    }
+
+    // a `size_t`, used for string lengths
+    pub(crate) fn parse_size_t<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], u32> {
+        match self.version {
+            Version::Lua54 => self.parse_varint(input),
+            _ => self.parse_u32(input, self.size_t_width),
+        }
+    }
+
+    pub(crate) fn parse_instruction<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], u32> {
+        self.parse_u32(input, self.instr_width)
+    }
+
+    // sign extended from the integer's width
+    fn sign_extend(value: u64, width: u8) -> i64 {
+        let shift = 64 - width as u32 * 8;
+        (value << shift) as i64 >> shift
+    }
+
+    pub(crate) fn parse_integer<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], i64> {
+        let (input, value) = self.parse_unsigned(input, self.integer_width)?;
+        Ok((input, Self::sign_extend(value, self.integer_width)))
+    }
+
+    pub(crate) fn parse_number<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], f64> {
+        let (input, value) = self.parse_unsigned(input, self.number_width)?;
+        let value = match (self.number_is_integral, self.number_width) {
+            (true, width) => Self::sign_extend(value, width) as f64,
+            (false, 4) => f32::from_bits(value as u32) as f64,
+            (false, _) => f64::from_bits(value),
+        };
+        Ok((input, value))
+    }
+}
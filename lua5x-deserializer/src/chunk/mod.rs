@@ -0,0 +1,64 @@
+use nom::{number::complete::le_u8, IResult};
+
+pub use header::{Header, Version};
+
+use crate::function::Function;
+
+pub mod header;
+
+#[derive(Debug)]
+pub struct Chunk<'a> {
+    pub header: Header,
+    pub function: Function<'a>,
+}
+
+impl<'a> Chunk<'a> {
+    pub fn parse(input: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (input, header) = Header::parse(input)?;
+        // 5.3 and later repeat the main function's number of upvalues before it
+        let (input, _) = match header.version {
+            Version::Lua52 => (input, 0),
+            Version::Lua53 | Version::Lua54 => le_u8(input)?,
+        };
+        let (input, function) = Function::parse(input, &header)?;
+
+        Ok((input, Self { header, function }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    // the fixtures are `sample.lua` dumped by stock 5.2, 5.3 and 5.4 interpreters
+    #[test]
+    fn parses_every_version() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        for (name, version) in [
+            ("sample_52.luac", Version::Lua52),
+            ("sample_53.luac", Version::Lua53),
+            ("sample_54.luac", Version::Lua54),
+        ] {
+            let input = fs::read(fixtures.join(name)).unwrap();
+            let (rest, chunk) = Chunk::parse(&input).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert!(rest.is_empty(), "{}: trailing bytes", name);
+            assert_eq!(chunk.header.version, version, "{}", name);
+            assert_eq!(chunk.function.closures.len(), 1, "{}", name);
+            let local_names = chunk
+                .function
+                .locals
+                .iter()
+                .map(|l| l.name)
+                .filter(|n| !n.starts_with(b"("))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                local_names,
+                [&b"greeting"[..], b"t", b"i", b"k", b"v", b"counter"],
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use nom::{
+    error::{Error, ErrorKind, ParseError},
+    multi::count,
+    number::complete::le_u8,
+    Err, IResult,
+};
+
+use crate::{
+    chunk::{Header, Version},
+    instruction::{argument::Operand, position::Position, BinaryOperation, Instruction},
+    local::Local,
+    value::{self, Value},
+};
+
+// where a closure gets an upvalue from, either a register or an upvalue of the enclosing function
+#[derive(Debug, Clone, Copy)]
+pub struct Capture {
+    pub in_stack: bool,
+    pub index: u8,
+}
+
+impl Capture {
+    fn parse<'a>(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Self> {
+        let (input, in_stack) = le_u8(input)?;
+        let (input, index) = le_u8(input)?;
+        // 5.4 also writes the kind of the variable, which only matters to the compiler
+        let (input, _) = match header.version {
+            Version::Lua54 => le_u8(input)?,
+            _ => (input, 0),
+        };
+
+        Ok((
+            input,
+            Self {
+                in_stack: in_stack != 0,
+                index,
+            },
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct Function<'a> {
+    pub name: &'a [u8],
+    pub line_defined: u32,
+    pub last_line_defined: u32,
+    pub vararg_flag: u8,
+    pub maximum_stack_size: u8,
+    pub code: Vec<Instruction>,
+    pub constants: Vec<Value<'a>>,
+    pub captures: Vec<Capture>,
+    pub closures: Vec<Function<'a>>,
+    pub positions: Vec<Position>,
+    pub locals: Vec<Local<'a>>,
+    pub upvalues: Vec<&'a [u8]>,
+    pub number_of_parameters: u8,
+}
+
+// `x - 1` is an addition of -1 that calls `__sub`, and `1 + x` an addition with its operands
+// flipped, the metamethod call after it knows which
+fn fold_metamethod(instruction: &mut Instruction, event: Option<BinaryOperation>, flip: bool) {
+    let Instruction::Binary {
+        lhs,
+        rhs,
+        operation,
+        ..
+    } = instruction
+    else {
+        return;
+    };
+    if let (Some(event), Operand::Integer(value)) = (event, &mut *rhs)
+        && event != *operation
+    {
+        *value = value.wrapping_neg();
+        *operation = event;
+    }
+    if flip && matches!(lhs, Operand::Register(_)) {
+        std::mem::swap(lhs, rhs);
+    }
+}
+
+fn parse_code<'a>(
+    mut input: &'a [u8],
+    header: &Header,
+    length: usize,
+) -> IResult<&'a [u8], Vec<Instruction>> {
+    let mut code = Vec::new();
+    while code.len() < length {
+        let (rest, (instruction, extra_argument)) = Instruction::parse(input, header)?;
+        if extra_argument.is_some() && code.len() + 2 > length {
+            return Err(Err::Failure(Error::from_error_kind(input, ErrorKind::Eof)));
+        }
+        if let Instruction::Metamethod { operation, flip } = instruction
+            && let Some(previous) = code.last_mut()
+        {
+            fold_metamethod(previous, operation, flip);
+        }
+        code.push(instruction);
+        code.extend(extra_argument.map(Instruction::Data));
+        input = rest;
+    }
+    Ok((input, code))
+}
+
+impl<'a> Function<'a> {
+    pub fn parse(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Self> {
+        // the source moved to the start of the function in 5.3
+        let (input, name) = match header.version {
+            Version::Lua52 => (input, None),
+            Version::Lua53 | Version::Lua54 => value::parse_string(input, header)?,
+        };
+        let (input, line_defined) = header.parse_int(input)?;
+        let (input, last_line_defined) = header.parse_int(input)?;
+        let (input, number_of_parameters) = le_u8(input)?;
+        let (input, vararg_flag) = le_u8(input)?;
+        let (input, maximum_stack_size) = le_u8(input)?;
+        let (input, code_length) = header.parse_int(input)?;
+        let (input, code) = parse_code(input, header, code_length as usize)?;
+        let (input, constants_length) = header.parse_int(input)?;
+        let (input, constants) =
+            count(|i| Value::parse(i, header), constants_length as usize)(input)?;
+        // 5.2 writes the closures right after the constants, and later versions after the
+        // upvalues
+        let (input, closures) = match header.version {
+            Version::Lua52 => Self::parse_list(input, header)?,
+            Version::Lua53 | Version::Lua54 => (input, Vec::new()),
+        };
+        let (input, captures_length) = header.parse_int(input)?;
+        let (input, captures) =
+            count(|i| Capture::parse(i, header), captures_length as usize)(input)?;
+        let (input, closures) = match header.version {
+            Version::Lua52 => (input, closures),
+            Version::Lua53 | Version::Lua54 => Self::parse_list(input, header)?,
+        };
+        let (input, name) = match header.version {
+            Version::Lua52 => value::parse_string(input, header)?,
+            Version::Lua53 | Version::Lua54 => (input, name),
+        };
+        let (input, positions) = Position::parse(input, header, line_defined)?;
+        let (input, locals) = Local::parse_list(input, header)?;
+        let (input, upvalues) = value::parse_strings(input, header)?;
+
+        Ok((
+            input,
+            Self {
+                name: name.unwrap_or_default(),
+                line_defined,
+                last_line_defined,
+                vararg_flag,
+                maximum_stack_size,
+                code,
+                constants,
+                captures,
+                closures,
+                positions,
+                locals,
+                upvalues,
+                number_of_parameters,
+            },
+        ))
+    }
+
+    fn parse_list(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Vec<Self>> {
+        let (input, length) = header.parse_int(input)?;
+
+        count(|i| Self::parse(i, header), length as usize)(input)
+    }
+}
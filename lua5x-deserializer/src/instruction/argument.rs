@@ -0,0 +1,46 @@
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Register(pub u8);
+
+impl From<u8> for Register {
+    fn from(value: u8) -> Self {
+        Self(value)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Constant(pub u32);
+
+#[derive(Debug, Copy, Clone)]
+pub struct Upvalue(pub u8);
+
+#[derive(Debug, Clone)]
+pub struct Function(pub u32);
+
+// 5.4 encodes small integers and floats in the instruction itself
+#[derive(Debug, Copy, Clone)]
+pub enum Operand {
+    Register(Register),
+    Constant(Constant),
+    Integer(i64),
+    Number(f64),
+}
+
+impl Operand {
+    // an argument with its high bit set refers to a constant in 5.2 and 5.3
+    pub(crate) fn from_rk(value: u16) -> Self {
+        if value & 0x100 != 0 {
+            Self::Constant(Constant((value & 0xFF) as u32))
+        } else {
+            Self::Register(Register(value as u8))
+        }
+    }
+
+    // 5.4 uses a separate bit to tell a constant from a register
+    pub(crate) fn from_k(value: u16, is_constant: bool) -> Self {
+        if is_constant {
+            Self::Constant(Constant(value as u32))
+        } else {
+            Self::Register(Register(value as u8))
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::chunk::Version;
+
+// every way of reading an instruction's arguments, which ones mean something depends on the
+// operation code
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub a: u8,
+    pub b: u16,
+    pub c: u16,
+    // 5.4 only
+    pub k: bool,
+    // b extended
+    pub b_x: u32,
+    // b signed, extended
+    pub b_sx: i32,
+    // a extended, used by EXTRAARG
+    pub a_x: u32,
+    // the signed jump offset of 5.4, 5.2 and 5.3 jumps use b_sx
+    pub s_j: i32,
+}
+
+impl Layout {
+    pub fn decode(instruction: u32, version: Version) -> Self {
+        match version {
+            Version::Lua52 | Version::Lua53 => {
+                let b_x = instruction >> 14;
+                // subtract maximum 18 bit signed int
+                let b_sx = b_x as i32 - (((1 << 18) - 1) >> 1);
+
+                Self {
+                    a: ((instruction >> 6) & 0xFF) as u8,
+                    b: ((instruction >> 23) & 0x1FF) as u16,
+                    c: ((instruction >> 14) & 0x1FF) as u16,
+                    k: false,
+                    b_x,
+                    b_sx,
+                    a_x: instruction >> 6,
+                    s_j: b_sx,
+                }
+            }
+            Version::Lua54 => {
+                let b_x = instruction >> 15;
+                let a_x = instruction >> 7;
+
+                Self {
+                    a: ((instruction >> 7) & 0xFF) as u8,
+                    b: ((instruction >> 16) & 0xFF) as u16,
+                    c: (instruction >> 24) as u16,
+                    k: (instruction >> 15) & 1 != 0,
+                    b_x,
+                    // subtract maximum 17 bit signed int
+                    b_sx: b_x as i32 - (((1 << 17) - 1) >> 1),
+                    a_x,
+                    // subtract maximum 25 bit signed int
+                    s_j: a_x as i32 - (((1 << 25) - 1) >> 1),
+                }
+            }
+        }
+    }
+
+    // the signed 8 bit arguments of 5.4's immediate instructions
+    pub fn s_b(&self) -> i64 {
+        self.b as i64 - 127
+    }
+
+    pub fn s_c(&self) -> i64 {
+        self.c as i64 - 127
+    }
+}
@@ -0,0 +1,649 @@
+use nom::{
+    error::{Error, ErrorKind, ParseError},
+    Err, IResult,
+};
+
+use argument::{Constant, Function, Operand, Register, Upvalue};
+use layout::Layout;
+use operation_code::OperationCode;
+
+use crate::chunk::{Header, Version};
+
+pub mod argument;
+mod layout;
+mod operation_code;
+pub mod position;
+
+#[derive(Debug)]
+struct RawInstruction(OperationCode, Layout);
+
+impl RawInstruction {
+    pub fn parse<'a>(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Self> {
+        let (rest, instruction) = header.parse_instruction(input)?;
+        let Some(operation_code) = OperationCode::decode(instruction, header.version) else {
+            return Err(Err::Failure(Error::from_error_kind(
+                input,
+                ErrorKind::Switch,
+            )));
+        };
+        let layout = Layout::decode(instruction, header.version);
+
+        Ok((rest, Self(operation_code, layout)))
+    }
+
+    // whether the instruction continues into the EXTRAARG that follows it
+    fn has_extra_argument(&self, version: Version) -> bool {
+        match (self.0, version) {
+            (OperationCode::LoadConstantExtended, _) => true,
+            (OperationCode::NewTable, Version::Lua54) => true,
+            (OperationCode::SetList, Version::Lua54) => self.1.k,
+            (OperationCode::SetList, _) => self.1.c == 0,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    IDiv,
+    Mod,
+    Pow,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl BinaryOperation {
+    // the tag method events of 5.4, which MMBIN instructions take to name their operation
+    fn from_event(event: u16) -> Option<Self> {
+        Some(match event {
+            6 => Self::Add,
+            7 => Self::Sub,
+            8 => Self::Mul,
+            9 => Self::Mod,
+            10 => Self::Pow,
+            11 => Self::Div,
+            12 => Self::IDiv,
+            13 => Self::BitAnd,
+            14 => Self::BitOr,
+            15 => Self::BitXor,
+            16 => Self::ShiftLeft,
+            17 => Self::ShiftRight,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperation {
+    Minus,
+    Not,
+    Length,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+}
+
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    Move {
+        destination: Register,
+        source: Register,
+    },
+    LoadConstant {
+        destination: Register,
+        source: Constant,
+    },
+    LoadInteger {
+        destination: Register,
+        value: i64,
+    },
+    LoadFloat {
+        destination: Register,
+        value: f64,
+    },
+    LoadBoolean {
+        destination: Register,
+        value: bool,
+        skip_next: bool,
+    },
+    LoadNil(Vec<Register>),
+    GetUpvalue {
+        destination: Register,
+        upvalue: Upvalue,
+    },
+    SetUpvalue {
+        destination: Upvalue,
+        source: Register,
+    },
+    // indexing an upvalue directly, which is how globals are read through `_ENV`
+    GetUpvalueIndex {
+        destination: Register,
+        upvalue: Upvalue,
+        key: Operand,
+    },
+    SetUpvalueIndex {
+        upvalue: Upvalue,
+        key: Operand,
+        value: Operand,
+    },
+    GetIndex {
+        destination: Register,
+        object: Register,
+        key: Operand,
+    },
+    SetIndex {
+        object: Register,
+        key: Operand,
+        value: Operand,
+    },
+    NewTable {
+        destination: Register,
+        array_size: u32,
+        hash_size: u32,
+    },
+    PrepMethodCall {
+        destination: Register,
+        self_arg: Register,
+        object: Register,
+        method: Operand,
+    },
+    Binary {
+        destination: Register,
+        lhs: Operand,
+        rhs: Operand,
+        operation: BinaryOperation,
+    },
+    Unary {
+        destination: Register,
+        operand: Register,
+        operation: UnaryOperation,
+    },
+    Concatenate {
+        destination: Register,
+        operands: Vec<Register>,
+    },
+    Jump {
+        skip: i32,
+        // 5.2 and 5.3 jumps out of a block close its upvalues from this register on
+        close: Option<Register>,
+    },
+    Compare {
+        lhs: Operand,
+        rhs: Operand,
+        operation: Comparison,
+        invert: bool,
+    },
+    Test {
+        value: Register,
+        invert: bool,
+    },
+    TestSet {
+        destination: Register,
+        value: Register,
+        invert: bool,
+    },
+    Call {
+        function: Register,
+        arguments: u8,
+        return_values: u8,
+    },
+    TailCall {
+        function: Register,
+        arguments: u8,
+    },
+    Return(Register, u8),
+    // the skips of numeric for loops are normalized to 5.1's, so FORPREP skips to the FORLOOP
+    // and the FORLOOP skips to the start of the body
+    IterateNumericForLoop {
+        // internal_counter, limit, step, external_counter
+        control: Vec<Register>,
+        skip: i32,
+    },
+    InitNumericForLoop {
+        // internal_counter, limit, step, external_counter
+        control: Vec<Register>,
+        skip: i32,
+    },
+    // TFORPREP, which only skips to the TFORCALL
+    InitGenericForLoop {
+        skip: i32,
+    },
+    CallGenericForLoop {
+        // ex. `next` in `for i, v in next, {}, 5`
+        generator: Register,
+        // ex. `{}` in `for i, v in next, {}, 5`
+        state: Register,
+        // internal control variable
+        // initial value ex. `5` in `for i, v in next, {}, 5`
+        internal_control: Register,
+        // variables returned by generator call, starting with the external control
+        vars: Vec<Register>,
+    },
+    // skips to the start of the body if the external control isn't nil, after assigning it
+    // to the internal control
+    IterateGenericForLoop {
+        internal_control: Register,
+        external_control: Register,
+        skip: i32,
+    },
+    SetList {
+        table: Register,
+        number_of_elements: u8,
+        first_index: u32,
+    },
+    Close(Register),
+    // marks a `<close>` variable
+    ToBeClosed(Register),
+    Closure {
+        destination: Register,
+        function: Function,
+    },
+    VarArg(Register, u8),
+    PrepVarArg,
+    // follows an arithmetic instruction of 5.4 to call the metamethod when it fails, and is
+    // folded into it while parsing
+    Metamethod {
+        operation: Option<BinaryOperation>,
+        flip: bool,
+    },
+    // an EXTRAARG, which holds the rest of the argument of the instruction before it
+    Data(u32),
+}
+
+// decodes the "floating point byte" table sizes of 5.2 and 5.3
+fn decode_size(value: u16) -> u32 {
+    let value = value as u32;
+    if value < 8 {
+        value
+    } else {
+        ((value & 7) | 8) << ((value >> 3) - 1)
+    }
+}
+
+impl Instruction {
+    // returns the instruction and the EXTRAARG following it, if it has one
+    pub fn parse<'a>(input: &'a [u8], header: &Header) -> IResult<&'a [u8], (Self, Option<u32>)> {
+        let (rest, instruction) = RawInstruction::parse(input, header)?;
+        let (rest, extra_argument) = if instruction.has_extra_argument(header.version) {
+            let (rest, data) = header.parse_instruction(rest)?;
+            (rest, Some(Layout::decode(data, header.version).a_x))
+        } else {
+            (rest, None)
+        };
+        let instruction = Self::decode(instruction, extra_argument.unwrap_or(0), header.version);
+
+        Ok((rest, (instruction, extra_argument)))
+    }
+
+    fn decode(instruction: RawInstruction, extra_argument: u32, version: Version) -> Self {
+        let RawInstruction(operation_code, layout) = instruction;
+        let Layout { a, b, c, k, .. } = layout;
+        let is_54 = version == Version::Lua54;
+        let register = Register;
+        let rk = |value| {
+            if is_54 {
+                Operand::from_k(value, k)
+            } else {
+                Operand::from_rk(value)
+            }
+        };
+        let binary = |lhs, rhs, operation| Self::Binary {
+            destination: register(a),
+            lhs,
+            rhs,
+            operation,
+        };
+        let register_binary = |operation| {
+            if is_54 {
+                binary(
+                    Operand::Register(register(b as u8)),
+                    Operand::Register(register(c as u8)),
+                    operation,
+                )
+            } else {
+                binary(Operand::from_rk(b), Operand::from_rk(c), operation)
+            }
+        };
+        let constant_binary = |operation| {
+            binary(
+                Operand::Register(register(b as u8)),
+                Operand::Constant(Constant(c as u32)),
+                operation,
+            )
+        };
+        let unary = |operation| Self::Unary {
+            destination: register(a),
+            operand: register(b as u8),
+            operation,
+        };
+        // 5.2 and 5.3 compare two RK arguments and use A as the expected result, 5.4 compares A
+        // to B and uses k
+        let compare = |rhs, operation| {
+            if is_54 {
+                Self::Compare {
+                    lhs: Operand::Register(register(a)),
+                    rhs,
+                    operation,
+                    invert: !k,
+                }
+            } else {
+                Self::Compare {
+                    lhs: Operand::from_rk(b),
+                    rhs: Operand::from_rk(c),
+                    operation,
+                    invert: a != 1,
+                }
+            }
+        };
+        // the immediate of EQI and the like, C says whether it was a float
+        let immediate = || {
+            if c != 0 {
+                Operand::Number(layout.s_b() as f64)
+            } else {
+                Operand::Integer(layout.s_b())
+            }
+        };
+
+        match operation_code {
+            OperationCode::Move => Self::Move {
+                destination: register(a),
+                source: register(b as u8),
+            },
+            OperationCode::LoadInteger => Self::LoadInteger {
+                destination: register(a),
+                value: layout.b_sx as i64,
+            },
+            OperationCode::LoadFloat => Self::LoadFloat {
+                destination: register(a),
+                value: layout.b_sx as f64,
+            },
+            OperationCode::LoadConstant => Self::LoadConstant {
+                destination: register(a),
+                source: Constant(layout.b_x),
+            },
+            OperationCode::LoadConstantExtended => Self::LoadConstant {
+                destination: register(a),
+                source: Constant(extra_argument),
+            },
+            OperationCode::LoadBoolean => Self::LoadBoolean {
+                destination: register(a),
+                value: b != 0,
+                skip_next: c != 0,
+            },
+            OperationCode::LoadFalse | OperationCode::LoadFalseSkip | OperationCode::LoadTrue => {
+                Self::LoadBoolean {
+                    destination: register(a),
+                    value: operation_code == OperationCode::LoadTrue,
+                    skip_next: operation_code == OperationCode::LoadFalseSkip,
+                }
+            }
+            OperationCode::LoadNil => {
+                Self::LoadNil((a..=a.wrapping_add(b as u8)).map(register).collect())
+            }
+            OperationCode::GetUpvalue => Self::GetUpvalue {
+                destination: register(a),
+                upvalue: Upvalue(b as u8),
+            },
+            OperationCode::SetUpvalue => Self::SetUpvalue {
+                destination: Upvalue(b as u8),
+                source: register(a),
+            },
+            OperationCode::GetUpvalueIndex => Self::GetUpvalueIndex {
+                destination: register(a),
+                upvalue: Upvalue(b as u8),
+                key: if is_54 {
+                    Operand::Constant(Constant(c as u32))
+                } else {
+                    Operand::from_rk(c)
+                },
+            },
+            OperationCode::SetUpvalueIndex => Self::SetUpvalueIndex {
+                upvalue: Upvalue(a),
+                key: if is_54 {
+                    Operand::Constant(Constant(b as u32))
+                } else {
+                    Operand::from_rk(b)
+                },
+                value: rk(c),
+            },
+            OperationCode::GetIndex => Self::GetIndex {
+                destination: register(a),
+                object: register(b as u8),
+                key: if is_54 {
+                    Operand::Register(register(c as u8))
+                } else {
+                    Operand::from_rk(c)
+                },
+            },
+            OperationCode::GetIndexInteger => Self::GetIndex {
+                destination: register(a),
+                object: register(b as u8),
+                key: Operand::Integer(c as i64),
+            },
+            OperationCode::GetIndexField => Self::GetIndex {
+                destination: register(a),
+                object: register(b as u8),
+                key: Operand::Constant(Constant(c as u32)),
+            },
+            OperationCode::SetIndex => Self::SetIndex {
+                object: register(a),
+                key: if is_54 {
+                    Operand::Register(register(b as u8))
+                } else {
+                    Operand::from_rk(b)
+                },
+                value: rk(c),
+            },
+            OperationCode::SetIndexInteger => Self::SetIndex {
+                object: register(a),
+                key: Operand::Integer(b as i64),
+                value: rk(c),
+            },
+            OperationCode::SetIndexField => Self::SetIndex {
+                object: register(a),
+                key: Operand::Constant(Constant(b as u32)),
+                value: rk(c),
+            },
+            OperationCode::NewTable if is_54 => Self::NewTable {
+                destination: register(a),
+                array_size: c as u32 + if k { extra_argument << 8 } else { 0 },
+                hash_size: if b > 0 { 1 << (b - 1) } else { 0 },
+            },
+            OperationCode::NewTable => Self::NewTable {
+                destination: register(a),
+                array_size: decode_size(b),
+                hash_size: decode_size(c),
+            },
+            OperationCode::PrepMethodCall => Self::PrepMethodCall {
+                destination: register(a),
+                self_arg: register(a.wrapping_add(1)),
+                object: register(b as u8),
+                method: rk(c),
+            },
+            OperationCode::AddImmediate => binary(
+                Operand::Register(register(b as u8)),
+                Operand::Integer(layout.s_c()),
+                BinaryOperation::Add,
+            ),
+            OperationCode::AddConstant => constant_binary(BinaryOperation::Add),
+            OperationCode::SubtractConstant => constant_binary(BinaryOperation::Sub),
+            OperationCode::MultiplyConstant => constant_binary(BinaryOperation::Mul),
+            OperationCode::ModuloConstant => constant_binary(BinaryOperation::Mod),
+            OperationCode::PowerConstant => constant_binary(BinaryOperation::Pow),
+            OperationCode::DivideConstant => constant_binary(BinaryOperation::Div),
+            OperationCode::IntegerDivideConstant => constant_binary(BinaryOperation::IDiv),
+            OperationCode::BitAndConstant => constant_binary(BinaryOperation::BitAnd),
+            OperationCode::BitOrConstant => constant_binary(BinaryOperation::BitOr),
+            OperationCode::BitXorConstant => constant_binary(BinaryOperation::BitXor),
+            OperationCode::ShiftRightImmediate => binary(
+                Operand::Register(register(b as u8)),
+                Operand::Integer(layout.s_c()),
+                BinaryOperation::ShiftRight,
+            ),
+            // the immediate is shifted by the register
+            OperationCode::ShiftLeftImmediate => binary(
+                Operand::Integer(layout.s_c()),
+                Operand::Register(register(b as u8)),
+                BinaryOperation::ShiftLeft,
+            ),
+            OperationCode::Add => register_binary(BinaryOperation::Add),
+            OperationCode::Subtract => register_binary(BinaryOperation::Sub),
+            OperationCode::Multiply => register_binary(BinaryOperation::Mul),
+            OperationCode::Modulo => register_binary(BinaryOperation::Mod),
+            OperationCode::Power => register_binary(BinaryOperation::Pow),
+            OperationCode::Divide => register_binary(BinaryOperation::Div),
+            OperationCode::IntegerDivide => register_binary(BinaryOperation::IDiv),
+            OperationCode::BitAnd => register_binary(BinaryOperation::BitAnd),
+            OperationCode::BitOr => register_binary(BinaryOperation::BitOr),
+            OperationCode::BitXor => register_binary(BinaryOperation::BitXor),
+            OperationCode::ShiftLeft => register_binary(BinaryOperation::ShiftLeft),
+            OperationCode::ShiftRight => register_binary(BinaryOperation::ShiftRight),
+            OperationCode::Metamethod
+            | OperationCode::MetamethodImmediate
+            | OperationCode::MetamethodConstant => Self::Metamethod {
+                operation: BinaryOperation::from_event(c),
+                flip: k,
+            },
+            OperationCode::Minus => unary(UnaryOperation::Minus),
+            OperationCode::BitNot => unary(UnaryOperation::BitNot),
+            OperationCode::Not => unary(UnaryOperation::Not),
+            OperationCode::Length => unary(UnaryOperation::Length),
+            OperationCode::Concatenate if is_54 => Self::Concatenate {
+                destination: register(a),
+                operands: (0..b as u8).map(|i| register(a.wrapping_add(i))).collect(),
+            },
+            OperationCode::Concatenate => Self::Concatenate {
+                destination: register(a),
+                operands: (b..=c).map(|r| register(r as u8)).collect(),
+            },
+            OperationCode::Close => Self::Close(register(a)),
+            OperationCode::ToBeClosed => Self::ToBeClosed(register(a)),
+            OperationCode::Jump if is_54 => Self::Jump {
+                skip: layout.s_j,
+                close: None,
+            },
+            OperationCode::Jump => Self::Jump {
+                skip: layout.b_sx,
+                close: (a != 0).then(|| register(a - 1)),
+            },
+            OperationCode::Equal => {
+                compare(Operand::Register(register(b as u8)), Comparison::Equal)
+            }
+            OperationCode::LessThan => {
+                compare(Operand::Register(register(b as u8)), Comparison::LessThan)
+            }
+            OperationCode::LessThanOrEqual => compare(
+                Operand::Register(register(b as u8)),
+                Comparison::LessThanOrEqual,
+            ),
+            OperationCode::EqualConstant => {
+                compare(Operand::Constant(Constant(b as u32)), Comparison::Equal)
+            }
+            OperationCode::EqualImmediate => compare(immediate(), Comparison::Equal),
+            OperationCode::LessThanImmediate => compare(immediate(), Comparison::LessThan),
+            OperationCode::LessThanOrEqualImmediate => {
+                compare(immediate(), Comparison::LessThanOrEqual)
+            }
+            OperationCode::GreaterThanImmediate => compare(immediate(), Comparison::GreaterThan),
+            OperationCode::GreaterThanOrEqualImmediate => {
+                compare(immediate(), Comparison::GreaterThanOrEqual)
+            }
+            OperationCode::Test => Self::Test {
+                value: register(a),
+                invert: if is_54 { !k } else { c != 1 },
+            },
+            OperationCode::TestSet => Self::TestSet {
+                destination: register(a),
+                value: register(b as u8),
+                invert: if is_54 { !k } else { c != 1 },
+            },
+            OperationCode::Call => Self::Call {
+                function: register(a),
+                arguments: b as u8,
+                return_values: c as u8,
+            },
+            OperationCode::TailCall => Self::TailCall {
+                function: register(a),
+                arguments: b as u8,
+            },
+            OperationCode::Return => Self::Return(register(a), b as u8),
+            OperationCode::Return0 => Self::Return(register(a), 1),
+            OperationCode::Return1 => Self::Return(register(a), 2),
+            OperationCode::IterateNumericForLoop => Self::IterateNumericForLoop {
+                control: (0..4).map(|i| register(a.wrapping_add(i))).collect(),
+                skip: if is_54 {
+                    -(layout.b_x as i32)
+                } else {
+                    layout.b_sx
+                },
+            },
+            OperationCode::InitNumericForLoop => Self::InitNumericForLoop {
+                control: (0..4).map(|i| register(a.wrapping_add(i))).collect(),
+                skip: if is_54 {
+                    layout.b_x as i32
+                } else {
+                    layout.b_sx
+                },
+            },
+            OperationCode::InitGenericForLoop => Self::InitGenericForLoop {
+                skip: layout.b_x as i32,
+            },
+            OperationCode::CallGenericForLoop => {
+                // 5.4 keeps the closing value before the variables
+                let first_var = a.wrapping_add(if is_54 { 4 } else { 3 });
+                Self::CallGenericForLoop {
+                    generator: register(a),
+                    state: register(a.wrapping_add(1)),
+                    internal_control: register(a.wrapping_add(2)),
+                    vars: (0..c as u8)
+                        .map(|i| register(first_var.wrapping_add(i)))
+                        .collect(),
+                }
+            }
+            OperationCode::IterateGenericForLoop if is_54 => Self::IterateGenericForLoop {
+                internal_control: register(a.wrapping_add(2)),
+                external_control: register(a.wrapping_add(4)),
+                skip: -(layout.b_x as i32),
+            },
+            OperationCode::IterateGenericForLoop => Self::IterateGenericForLoop {
+                internal_control: register(a),
+                external_control: register(a.wrapping_add(1)),
+                skip: layout.b_sx,
+            },
+            OperationCode::SetList if is_54 => Self::SetList {
+                table: register(a),
+                number_of_elements: b as u8,
+                first_index: c as u32 + if k { extra_argument << 8 } else { 0 } + 1,
+            },
+            OperationCode::SetList => {
+                let block_number = if c == 0 { extra_argument } else { c as u32 };
+                Self::SetList {
+                    table: register(a),
+                    number_of_elements: b as u8,
+                    first_index: block_number.saturating_sub(1) * 50 + 1,
+                }
+            }
+            OperationCode::Closure => Self::Closure {
+                destination: register(a),
+                function: Function(layout.b_x),
+            },
+            OperationCode::VarArg => Self::VarArg(register(a), if is_54 { c } else { b } as u8),
+            OperationCode::PrepVarArg => Self::PrepVarArg,
+            OperationCode::ExtraArgument => Self::Data(layout.a_x),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use crate::chunk::Version;
+
+// the operation codes of every supported version, which number each one has depends on the
+// version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationCode {
+    Move,
+    LoadInteger,
+    LoadFloat,
+    LoadConstant,
+    LoadConstantExtended,
+    LoadBoolean,
+    LoadFalse,
+    LoadFalseSkip,
+    LoadTrue,
+    LoadNil,
+    GetUpvalue,
+    SetUpvalue,
+    GetUpvalueIndex,
+    GetIndex,
+    GetIndexInteger,
+    GetIndexField,
+    SetUpvalueIndex,
+    SetIndex,
+    SetIndexInteger,
+    SetIndexField,
+    NewTable,
+    PrepMethodCall,
+    AddImmediate,
+    AddConstant,
+    SubtractConstant,
+    MultiplyConstant,
+    ModuloConstant,
+    PowerConstant,
+    DivideConstant,
+    IntegerDivideConstant,
+    BitAndConstant,
+    BitOrConstant,
+    BitXorConstant,
+    ShiftRightImmediate,
+    ShiftLeftImmediate,
+    Add,
+    Subtract,
+    Multiply,
+    Modulo,
+    Power,
+    Divide,
+    IntegerDivide,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Metamethod,
+    MetamethodImmediate,
+    MetamethodConstant,
+    Minus,
+    BitNot,
+    Not,
+    Length,
+    Concatenate,
+    Close,
+    ToBeClosed,
+    Jump,
+    Equal,
+    LessThan,
+    LessThanOrEqual,
+    EqualConstant,
+    EqualImmediate,
+    LessThanImmediate,
+    LessThanOrEqualImmediate,
+    GreaterThanImmediate,
+    GreaterThanOrEqualImmediate,
+    Test,
+    TestSet,
+    Call,
+    TailCall,
+    Return,
+    Return0,
+    Return1,
+    IterateNumericForLoop,
+    InitNumericForLoop,
+    InitGenericForLoop,
+    CallGenericForLoop,
+    IterateGenericForLoop,
+    SetList,
+    Closure,
+    VarArg,
+    PrepVarArg,
+    ExtraArgument,
+}
+
+use OperationCode::*;
+
+const LUA52: [OperationCode; 40] = [
+    Move,
+    LoadConstant,
+    LoadConstantExtended,
+    LoadBoolean,
+    LoadNil,
+    GetUpvalue,
+    GetUpvalueIndex,
+    GetIndex,
+    SetUpvalueIndex,
+    SetUpvalue,
+    SetIndex,
+    NewTable,
+    PrepMethodCall,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    Minus,
+    Not,
+    Length,
+    Concatenate,
+    Jump,
+    Equal,
+    LessThan,
+    LessThanOrEqual,
+    Test,
+    TestSet,
+    Call,
+    TailCall,
+    Return,
+    IterateNumericForLoop,
+    InitNumericForLoop,
+    CallGenericForLoop,
+    IterateGenericForLoop,
+    SetList,
+    Closure,
+    VarArg,
+    ExtraArgument,
+];
+
+const LUA53: [OperationCode; 47] = [
+    Move,
+    LoadConstant,
+    LoadConstantExtended,
+    LoadBoolean,
+    LoadNil,
+    GetUpvalue,
+    GetUpvalueIndex,
+    GetIndex,
+    SetUpvalueIndex,
+    SetUpvalue,
+    SetIndex,
+    NewTable,
+    PrepMethodCall,
+    Add,
+    Subtract,
+    Multiply,
+    Modulo,
+    Power,
+    Divide,
+    IntegerDivide,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Minus,
+    BitNot,
+    Not,
+    Length,
+    Concatenate,
+    Jump,
+    Equal,
+    LessThan,
+    LessThanOrEqual,
+    Test,
+    TestSet,
+    Call,
+    TailCall,
+    Return,
+    IterateNumericForLoop,
+    InitNumericForLoop,
+    CallGenericForLoop,
+    IterateGenericForLoop,
+    SetList,
+    Closure,
+    VarArg,
+    ExtraArgument,
+];
+
+const LUA54: [OperationCode; 83] = [
+    Move,
+    LoadInteger,
+    LoadFloat,
+    LoadConstant,
+    LoadConstantExtended,
+    LoadFalse,
+    LoadFalseSkip,
+    LoadTrue,
+    LoadNil,
+    GetUpvalue,
+    SetUpvalue,
+    GetUpvalueIndex,
+    GetIndex,
+    GetIndexInteger,
+    GetIndexField,
+    SetUpvalueIndex,
+    SetIndex,
+    SetIndexInteger,
+    SetIndexField,
+    NewTable,
+    PrepMethodCall,
+    AddImmediate,
+    AddConstant,
+    SubtractConstant,
+    MultiplyConstant,
+    ModuloConstant,
+    PowerConstant,
+    DivideConstant,
+    IntegerDivideConstant,
+    BitAndConstant,
+    BitOrConstant,
+    BitXorConstant,
+    ShiftRightImmediate,
+    ShiftLeftImmediate,
+    Add,
+    Subtract,
+    Multiply,
+    Modulo,
+    Power,
+    Divide,
+    IntegerDivide,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Metamethod,
+    MetamethodImmediate,
+    MetamethodConstant,
+    Minus,
+    BitNot,
+    Not,
+    Length,
+    Concatenate,
+    Close,
+    ToBeClosed,
+    Jump,
+    Equal,
+    LessThan,
+    LessThanOrEqual,
+    EqualConstant,
+    EqualImmediate,
+    LessThanImmediate,
+    LessThanOrEqualImmediate,
+    GreaterThanImmediate,
+    GreaterThanOrEqualImmediate,
+    Test,
+    TestSet,
+    Call,
+    TailCall,
+    Return,
+    Return0,
+    Return1,
+    IterateNumericForLoop,
+    InitNumericForLoop,
+    InitGenericForLoop,
+    CallGenericForLoop,
+    IterateGenericForLoop,
+    SetList,
+    Closure,
+    VarArg,
+    PrepVarArg,
+    ExtraArgument,
+];
+
+impl OperationCode {
+    pub fn decode(instruction: u32, version: Version) -> Option<Self> {
+        let (table, operation_code): (&[Self], _) = match version {
+            Version::Lua52 => (&LUA52, instruction & 0x3F),
+            Version::Lua53 => (&LUA53, instruction & 0x3F),
+            Version::Lua54 => (&LUA54, instruction & 0x7F),
+        };
+        table.get(operation_code as usize).copied()
+    }
+}
@@ -0,0 +1,71 @@
+use nom::{multi::count, number::complete::le_i8, IResult};
+
+use crate::chunk::{Header, Version};
+
+#[derive(Debug)]
+pub struct Position {
+    pub instruction: usize,
+    pub source: u32,
+}
+
+// a line difference that isn't one, the line is in the absolute line info instead
+const ABSOLUTE_LINE_INFO: i8 = -0x80;
+
+impl Position {
+    pub fn parse<'a>(
+        input: &'a [u8],
+        header: &Header,
+        line_defined: u32,
+    ) -> IResult<&'a [u8], Vec<Self>> {
+        let (input, positions_length) = header.parse_int(input)?;
+        let (input, source_positions) = match header.version {
+            Version::Lua52 | Version::Lua53 => {
+                count(|i| header.parse_int(i), positions_length as usize)(input)?
+            }
+            // 5.4 stores the difference to the previous instruction's line, with the line
+            // written down every so often
+            Version::Lua54 => {
+                let (input, differences) = count(le_i8, positions_length as usize)(input)?;
+                let (input, absolute_length) = header.parse_int(input)?;
+                let (input, absolute) = count(
+                    |i| {
+                        let (i, pc) = header.parse_int(i)?;
+                        let (i, line) = header.parse_int(i)?;
+                        Ok((i, (pc as usize, line)))
+                    },
+                    absolute_length as usize,
+                )(input)?;
+
+                let mut line = line_defined;
+                let source_positions = differences
+                    .into_iter()
+                    .enumerate()
+                    .map(|(pc, difference)| {
+                        line = if difference == ABSOLUTE_LINE_INFO {
+                            absolute
+                                .iter()
+                                .find(|&&(p, _)| p == pc)
+                                .map_or(line, |&(_, line)| line)
+                        } else {
+                            line.wrapping_add_signed(difference as i32)
+                        };
+                        line
+                    })
+                    .collect();
+                (input, source_positions)
+            }
+        };
+
+        Ok((
+            input,
+            source_positions
+                .iter()
+                .enumerate()
+                .map(|(instruction, &source)| Self {
+                    instruction,
+                    source,
+                })
+                .collect(),
+        ))
+    }
+}
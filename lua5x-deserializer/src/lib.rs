@@ -0,0 +1,11 @@
+#![feature(let_chains)]
+
+pub use function::{Capture, Function};
+pub use instruction::{argument, Instruction};
+pub use value::Value;
+
+pub mod chunk;
+pub mod function;
+pub mod instruction;
+pub mod local;
+pub mod value;
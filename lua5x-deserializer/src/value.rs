@@ -0,0 +1,89 @@
+use enum_as_inner::EnumAsInner;
+use nom::{
+    bytes::complete::take,
+    error::{Error, ErrorKind, ParseError},
+    multi::count,
+    number::complete::le_u8,
+    Err, IResult,
+};
+
+use crate::chunk::{Header, Version};
+
+#[derive(Debug, EnumAsInner)]
+pub enum Value<'a> {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(&'a [u8]),
+}
+
+impl<'a> Value<'a> {
+    pub fn parse(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Self> {
+        let (input, kind) = le_u8(input)?;
+
+        // the type tags include a variant in their high bits from 5.3 on, and 5.4 moved the
+        // booleans into them
+        match (header.version, kind) {
+            (_, 0) => Ok((input, Self::Nil)),
+            (Version::Lua52 | Version::Lua53, 1) => {
+                let (input, value) = le_u8(input)?;
+
+                Ok((input, Self::Boolean(value != 0)))
+            }
+            (Version::Lua54, 1) => Ok((input, Self::Boolean(false))),
+            (Version::Lua54, 17) => Ok((input, Self::Boolean(true))),
+            (Version::Lua52 | Version::Lua53, 3) | (Version::Lua54, 19) => {
+                let (input, value) = header.parse_number(input)?;
+
+                Ok((input, Self::Number(value)))
+            }
+            (Version::Lua53, 19) | (Version::Lua54, 3) => {
+                let (input, value) = header.parse_integer(input)?;
+
+                Ok((input, Self::Integer(value)))
+            }
+            (Version::Lua52, 4) | (Version::Lua53 | Version::Lua54, 4 | 20) => {
+                let (input, value) = parse_string(input, header)?;
+
+                Ok((input, Self::String(value.unwrap_or_default())))
+            }
+            _ => Err(Err::Failure(Error::from_error_kind(
+                input,
+                ErrorKind::Switch,
+            ))),
+        }
+    }
+}
+
+// `None` for the null strings written in place of missing debug info
+pub fn parse_string<'a>(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Option<&'a [u8]>> {
+    let (input, size) = match header.version {
+        // short strings have their size in a byte, 0xFF means a size_t follows
+        Version::Lua53 => match le_u8(input)? {
+            (input, 0xFF) => header.parse_size_t(input)?,
+            (input, size) => (input, size as u32),
+        },
+        _ => header.parse_size_t(input)?,
+    };
+    if size == 0 {
+        return Ok((input, None));
+    }
+    // the size counts a null terminator, which only 5.2 writes
+    let (input, string) = take(size - 1)(input)?;
+    let input = match header.version {
+        Version::Lua52 => take(1usize)(input)?.0,
+        _ => input,
+    };
+    Ok((input, Some(string)))
+}
+
+pub fn parse_strings<'a>(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Vec<&'a [u8]>> {
+    let (input, string_count) = header.parse_int(input)?;
+    let (input, strings) = count(
+        |i| parse_string(i, header).map(|(i, s)| (i, s.unwrap_or_default())),
+        string_count as usize,
+    )(input)?;
+
+    Ok((input, strings))
+}
@@ -0,0 +1,125 @@
+#![feature(let_chains)]
+
+mod lifter;
+
+use std::time::Instant;
+
+use lifter::Lifter;
+use lua5x_deserializer::chunk::Chunk;
+
+pub use decompiler::{
+    DecompileOutput, Fallback, FunctionReport, FunctionStatus, Limits, LineMode, Timings,
+};
+pub use lua51_lifter::DecompileError;
+
+#[cfg(feature = "dhat-heap")]
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+// the lua versions with a front end, told apart by the version byte after the signature.
+// luajit has a signature of its own, and one front end for both of its versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Lua51,
+    Lua52,
+    Lua53,
+    Lua54,
+    LuaJit,
+}
+
+impl Dialect {
+    pub fn sniff(bytecode: &[u8]) -> Option<Self> {
+        if bytecode.starts_with(b"\x1BLJ") {
+            return Some(Self::LuaJit);
+        }
+        match bytecode.strip_prefix(b"\x1BLua")?.first()? {
+            0x51 => Some(Self::Lua51),
+            0x52 => Some(Self::Lua52),
+            0x53 => Some(Self::Lua53),
+            0x54 => Some(Self::Lua54),
+            _ => None,
+        }
+    }
+}
+
+pub fn decompile_bytecode(
+    bytecode: &[u8],
+    limits: &Limits,
+) -> Result<DecompileOutput, DecompileError> {
+    let now = Instant::now();
+    let (_, chunk) = Chunk::parse(bytecode).map_err(|e| DecompileError::new(bytecode, e))?;
+    let deserialize_time = now.elapsed();
+
+    let mut output = decompiler::decompile::<Lifter>(&chunk, limits);
+    output.timings.deserialize = deserialize_time;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decompile(fixture: &str) -> String {
+        let path = format!(
+            "{}/../lua5x-deserializer/fixtures/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            fixture
+        );
+        let output = decompile_bytecode(&std::fs::read(path).unwrap(), &Limits::default()).unwrap();
+        for function in &output.functions {
+            assert_eq!(function.status, FunctionStatus::Ok, "{}", output.source);
+        }
+        output.source
+    }
+
+    #[test]
+    fn sniffs_the_version_byte() {
+        assert_eq!(Dialect::sniff(b"\x1bLuaQ\x00"), Some(Dialect::Lua51));
+        assert_eq!(Dialect::sniff(b"\x1bLuaT\x00"), Some(Dialect::Lua54));
+        assert_eq!(Dialect::sniff(b"\x1bLua"), None);
+        assert_eq!(Dialect::sniff(b"\x1bLJ\x02"), Some(Dialect::LuaJit));
+    }
+
+    #[test]
+    fn lifts_every_version() {
+        for fixture in ["sample_52.luac", "sample_53.luac", "sample_54.luac"] {
+            let source = decompile(fixture);
+            for expected in [
+                "local greeting = \"hello\"",
+                "for k, v in pairs(t) do",
+                "function(...)",
+                "-- upvalues: (ref) count",
+                "shared = counter()",
+                "_ENV[\"not a name\"] = 1",
+            ] {
+                assert!(
+                    source.contains(expected),
+                    "{}: missing {:?} in:\n{}",
+                    fixture,
+                    expected,
+                    source
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lifts_operators() {
+        let source = decompile("operators_54.luac");
+        for expected in [
+            "print(a // b, a & b, a | b, a ~ b, ~a, a << 2, 1 << a, a >> 1, a - 1, 2 + a, 1.5, 3)",
+            "local f <close> = setmetatable({}, {",
+            // declared above the branches that assign it
+            "local g\n",
+            "-- g <close>\nprint(g)",
+            "return a > 5",
+        ] {
+            assert!(
+                source.contains(expected),
+                "missing {:?} in:\n{}",
+                expected,
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,999 @@
+use std::ops::Range;
+
+use by_address::ByAddress;
+use cfg::block::{BlockEdge, BranchType};
+
+use itertools::Itertools;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+
+use ast::{formatter::Formatter, RcLocal, Statement};
+use cfg::function::Function;
+use decompiler::{
+    debug_name, function_count, local_variables, BlockMap, LiftedFunction, RegisterWrites,
+};
+
+use lua5x_deserializer::{
+    argument::{Constant, Operand, Register, Upvalue},
+    chunk::{Chunk, Version},
+    instruction::{BinaryOperation, Comparison, UnaryOperation},
+    Function as BytecodeFunction, Instruction, Value,
+};
+
+use petgraph::stable_graph::NodeIndex;
+
+use triomphe::Arc;
+
+pub struct Lifter<'a> {
+    bytecode: &'a BytecodeFunction<'a>,
+    version: Version,
+    blocks: BlockMap,
+    insert_between: FxHashMap<NodeIndex, (NodeIndex, Statement)>,
+    locals: FxHashMap<Register, RcLocal>,
+    constants: FxHashMap<usize, ast::Literal>,
+    function: Function,
+    upvalues: Vec<RcLocal>,
+    // whether each upvalue is `_ENV`, which is what globals are indexed from
+    environment: Vec<bool>,
+    // the prototypes are paired with their id, which is their index in a preorder traversal of
+    // the function tree, and which of their upvalues are `_ENV`
+    child_functions: FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, Prototype<'a>>,
+    register_writes: RegisterWrites<Register>,
+}
+
+type Prototype<'a> = (usize, &'a BytecodeFunction<'a>, Vec<bool>);
+
+// the control flow of the bytecode, which `BlockMap` splits into blocks
+struct Code<'a>(&'a BytecodeFunction<'a>);
+
+impl decompiler::Code for Code<'_> {
+    fn successors(&self, pc: usize) -> Vec<(isize, BranchType)> {
+        let next = pc as isize + 1;
+        match self.0.code[pc] {
+            Instruction::Compare { .. }
+            | Instruction::Test { .. }
+            | Instruction::TestSet { .. } => {
+                vec![(next, BranchType::Then), (next + 1, BranchType::Else)]
+            }
+            Instruction::IterateNumericForLoop { skip, .. }
+            | Instruction::IterateGenericForLoop { skip, .. } => vec![
+                (next + skip as isize, BranchType::Then),
+                (next, BranchType::Else),
+            ],
+            Instruction::Jump { skip, .. }
+            | Instruction::InitNumericForLoop { skip, .. }
+            | Instruction::InitGenericForLoop { skip } => {
+                vec![(next + skip as isize, BranchType::Unconditional)]
+            }
+            Instruction::LoadBoolean { skip_next, .. } => {
+                vec![(next + skip_next as isize, BranchType::Unconditional)]
+            }
+            Instruction::Return(..) => Vec::new(),
+            _ => vec![(self.next_pc(pc) as isize, BranchType::Unconditional)],
+        }
+    }
+
+    // jumping to an EXTRAARG executes it as an instruction, which we can't lift
+    fn is_valid_pc(&self, pc: isize) -> bool {
+        usize::try_from(pc).is_ok_and(|pc| {
+            self.0
+                .code
+                .get(pc)
+                .is_some_and(|i| !matches!(i, Instruction::Data(_)))
+        })
+    }
+
+    // the instruction after the one at `pc`, skipping over the EXTRAARG holding the rest of its
+    // argument
+    fn next_pc(&self, pc: usize) -> usize {
+        match self.0.code.get(pc + 1) {
+            Some(Instruction::Data(_)) => pc + 2,
+            _ => pc + 1,
+        }
+    }
+}
+
+impl<'a> Lifter<'a> {
+    fn local_variables(&self) -> Vec<(Register, Range<usize>, &'a [u8])> {
+        local_variables(
+            self.bytecode
+                .locals
+                .iter()
+                .map(|l| (l.name, l.range.clone())),
+        )
+    }
+
+    fn allocate_locals(&mut self) {
+        self.upvalues.reserve(self.bytecode.captures.len());
+        for i in 0..self.bytecode.captures.len() {
+            let name = self.bytecode.upvalues.get(i).and_then(|&n| debug_name(n));
+            self.upvalues.push(RcLocal::new(ast::Local::new(name)));
+        }
+
+        self.locals
+            .reserve(self.bytecode.maximum_stack_size as usize);
+        for i in 0..self.bytecode.maximum_stack_size {
+            let local = if i < self.bytecode.number_of_parameters {
+                // the parameters are the first locals
+                let name = self
+                    .bytecode
+                    .locals
+                    .get(i as usize)
+                    .and_then(|l| debug_name(l.name));
+                let local = RcLocal::new(ast::Local::new(name));
+                self.function.parameters.push(local.clone());
+                local
+            } else {
+                RcLocal::default()
+            };
+            self.locals.insert(Register(i), local);
+        }
+        self.function.is_variadic = self.bytecode.vararg_flag != 0;
+    }
+
+    fn constant(&mut self, constant: Constant) -> ast::Literal {
+        let version = self.version;
+        self.constants
+            .entry(constant.0 as usize)
+            .or_insert_with(
+                || match self.bytecode.constants.get(constant.0 as usize).unwrap() {
+                    Value::Nil => ast::Literal::Nil,
+                    Value::Boolean(v) => ast::Literal::Boolean(*v),
+                    Value::Integer(v) => ast::Literal::Integer(*v),
+                    // 5.2 doesn't have integers, so its numbers are written without a `.0`
+                    Value::Number(v) if version != Version::Lua52 && v.is_finite() => {
+                        ast::Literal::Float(*v)
+                    }
+                    Value::Number(v) => ast::Literal::Number(*v),
+                    Value::String(v) => ast::Literal::String(v.to_vec()),
+                },
+            )
+            .clone()
+    }
+
+    fn operand(&mut self, operand: Operand) -> ast::RValue {
+        match operand {
+            Operand::Register(register) => self.locals[&register].clone().into(),
+            Operand::Constant(constant) => self.constant(constant).into(),
+            Operand::Integer(value) => ast::Literal::Integer(value).into(),
+            Operand::Number(value) => ast::Literal::Float(value).into(),
+        }
+    }
+
+    fn upvalue(&self, upvalue: Upvalue) -> ast::RValue {
+        if self.environment[upvalue.0 as usize] {
+            ast::Global::from("_ENV").into()
+        } else {
+            self.upvalues[upvalue.0 as usize].clone().into()
+        }
+    }
+
+    // the name of the global an index of `_ENV` reads or writes, if it can be written as one
+    fn global_name(&mut self, upvalue: Upvalue, key: Operand) -> Option<Vec<u8>> {
+        if !self.environment[upvalue.0 as usize] {
+            return None;
+        }
+        let Operand::Constant(key) = key else {
+            return None;
+        };
+        match self.constant(key) {
+            ast::Literal::String(name) if Formatter::<String>::is_valid_name(&name) => Some(name),
+            _ => None,
+        }
+    }
+
+    // 5.2 and 5.3 start generic for loops with a jump to the generator call, 5.4 with a TFORPREP,
+    // and the loop jumps back to the instruction after either
+    fn generic_for_init(&self, pc: usize, skip: i32) -> Option<ast::GenericForInit> {
+        let call_pc = (pc + 1).checked_add_signed(skip as isize)?;
+        let Some(Instruction::CallGenericForLoop {
+            generator,
+            state,
+            internal_control,
+            ..
+        }) = self.bytecode.code.get(call_pc)
+        else {
+            return None;
+        };
+        let Some(&Instruction::IterateGenericForLoop { skip, .. }) =
+            self.bytecode.code.get(call_pc + 1)
+        else {
+            return None;
+        };
+        if (call_pc + 2).checked_add_signed(skip as isize) != Some(pc + 1) {
+            return None;
+        }
+        let mut init = ast::GenericForInit::new(
+            self.locals[generator].clone(),
+            self.locals[state].clone(),
+            self.locals[internal_control].clone(),
+        );
+        // 5.4 loops take a fourth value, which is closed when the loop ends
+        if self.version == Version::Lua54 {
+            let closing = self.locals[&Register(internal_control.0 + 1)].clone();
+            init.0.left.push(closing.clone().into());
+            init.0.right.push(closing.into());
+        }
+        Some(init)
+    }
+
+    // TODO: rename to one of: lift_instructions, lift_range, lift_instruction_range, lift_block?
+    fn lift_instruction(&mut self, start: usize, end: usize, statements: &mut Vec<Statement>) {
+        if end > start {
+            statements.reserve(end - start + 1);
+        }
+        let mut top: Option<(ast::RValue, u8)> = None;
+        let mut iter = self.bytecode.code[start..=end].iter();
+        // the first instruction that isn't part of a statement yet
+        let mut first_pc = start;
+        while let Some(instruction) = iter.next() {
+            let pc = end - iter.len();
+            let statement_count = statements.len();
+            match instruction {
+                Instruction::Move {
+                    destination,
+                    source,
+                } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[destination].clone().into()],
+                            vec![self.locals[source].clone().into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::LoadBoolean {
+                    destination, value, ..
+                } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![ast::Literal::Boolean(value).into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::LoadConstant {
+                    destination,
+                    source,
+                } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![self.constant(source).into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::LoadInteger { destination, value } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![ast::Literal::Integer(value).into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::LoadFloat { destination, value } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![ast::Literal::Float(value).into()],
+                        )
+                        .into(),
+                    );
+                }
+                Instruction::LoadNil(registers) => {
+                    for register in registers {
+                        statements.push(
+                            ast::Assign::new(
+                                vec![self.locals[register].clone().into()],
+                                vec![ast::Literal::Nil.into()],
+                            )
+                            .into(),
+                        );
+                    }
+                }
+                &Instruction::GetUpvalue {
+                    destination,
+                    upvalue,
+                } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![self.upvalue(upvalue)],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::SetUpvalue {
+                    destination,
+                    source,
+                } => {
+                    let upvalue = if self.environment[destination.0 as usize] {
+                        ast::Global::from("_ENV").into()
+                    } else {
+                        self.upvalues[destination.0 as usize].clone().into()
+                    };
+                    statements.push(
+                        ast::Assign::new(vec![upvalue], vec![self.locals[&source].clone().into()])
+                            .into(),
+                    );
+                }
+                &Instruction::GetUpvalueIndex {
+                    destination,
+                    upvalue,
+                    key,
+                } => {
+                    let value = match self.global_name(upvalue, key) {
+                        Some(name) => ast::Global::new(name).into(),
+                        None => ast::Index::new(self.upvalue(upvalue), self.operand(key)).into(),
+                    };
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![value],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::SetUpvalueIndex {
+                    upvalue,
+                    key,
+                    value,
+                } => {
+                    let target = match self.global_name(upvalue, key) {
+                        Some(name) => ast::Global::new(name).into(),
+                        None => ast::Index {
+                            left: Box::new(self.upvalue(upvalue)),
+                            right: Box::new(self.operand(key)),
+                        }
+                        .into(),
+                    };
+                    let value = self.operand(value);
+                    statements.push(ast::Assign::new(vec![target], vec![value]).into());
+                }
+                &Instruction::GetIndex {
+                    destination,
+                    object,
+                    key,
+                } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![ast::Index::new(
+                                self.locals[&object].clone().into(),
+                                self.operand(key),
+                            )
+                            .into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::SetIndex { object, key, value } => {
+                    let key = self.operand(key);
+                    let value = self.operand(value);
+
+                    statements.push(
+                        ast::Assign::new(
+                            vec![ast::Index {
+                                left: Box::new(self.locals[&object].clone().into()),
+                                right: Box::new(key),
+                            }
+                            .into()],
+                            vec![value],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::Test { value, invert } => {
+                    let value = self.locals[&value].clone().into();
+                    let condition = if invert {
+                        ast::Unary::new(value, ast::UnaryOperation::Not).into()
+                    } else {
+                        value
+                    };
+                    statements.push(
+                        ast::If::new(condition, ast::Block::default(), ast::Block::default())
+                            .into(),
+                    )
+                }
+                &Instruction::Unary {
+                    destination,
+                    operand,
+                    operation,
+                } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![ast::Unary::new(
+                                self.locals[&operand].clone().into(),
+                                match operation {
+                                    UnaryOperation::Minus => ast::UnaryOperation::Negate,
+                                    UnaryOperation::Not => ast::UnaryOperation::Not,
+                                    UnaryOperation::Length => ast::UnaryOperation::Length,
+                                    UnaryOperation::BitNot => ast::UnaryOperation::BitNot,
+                                },
+                            )
+                            .into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::Return(values, b) => {
+                    let values = if b != 0 {
+                        (values.0..values.0 + (b - 1))
+                            .map(|r| self.locals[&Register(r)].clone().into())
+                            .collect()
+                    } else {
+                        let (tail, end) = top.take().unwrap();
+                        (values.0..end)
+                            .map(|r| self.locals[&Register(r)].clone().into())
+                            .chain(std::iter::once(tail))
+                            .collect()
+                    };
+                    statements.push(ast::Return::new(values).into());
+                }
+                &Instruction::Jump { skip, close } => {
+                    if let Some(start) = close {
+                        let locals = (start.0..self.bytecode.maximum_stack_size)
+                            .map(|i| self.locals[&Register(i)].clone())
+                            .collect();
+                        statements.push(ast::Close { locals }.into());
+                    }
+                    if let Some(init) = self.generic_for_init(pc, skip) {
+                        statements.push(init.into());
+                    }
+                }
+                &Instruction::InitGenericForLoop { skip } => {
+                    if let Some(init) = self.generic_for_init(pc, skip) {
+                        statements.push(init.into());
+                    }
+                }
+                Instruction::IterateGenericForLoop { .. }
+                | Instruction::PrepVarArg
+                | Instruction::Metamethod { .. }
+                | Instruction::Data(..) => {}
+                &Instruction::Binary {
+                    destination,
+                    lhs,
+                    rhs,
+                    operation,
+                } => {
+                    let lhs = self.operand(lhs);
+                    let rhs = self.operand(rhs);
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![ast::Binary::new(
+                                lhs,
+                                rhs,
+                                match operation {
+                                    BinaryOperation::Add => ast::BinaryOperation::Add,
+                                    BinaryOperation::Sub => ast::BinaryOperation::Sub,
+                                    BinaryOperation::Mul => ast::BinaryOperation::Mul,
+                                    BinaryOperation::Div => ast::BinaryOperation::Div,
+                                    BinaryOperation::IDiv => ast::BinaryOperation::IDiv,
+                                    BinaryOperation::Mod => ast::BinaryOperation::Mod,
+                                    BinaryOperation::Pow => ast::BinaryOperation::Pow,
+                                    BinaryOperation::BitAnd => ast::BinaryOperation::BitAnd,
+                                    BinaryOperation::BitOr => ast::BinaryOperation::BitOr,
+                                    BinaryOperation::BitXor => ast::BinaryOperation::BitXor,
+                                    BinaryOperation::ShiftLeft => ast::BinaryOperation::ShiftLeft,
+                                    BinaryOperation::ShiftRight => ast::BinaryOperation::ShiftRight,
+                                },
+                            )
+                            .into()],
+                        )
+                        .into(),
+                    );
+                }
+                Instruction::Concatenate {
+                    destination,
+                    operands,
+                } => {
+                    assert!(operands.len() >= 2);
+                    let mut operands = operands.iter().rev();
+
+                    let right = operands.next().unwrap();
+                    let left = operands.next().unwrap();
+                    let mut concat = ast::Binary::new(
+                        self.locals[left].clone().into(),
+                        self.locals[right].clone().into(),
+                        ast::BinaryOperation::Concat,
+                    );
+                    for r in operands {
+                        concat = ast::Binary::new(
+                            self.locals[r].clone().into(),
+                            concat.into(),
+                            ast::BinaryOperation::Concat,
+                        );
+                    }
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[destination].clone().into()],
+                            vec![concat.into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::Compare {
+                    lhs,
+                    rhs,
+                    operation,
+                    invert,
+                } => {
+                    let lhs = self.operand(lhs);
+                    let rhs = self.operand(rhs);
+                    let value = ast::Binary::new(
+                        lhs,
+                        rhs,
+                        match operation {
+                            Comparison::Equal => ast::BinaryOperation::Equal,
+                            Comparison::LessThan => ast::BinaryOperation::LessThan,
+                            Comparison::LessThanOrEqual => ast::BinaryOperation::LessThanOrEqual,
+                            Comparison::GreaterThan => ast::BinaryOperation::GreaterThan,
+                            Comparison::GreaterThanOrEqual => {
+                                ast::BinaryOperation::GreaterThanOrEqual
+                            }
+                        },
+                    )
+                    .into();
+                    let condition = if invert {
+                        ast::Unary::new(value, ast::UnaryOperation::Not).into()
+                    } else {
+                        value
+                    };
+                    statements.push(
+                        ast::If::new(condition, ast::Block::default(), ast::Block::default())
+                            .into(),
+                    )
+                }
+                Instruction::TestSet {
+                    destination,
+                    value,
+                    invert,
+                } => {
+                    let value: ast::RValue = self.locals[value].clone().into();
+                    statements.push(
+                        ast::If::new(
+                            if *invert {
+                                ast::Unary {
+                                    value: Box::new(value.clone()),
+                                    operation: ast::UnaryOperation::Not,
+                                }
+                                .into()
+                            } else {
+                                value.clone()
+                            },
+                            ast::Block::default(),
+                            ast::Block::default(),
+                        )
+                        .into(),
+                    );
+
+                    let assign = ast::Assign::new(
+                        vec![self.locals[destination].clone().into()],
+                        vec![value.clone()],
+                    );
+
+                    self.function
+                        .block_mut(self.blocks.target(end as isize + 1))
+                        .unwrap()
+                        .push(assign.into());
+                }
+                &Instruction::PrepMethodCall {
+                    destination,
+                    self_arg,
+                    object,
+                    method,
+                } => {
+                    let destination = self.locals[&destination].clone();
+                    let self_arg = self.locals[&self_arg].clone();
+                    let object = self.locals[&object].clone();
+                    statements.push(
+                        ast::Assign::new(vec![self_arg.into()], vec![object.clone().into()]).into(),
+                    );
+                    statements.push(
+                        ast::Assign::new(
+                            vec![destination.into()],
+                            vec![ast::Index::new(object.into(), self.operand(method)).into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::TailCall {
+                    function,
+                    arguments,
+                }
+                | &Instruction::Call {
+                    function,
+                    arguments,
+                    ..
+                } => {
+                    let arguments = if arguments != 0 {
+                        (function.0 + 1..function.0 + arguments)
+                            .map(|r| self.locals[&Register(r)].clone().into())
+                            .collect()
+                    } else {
+                        let top = top.take().unwrap();
+                        (function.0 + 1..top.1)
+                            .map(|r| self.locals[&Register(r)].clone().into())
+                            .chain(std::iter::once(top.0))
+                            .collect()
+                    };
+
+                    let call = ast::Call::new(self.locals[&function].clone().into(), arguments);
+
+                    if let &Instruction::Call { return_values, .. } = instruction
+                        && return_values != 0
+                    {
+                        if return_values == 1 {
+                            statements.push(call.into());
+                        } else {
+                            statements.push(
+                                ast::Assign::new(
+                                    (function.0..function.0 + return_values - 1)
+                                        .map(|r| self.locals[&Register(r)].clone().into())
+                                        .collect_vec(),
+                                    vec![ast::RValue::Select(call.into())],
+                                )
+                                .into(),
+                            );
+                        }
+                    } else {
+                        top = Some((call.into(), function.0));
+                    }
+                }
+                &Instruction::VarArg(destination, b) => {
+                    let vararg = ast::VarArg {};
+                    if b != 0 {
+                        statements.push(
+                            ast::Assign::new(
+                                (destination.0..destination.0 + b - 1)
+                                    .map(|r| self.locals[&Register(r)].clone().into())
+                                    .collect(),
+                                vec![ast::RValue::Select(vararg.into())],
+                            )
+                            .into(),
+                        );
+                    } else {
+                        top = Some((vararg.into(), destination.0));
+                    }
+                }
+                Instruction::Closure {
+                    destination,
+                    function,
+                } => {
+                    let closure = &self.bytecode.closures[function.0 as usize];
+
+                    // `_ENV` isn't passed on, globals are written as globals in the closure too
+                    let environment = closure
+                        .captures
+                        .iter()
+                        .map(|capture| {
+                            !capture.in_stack && self.environment[capture.index as usize]
+                        })
+                        .collect::<Vec<_>>();
+                    let upvalues_passed = closure
+                        .captures
+                        .iter()
+                        .zip(&environment)
+                        .filter(|(_, &is_environment)| !is_environment)
+                        .map(|(capture, _)| {
+                            if capture.in_stack {
+                                self.locals[&Register(capture.index)].clone()
+                            } else {
+                                self.upvalues[capture.index as usize].clone()
+                            }
+                        })
+                        .collect::<Vec<_>>();
+
+                    let ast_function = Arc::<Mutex<_>>::default();
+
+                    let closure_id = self.function.id
+                        + 1
+                        + self.bytecode.closures[..function.0 as usize]
+                            .iter()
+                            .map(|closure| function_count(closure, |f| &f.closures))
+                            .sum::<usize>();
+                    self.child_functions.insert(
+                        ByAddress(ast_function.clone()),
+                        (closure_id, closure, environment),
+                    );
+
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[destination].clone().into()],
+                            vec![ast::Closure {
+                                function: ByAddress(ast_function),
+                                upvalues: upvalues_passed
+                                    .into_iter()
+                                    .map(ast::Upvalue::Ref)
+                                    .collect(),
+                            }
+                            .into()],
+                        )
+                        .into(),
+                    );
+                }
+                Instruction::NewTable { destination, .. } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[destination].clone().into()],
+                            vec![ast::Table::default().into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::SetList {
+                    table,
+                    number_of_elements,
+                    first_index,
+                } => {
+                    let setlist = if number_of_elements != 0 {
+                        ast::SetList::new(
+                            self.locals[&table].clone(),
+                            first_index as usize,
+                            (table.0 + 1..table.0 + 1 + number_of_elements)
+                                .map(|r| self.locals[&Register(r)].clone().into())
+                                .collect(),
+                            None,
+                        )
+                    } else {
+                        let top = top.take().unwrap();
+                        ast::SetList::new(
+                            self.locals[&table].clone(),
+                            first_index as usize,
+                            (table.0 + 1..top.1)
+                                .map(|r| self.locals[&Register(r)].clone().into())
+                                .collect(),
+                            Some(top.0),
+                        )
+                    };
+                    statements.push(setlist.into());
+                }
+                Instruction::Close(start) => {
+                    let locals = (start.0..self.bytecode.maximum_stack_size)
+                        .map(|i| self.locals[&Register(i)].clone())
+                        .collect();
+                    statements.push(ast::Close { locals }.into());
+                }
+                // the value to close was just written to the register
+                &Instruction::ToBeClosed(register) => {
+                    let local = &self.locals[&register];
+                    if let Some((assign, index)) =
+                        statements.iter_mut().rev().find_map(|statement| {
+                            let assign = statement.as_assign_mut()?;
+                            let index = assign
+                                .left
+                                .iter()
+                                .position(|l| l.as_local() == Some(local))?;
+                            Some((assign, index))
+                        })
+                    {
+                        assign.set_attribute(index, ast::Attribute::Close);
+                    } else {
+                        // written in another block, so there's no declaration to mark
+                        let name = self
+                            .local_variables()
+                            .into_iter()
+                            .filter(|(r, range, _)| *r == register && range.contains(&pc))
+                            .max_by_key(|(_, range, _)| range.start)
+                            .and_then(|(_, _, name)| debug_name(name))
+                            .unwrap_or_else(|| format!("register {}", register.0));
+                        statements.push(ast::Comment::new(format!("{} <close>", name)).into());
+                    }
+                }
+                Instruction::InitNumericForLoop { control, .. } => {
+                    let (internal_counter, limit, step) = (
+                        self.locals[&control[0]].clone(),
+                        self.locals[&control[1]].clone(),
+                        self.locals[&control[2]].clone(),
+                    );
+                    statements.push(ast::NumForInit::new(internal_counter, limit, step).into());
+                }
+                &Instruction::IterateNumericForLoop { ref control, skip } => {
+                    let (internal_counter, limit, step, external_counter) = (
+                        self.locals[&control[0]].clone(),
+                        self.locals[&control[1]].clone(),
+                        self.locals[&control[2]].clone(),
+                        self.locals[&control[3]].clone(),
+                    );
+                    statements.push(
+                        ast::NumForNext::new(internal_counter.clone(), limit.into(), step.into())
+                            .into(),
+                    );
+
+                    let body_node = self.blocks.target(end as isize + 1 + skip as isize);
+                    assert!(self
+                        .insert_between
+                        .insert(
+                            self.blocks.node(start),
+                            (
+                                body_node,
+                                ast::Assign::new(
+                                    vec![external_counter.into()],
+                                    vec![internal_counter.into()],
+                                )
+                                .into()
+                            )
+                        )
+                        .is_none());
+                }
+                // the TFORLOOP after it tests the first variable and assigns it to the internal
+                // control, which is what `GenericForNext` does
+                Instruction::CallGenericForLoop {
+                    generator,
+                    state,
+                    vars,
+                    ..
+                } => {
+                    statements.push(
+                        ast::GenericForNext::new(
+                            vars.iter().map(|x| self.locals[x].clone()).collect(),
+                            self.locals[generator].clone().into(),
+                            self.locals[state].clone(),
+                        )
+                        .into(),
+                    );
+                }
+            }
+
+            // the loop variables are written by the generator call, but only in scope at the
+            // start of the body, which the TFORLOOP after it goes to
+            let written_pc = match (instruction, self.bytecode.code.get(pc + 1)) {
+                (
+                    Instruction::CallGenericForLoop { .. },
+                    Some(&Instruction::IterateGenericForLoop { skip, .. }),
+                ) => (pc + 2).checked_add_signed(skip as isize).unwrap_or(pc),
+                _ => pc,
+            };
+            let node = self.blocks.node(start);
+            for (statement_index, statement) in statements.iter().enumerate().skip(statement_count)
+            {
+                self.register_writes.record(
+                    &self.locals,
+                    node,
+                    statement_index,
+                    statement,
+                    written_pc,
+                );
+            }
+
+            if statements.len() > statement_count {
+                let end_pc = end + 1 - iter.len();
+                let origin = ast::Origin::new(
+                    self.bytecode
+                        .positions
+                        .get(pc)
+                        .map(|position| position.source as usize),
+                    first_pc..end_pc,
+                );
+                for statement in &mut statements[statement_count..] {
+                    if let Some(statement_origin) = statement.origin_mut() {
+                        *statement_origin = origin.clone();
+                    }
+                }
+                first_pc = end_pc;
+            }
+
+            if matches!(instruction, Instruction::Return { .. }) {
+                break;
+            }
+        }
+    }
+
+    fn lift_blocks(&mut self) {
+        let code = Code(self.bytecode);
+        for (start, end) in self.blocks.ranges(&code) {
+            // the block may already hold statements, see `TestSet`
+            let mut statements =
+                std::mem::take(self.function.block_mut(self.blocks.node(start)).unwrap());
+            self.lift_instruction(start, end, &mut statements);
+            *self.function.block_mut(self.blocks.node(start)).unwrap() = statements;
+
+            let edges = self.blocks.edges(&code, end);
+            self.function.set_edges(self.blocks.node(start), edges);
+        }
+    }
+}
+
+impl<'a> decompiler::Lifter<'a> for Lifter<'a> {
+    type Chunk = Chunk<'a>;
+    type Prototype = Prototype<'a>;
+
+    const STRUCTURE_METHOD_CALLS: bool = true;
+
+    // the main function's only upvalue is `_ENV`
+    fn main(chunk: &'a Chunk<'a>) -> Self::Prototype {
+        (
+            0,
+            &chunk.function,
+            vec![true; chunk.function.captures.len()],
+        )
+    }
+
+    fn id((id, ..): &Self::Prototype) -> usize {
+        *id
+    }
+
+    fn disassemble(_: &'a Chunk<'a>, (_, bytecode, _): &Self::Prototype) -> Vec<String> {
+        bytecode
+            .code
+            .iter()
+            .enumerate()
+            .map(|(pc, instruction)| format!("{:>4} {:?}", pc, instruction))
+            .collect()
+    }
+
+    fn lift(
+        chunk: &'a Chunk<'a>,
+        (id, bytecode, environment): Self::Prototype,
+    ) -> LiftedFunction<Self::Prototype> {
+        let mut function = Function::new(id);
+        let blocks = BlockMap::new(&Code(bytecode), &mut function);
+        let mut context = Self {
+            bytecode,
+            version: chunk.header.version,
+            blocks,
+            insert_between: FxHashMap::default(),
+            locals: FxHashMap::default(),
+            constants: FxHashMap::default(),
+            function,
+            upvalues: Vec::new(),
+            environment,
+            child_functions: FxHashMap::default(),
+            register_writes: RegisterWrites::default(),
+        };
+
+        context.allocate_locals();
+        context.lift_blocks();
+
+        let stack_init_node = context.function.new_block();
+        let stack_init_block = context.function.block_mut(stack_init_node).unwrap();
+        stack_init_block.reserve(context.locals.len());
+        for local in context.locals.values() {
+            if !context.function.parameters.contains(local) {
+                let stack_init_block = context.function.block_mut(stack_init_node).unwrap();
+                stack_init_block.push(
+                    ast::Assign::new(vec![local.clone().into()], vec![ast::Literal::Nil.into()])
+                        .into(),
+                )
+            }
+        }
+        context.function.set_edges(
+            stack_init_node,
+            vec![(
+                context.blocks.target(0),
+                BlockEdge::new(BranchType::Unconditional),
+            )],
+        );
+        context.function.set_entry(stack_init_node);
+
+        context.blocks.insert_between(
+            &mut context.function,
+            std::mem::take(&mut context.insert_between),
+            &mut context.register_writes,
+            &context.locals,
+        );
+        let local_variables = context.local_variables();
+        context
+            .register_writes
+            .name(&local_variables, &mut context.function);
+
+        // `_ENV` isn't captured, see `Instruction::Closure`
+        let upvalues = context
+            .upvalues
+            .into_iter()
+            .zip(&context.environment)
+            .filter(|(_, &is_environment)| !is_environment)
+            .map(|(upvalue, _)| upvalue)
+            .collect();
+        LiftedFunction {
+            function: context.function,
+            upvalues,
+            child_functions: context.child_functions,
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+    time::Instant,
+};
+
+use anyhow::anyhow;
+use clap::{Parser, ValueEnum};
+use lua5x_lifter::{Dialect, LineMode};
+
+#[derive(Parser, Debug)]
+#[clap(about, version, author)]
+struct Args {
+    #[clap(short, long)]
+    file: String,
+    /// Use the bytecode's line info to annotate or lay out statements
+    #[clap(long, value_enum)]
+    line_info: Option<LineInfo>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LineInfo {
+    /// Precede statements with `-- line N` comments
+    Annotate,
+    /// Pad with blank lines so statements land on their original line
+    Preserve,
+}
+
+fn main() -> anyhow::Result<()> {
+    #[cfg(feature = "dhat-heap")]
+    let _profiler = dhat::Profiler::new_heap();
+
+    let args = Args::parse();
+    let path = Path::new(&args.file);
+    let mut input = File::open(path)?;
+    let mut buffer = vec![0; input.metadata()?.len() as usize];
+    input.read_exact(&mut buffer)?;
+
+    let start = Instant::now();
+    let dialect = Dialect::sniff(&buffer)
+        .ok_or_else(|| anyhow!("not a lua 5.1 to 5.4 or luajit bytecode chunk"))?;
+    let output = match dialect {
+        Dialect::Lua51 => lua51_lifter::decompile_bytecode(&buffer, &Default::default())?,
+        Dialect::LuaJit => luajit_lifter::decompile_bytecode(&buffer, &Default::default())?,
+        _ => lua5x_lifter::decompile_bytecode(&buffer, &Default::default())?,
+    };
+    let res = match args.line_info {
+        None => output.source,
+        Some(LineInfo::Annotate) => output.render(LineMode::Annotate),
+        Some(LineInfo::Preserve) => output.render(LineMode::Preserve),
+    };
+    let duration = start.elapsed();
+
+    let extension = match dialect {
+        Dialect::Lua51 => "dec.51.lua",
+        Dialect::Lua52 => "dec.52.lua",
+        Dialect::Lua53 => "dec.53.lua",
+        Dialect::Lua54 => "dec.54.lua",
+        Dialect::LuaJit => "dec.jit.lua",
+    };
+    // TODO: use BufWriter?
+    let mut out = File::create(path.with_extension(extension).file_name().unwrap())?;
+    writeln!(out, "-- decompiled by Sentinel (took {:?})", duration)?;
+    writeln!(out, "{}", res)?;
+
+    Ok(())
+}
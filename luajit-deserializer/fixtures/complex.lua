@@ -0,0 +1,3 @@
+local z = 2i
+local w = -1.5i
+print(z * w, -z, 1e999i)
@@ -0,0 +1,32 @@
+local greeting = "hello"
+local t = {1, 2, 3, x = "y"}
+for i = 1, #t, 2 do
+  print(i, t[i])
+end
+for k, v in pairs(t) do
+  if k == "x" and v ~= nil then
+    print(k, v)
+  end
+end
+for k, v in ipairs(t) do
+  print(k, v)
+end
+local function counter()
+  local count = 0
+  return function(...)
+    count = count + select("#", ...)
+    return count
+  end
+end
+shared = counter()
+local big = 0x7fffffffffffffffLL
+local unsigned = 18446744073709551615ULL
+if greeting < "z" then
+  print(big, unsigned, -3, true, nil)
+end
+while shared() < 10 do
+  local x = {}
+  for i = 1, 3 do
+    x[i] = function() return i end
+  end
+end
@@ -0,0 +1,98 @@
+use nom::{
+    bytes::complete::{tag, take},
+    error::{Error, ErrorKind, ParseError},
+    number::complete::{be_u16, be_u32, le_u16, le_u32, le_u8},
+    Err, IResult,
+};
+
+use crate::leb128::parse_uleb128;
+
+// the version byte, which changed when 2.1 added opcodes and the two-slot frame layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    LuaJit20,
+    LuaJit21,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Big,
+    Little,
+}
+
+const FLAG_BIG_ENDIAN: u32 = 0x01;
+const FLAG_STRIP: u32 = 0x02;
+const FLAG_FFI: u32 = 0x04;
+const FLAG_FR2: u32 = 0x08;
+
+// describes how the rest of the dump is encoded, every parser after the header takes it
+#[derive(Debug, Clone, Copy)]
+pub struct Header {
+    pub version: Version,
+    // only the instructions and the fixed width fields of the debug info depend on it
+    pub endianness: Endianness,
+    // whether the debug info was left out
+    pub is_stripped: bool,
+    // whether a prototype loads 64-bit integer or complex cdata constants
+    pub uses_ffi: bool,
+    // 64-bit builds of 2.1 keep a second frame slot after the function of a call, which moves
+    // its arguments up by one register
+    pub two_slot_frame: bool,
+}
+
+pub(crate) fn fail<T>(input: &[u8], kind: ErrorKind) -> IResult<&[u8], T> {
+    Err(Err::Failure(Error::from_error_kind(input, kind)))
+}
+
+impl Header {
+    // returns the header and the chunk name, which stripped dumps don't have
+    pub fn parse(input: &[u8]) -> IResult<&[u8], (Self, &[u8])> {
+        let (input, _) = tag("\x1BLJ")(input)?;
+        let (input, version) = match le_u8(input)? {
+            (input, 1) => Ok((input, Version::LuaJit20)),
+            (input, 2) => Ok((input, Version::LuaJit21)),
+            _ => fail(input, ErrorKind::Switch),
+        }?;
+        let (rest, flags) = parse_uleb128(input)?;
+        let known_flags = match version {
+            Version::LuaJit20 => FLAG_BIG_ENDIAN | FLAG_STRIP | FLAG_FFI,
+            Version::LuaJit21 => FLAG_BIG_ENDIAN | FLAG_STRIP | FLAG_FFI | FLAG_FR2,
+        };
+        if flags & !known_flags != 0 {
+            return fail(input, ErrorKind::Verify);
+        }
+        let header = Self {
+            version,
+            endianness: if flags & FLAG_BIG_ENDIAN != 0 {
+                Endianness::Big
+            } else {
+                Endianness::Little
+            },
+            is_stripped: flags & FLAG_STRIP != 0,
+            uses_ffi: flags & FLAG_FFI != 0,
+            two_slot_frame: flags & FLAG_FR2 != 0,
+        };
+        let (input, name) = if header.is_stripped {
+            (rest, &[][..])
+        } else {
+            let (rest, length) = parse_uleb128(rest)?;
+            take(length)(rest)?
+        };
+
+        Ok((input, (header, name)))
+    }
+
+    pub(crate) fn parse_u16<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], u16> {
+        match self.endianness {
+            Endianness::Big => be_u16(input),
+            Endianness::Little => le_u16(input),
+        }
+    }
+
+    pub(crate) fn parse_u32<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], u32> {
+        match self.endianness {
+            Endianness::Big => be_u32(input),
+            Endianness::Little => le_u32(input),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use nom::{bytes::complete::take, error::ErrorKind, IResult};
+
+pub use header::{Header, Version};
+
+use crate::{function::Function, leb128::parse_uleb128};
+
+pub mod header;
+
+#[derive(Debug)]
+pub struct Chunk<'a> {
+    pub header: Header,
+    pub name: &'a [u8],
+    pub function: Function<'a>,
+}
+
+impl<'a> Chunk<'a> {
+    pub fn parse(input: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (mut input, (header, name)) = Header::parse(input)?;
+        // the prototypes are dumped children first, a parent takes its children off the stack
+        // and the main function is left
+        let mut stack = Vec::new();
+        while !input.is_empty() {
+            let (rest, length) = parse_uleb128(input)?;
+            input = rest;
+            if length == 0 {
+                break;
+            }
+            let (rest, prototype) = take(length)(input)?;
+            let (prototype, function) = Function::parse(prototype, &header, &mut stack)?;
+            if !prototype.is_empty() {
+                return header::fail(prototype, ErrorKind::Verify);
+            }
+            stack.push(function);
+            input = rest;
+        }
+        let function = match (stack.pop(), stack.is_empty()) {
+            (Some(function), true) => function,
+            _ => return header::fail(input, ErrorKind::Verify),
+        };
+
+        Ok((
+            input,
+            Self {
+                header,
+                name,
+                function,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    // the fixtures are `sample.lua` dumped by a 64-bit LuaJIT 2.1, with and without debug info
+    #[test]
+    fn parses_dumps() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        for (name, is_stripped) in [("sample.ljbc", false), ("sample_stripped.ljbc", true)] {
+            let input = fs::read(fixtures.join(name)).unwrap();
+            let (rest, chunk) = Chunk::parse(&input).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert!(rest.is_empty(), "{}: trailing bytes", name);
+            assert_eq!(chunk.header.version, Version::LuaJit21, "{}", name);
+            assert_eq!(chunk.header.is_stripped, is_stripped, "{}", name);
+            assert!(chunk.header.two_slot_frame, "{}", name);
+            assert_eq!(chunk.function.closures.len(), 2, "{}", name);
+            let local_names = chunk
+                .function
+                .locals
+                .iter()
+                .map(|l| l.name)
+                .filter(|n| !n.starts_with(b"("))
+                .collect::<Vec<_>>();
+            if is_stripped {
+                assert!(local_names.is_empty(), "{}", name);
+            } else {
+                assert_eq!(
+                    local_names,
+                    [
+                        &b"greeting"[..],
+                        b"t",
+                        b"i",
+                        b"k",
+                        b"v",
+                        b"k",
+                        b"v",
+                        b"counter",
+                        b"big",
+                        b"unsigned",
+                        b"x",
+                        b"i",
+                    ],
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+}
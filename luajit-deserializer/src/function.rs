@@ -0,0 +1,179 @@
+use nom::{
+    bytes::complete::take, error::ErrorKind, multi::count, number::complete::le_u8, IResult,
+};
+
+use crate::{
+    chunk::{header::fail, Header},
+    instruction::Instruction,
+    leb128::parse_uleb128,
+    local::{self, Local},
+    value::{Number, Value},
+};
+
+// where a closure gets an upvalue from, either a register or an upvalue of the enclosing function
+#[derive(Debug, Clone, Copy)]
+pub struct Capture {
+    pub in_stack: bool,
+    // the upvalue is never assigned to after it's captured
+    pub is_immutable: bool,
+    pub index: u8,
+}
+
+const UV_LOCAL: u16 = 0x8000;
+const UV_IMMUTABLE: u16 = 0x4000;
+
+impl Capture {
+    fn parse<'a>(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Self> {
+        let (input, capture) = header.parse_u16(input)?;
+
+        Ok((
+            input,
+            Self {
+                in_stack: capture & UV_LOCAL != 0,
+                is_immutable: capture & UV_IMMUTABLE != 0,
+                index: capture as u8,
+            },
+        ))
+    }
+}
+
+const FLAG_VARARG: u8 = 0x02;
+
+#[derive(Debug)]
+pub struct Function<'a> {
+    pub line_defined: u32,
+    pub number_of_lines: u32,
+    pub is_variadic: bool,
+    pub number_of_parameters: u8,
+    pub frame_size: u8,
+    pub code: Vec<Instruction>,
+    pub captures: Vec<Capture>,
+    // indexed by the operand that refers to them, which counts from the end of the dumped list
+    pub constants: Vec<Value<'a>>,
+    pub numbers: Vec<Number>,
+    pub closures: Vec<Function<'a>>,
+    // the line of each instruction, empty without debug info
+    pub lines: Vec<u32>,
+    pub locals: Vec<Local<'a>>,
+    pub upvalues: Vec<&'a [u8]>,
+}
+
+impl<'a> Function<'a> {
+    pub fn parse(
+        input: &'a [u8],
+        header: &Header,
+        stack: &mut Vec<Self>,
+    ) -> IResult<&'a [u8], Self> {
+        let (input, flags) = le_u8(input)?;
+        let (input, number_of_parameters) = le_u8(input)?;
+        let (input, frame_size) = le_u8(input)?;
+        let (input, captures_length) = le_u8(input)?;
+        let (input, constants_length) = parse_uleb128(input)?;
+        let (input, numbers_length) = parse_uleb128(input)?;
+        let (input, code_length) = parse_uleb128(input)?;
+        let (input, (debug_length, line_defined, number_of_lines)) = if header.is_stripped {
+            (input, (0, 0, 0))
+        } else {
+            let (input, debug_length) = parse_uleb128(input)?;
+            if debug_length == 0 {
+                (input, (0, 0, 0))
+            } else {
+                let (input, line_defined) = parse_uleb128(input)?;
+                let (input, number_of_lines) = parse_uleb128(input)?;
+                (input, (debug_length, line_defined, number_of_lines))
+            }
+        };
+        let (input, code) = count(|i| Instruction::parse(i, header), code_length as usize)(input)?;
+        let (input, captures) =
+            count(|i| Capture::parse(i, header), captures_length as usize)(input)?;
+
+        // a child is the last prototype on the stack that hasn't been taken yet
+        let mut closures = Vec::new();
+        let mut constants = Vec::new();
+        let mut input = input;
+        for _ in 0..constants_length {
+            let (rest, constant) = Value::parse(input)?;
+            if let Value::Function(_) = constant {
+                match stack.pop() {
+                    Some(closure) => closures.push(closure),
+                    None => return fail(input, ErrorKind::Verify),
+                }
+            }
+            constants.push(constant);
+            input = rest;
+        }
+        constants.reverse();
+        closures.reverse();
+        let mut closure_index = 0;
+        for constant in &mut constants {
+            if let Value::Function(index) = constant {
+                *index = closure_index;
+                closure_index += 1;
+            }
+        }
+
+        let (input, numbers) = count(Number::parse, numbers_length as usize)(input)?;
+        let (input, debug) = take(debug_length)(input)?;
+        let (lines, locals, upvalues) = if debug_length == 0 {
+            Default::default()
+        } else {
+            let (_, debug) = Self::parse_debug(
+                debug,
+                header,
+                code.len(),
+                captures.len(),
+                line_defined,
+                number_of_lines,
+            )?;
+            debug
+        };
+
+        Ok((
+            input,
+            Self {
+                line_defined,
+                number_of_lines,
+                is_variadic: flags & FLAG_VARARG != 0,
+                number_of_parameters,
+                frame_size,
+                code,
+                captures,
+                constants,
+                numbers,
+                closures,
+                lines,
+                locals,
+                upvalues,
+            },
+        ))
+    }
+
+    // the lines are stored relative to the line the function was defined on, in as few bytes as
+    // the function's number of lines allows
+    #[allow(clippy::type_complexity)]
+    fn parse_debug(
+        input: &'a [u8],
+        header: &Header,
+        code_length: usize,
+        upvalues_length: usize,
+        line_defined: u32,
+        number_of_lines: u32,
+    ) -> IResult<&'a [u8], (Vec<u32>, Vec<Local<'a>>, Vec<&'a [u8]>)> {
+        let (input, lines) = count(
+            |i| match number_of_lines {
+                0..=0xFF => le_u8(i).map(|(i, line)| (i, line as u32)),
+                0x100..=0xFFFF => header.parse_u16(i).map(|(i, line)| (i, line as u32)),
+                _ => header.parse_u32(i),
+            },
+            code_length,
+        )(input)?;
+        let lines = lines
+            .into_iter()
+            .map(|line| line_defined.wrapping_add(line))
+            .collect();
+        let (input, upvalues) = count(local::parse_name, upvalues_length)(input)?;
+        let (input, locals) = Local::parse_list(input)?;
+
+        Ok((input, (lines, locals, upvalues)))
+    }
+}
@@ -0,0 +1,49 @@
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Register(pub u8);
+
+impl From<u8> for Register {
+    fn from(value: u8) -> Self {
+        Self(value)
+    }
+}
+
+// a garbage collected constant, see `Value`
+#[derive(Debug, Copy, Clone)]
+pub struct Constant(pub u16);
+
+// a number constant, see `Number`
+#[derive(Debug, Copy, Clone)]
+pub struct NumberConstant(pub u16);
+
+#[derive(Debug, Copy, Clone)]
+pub struct Upvalue(pub u8);
+
+// nil, false and true are encoded in the instruction
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Primitive {
+    Nil,
+    False,
+    True,
+}
+
+impl Primitive {
+    pub(crate) fn decode(value: u16) -> Option<Self> {
+        match value {
+            0 => Some(Self::Nil),
+            1 => Some(Self::False),
+            2 => Some(Self::True),
+            _ => None,
+        }
+    }
+}
+
+// the opcode of an instruction tells which kind of operand it takes
+#[derive(Debug, Copy, Clone)]
+pub enum Operand {
+    Register(Register),
+    Constant(Constant),
+    Number(NumberConstant),
+    // the byte key of TGETB and TSETB
+    Integer(u8),
+    Primitive(Primitive),
+}
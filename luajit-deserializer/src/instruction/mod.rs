@@ -0,0 +1,489 @@
+use nom::{
+    error::{Error, ErrorKind, ParseError},
+    Err, IResult,
+};
+
+use argument::{Constant, NumberConstant, Operand, Primitive, Register, Upvalue};
+use operation_code::OperationCode;
+
+use crate::chunk::Header;
+
+pub mod argument;
+mod operation_code;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperation {
+    Minus,
+    Not,
+    Length,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    LessThan,
+    LessThanOrEqual,
+}
+
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    Move {
+        destination: Register,
+        source: Register,
+    },
+    // KSTR and KCDATA
+    LoadConstant {
+        destination: Register,
+        source: Constant,
+    },
+    LoadNumber {
+        destination: Register,
+        source: NumberConstant,
+    },
+    LoadInteger {
+        destination: Register,
+        value: i16,
+    },
+    LoadPrimitive {
+        destination: Register,
+        value: Primitive,
+    },
+    LoadNil(Vec<Register>),
+    GetUpvalue {
+        destination: Register,
+        upvalue: Upvalue,
+    },
+    SetUpvalue {
+        destination: Upvalue,
+        source: Operand,
+    },
+    GetGlobal {
+        destination: Register,
+        name: Constant,
+    },
+    SetGlobal {
+        name: Constant,
+        source: Register,
+    },
+    GetIndex {
+        destination: Register,
+        object: Register,
+        key: Operand,
+    },
+    SetIndex {
+        object: Register,
+        key: Operand,
+        value: Register,
+    },
+    NewTable {
+        destination: Register,
+    },
+    DuplicateTable {
+        destination: Register,
+        template: Constant,
+    },
+    Binary {
+        destination: Register,
+        lhs: Operand,
+        rhs: Operand,
+        operation: BinaryOperation,
+    },
+    Unary {
+        destination: Register,
+        operand: Register,
+        operation: UnaryOperation,
+    },
+    Concatenate {
+        destination: Register,
+        operands: Vec<Register>,
+    },
+    // JMP, and UCLO which closes the upvalues from a register on before jumping
+    Jump {
+        skip: i32,
+        close: Option<Register>,
+    },
+    // the comparisons and tests are followed by a jump, which is taken if they hold
+    Compare {
+        lhs: Register,
+        rhs: Operand,
+        operation: Comparison,
+        invert: bool,
+    },
+    Test {
+        value: Register,
+        invert: bool,
+    },
+    TestSet {
+        destination: Register,
+        value: Register,
+        invert: bool,
+    },
+    // ISTYPE and ISNUM, assertions on the type of a register that the parser doesn't emit
+    CheckType {
+        value: Register,
+    },
+    // the arguments start after the function, or after the frame slot with the two-slot frame
+    // layout. `arguments` is one more than their number, and 0 if the last is multiple values,
+    // as is `return_values`
+    Call {
+        function: Register,
+        arguments: u8,
+        return_values: u8,
+    },
+    TailCall {
+        function: Register,
+        arguments: u16,
+    },
+    Return(Register, u16),
+    // the skips of numeric for loops are normalized to 5.1's, so FORI skips to the FORL and the
+    // FORL skips to the start of the body
+    IterateNumericForLoop {
+        // internal_counter, limit, step, external_counter
+        control: Vec<Register>,
+        skip: i32,
+    },
+    InitNumericForLoop {
+        // internal_counter, limit, step, external_counter
+        control: Vec<Register>,
+        skip: i32,
+    },
+    // ISNEXT, which skips to the ITERN after checking that the generator is `next`
+    InitGenericForLoop {
+        skip: i32,
+    },
+    // ITERC and ITERN
+    CallGenericForLoop {
+        generator: Register,
+        state: Register,
+        internal_control: Register,
+        // variables returned by generator call, starting with the external control
+        vars: Vec<Register>,
+    },
+    // skips to the start of the body if the external control isn't nil, after assigning it
+    // to the internal control
+    IterateGenericForLoop {
+        internal_control: Register,
+        external_control: Register,
+        skip: i32,
+    },
+    // marks the start of a loop for the JIT compiler
+    Loop,
+    // TSETM, which stores the values from the register after the table up to the top of the stack
+    // from the index held in the low bits of the number constant
+    SetList {
+        table: Register,
+        first_index: NumberConstant,
+    },
+    Closure {
+        destination: Register,
+        function: Constant,
+    },
+    VarArg(Register, u8),
+}
+
+impl Instruction {
+    pub fn parse<'a>(input: &'a [u8], header: &Header) -> IResult<&'a [u8], Self> {
+        let (rest, instruction) = header.parse_u32(input)?;
+        let instruction = OperationCode::decode(instruction, header.version)
+            .and_then(|operation_code| Self::decode(operation_code, instruction));
+        match instruction {
+            Some(instruction) => Ok((rest, instruction)),
+            None => Err(Err::Failure(Error::from_error_kind(
+                input,
+                ErrorKind::Switch,
+            ))),
+        }
+    }
+
+    fn decode(operation_code: OperationCode, instruction: u32) -> Option<Self> {
+        let a = (instruction >> 8) as u8;
+        let b = (instruction >> 24) as u8;
+        let c = (instruction >> 16) as u8;
+        let d = (instruction >> 16) as u16;
+        // jumps are biased to be unsigned
+        let skip = d as i32 - 0x8000;
+        let register = Register;
+        let compare = |rhs, operation, invert| Self::Compare {
+            lhs: register(a),
+            rhs,
+            operation,
+            invert,
+        };
+        let binary = |lhs, rhs, operation| Self::Binary {
+            destination: register(a),
+            lhs,
+            rhs,
+            operation,
+        };
+        let variable_number = |operation| {
+            binary(
+                Operand::Register(register(b)),
+                Operand::Number(NumberConstant(c as u16)),
+                operation,
+            )
+        };
+        let number_variable = |operation| {
+            binary(
+                Operand::Number(NumberConstant(c as u16)),
+                Operand::Register(register(b)),
+                operation,
+            )
+        };
+        let variable_variable = |operation| {
+            binary(
+                Operand::Register(register(b)),
+                Operand::Register(register(c)),
+                operation,
+            )
+        };
+        let unary = |operation| Self::Unary {
+            destination: register(a),
+            operand: register(d as u8),
+            operation,
+        };
+        let get_index = |key| Self::GetIndex {
+            destination: register(a),
+            object: register(b),
+            key,
+        };
+        let set_index = |key| Self::SetIndex {
+            object: register(b),
+            key,
+            value: register(a),
+        };
+        let set_upvalue = |source| Self::SetUpvalue {
+            destination: Upvalue(a),
+            source,
+        };
+        let control = || (0..4).map(|i| register(a.wrapping_add(i))).collect();
+
+        Some(match operation_code {
+            OperationCode::LessThan => compare(
+                Operand::Register(register(d as u8)),
+                Comparison::LessThan,
+                false,
+            ),
+            OperationCode::NotLessThan => compare(
+                Operand::Register(register(d as u8)),
+                Comparison::LessThan,
+                true,
+            ),
+            OperationCode::LessThanOrEqual => compare(
+                Operand::Register(register(d as u8)),
+                Comparison::LessThanOrEqual,
+                false,
+            ),
+            OperationCode::NotLessThanOrEqual => compare(
+                Operand::Register(register(d as u8)),
+                Comparison::LessThanOrEqual,
+                true,
+            ),
+            OperationCode::Equal => compare(
+                Operand::Register(register(d as u8)),
+                Comparison::Equal,
+                false,
+            ),
+            OperationCode::NotEqual => compare(
+                Operand::Register(register(d as u8)),
+                Comparison::Equal,
+                true,
+            ),
+            OperationCode::EqualString => {
+                compare(Operand::Constant(Constant(d)), Comparison::Equal, false)
+            }
+            OperationCode::NotEqualString => {
+                compare(Operand::Constant(Constant(d)), Comparison::Equal, true)
+            }
+            OperationCode::EqualNumber => {
+                compare(Operand::Number(NumberConstant(d)), Comparison::Equal, false)
+            }
+            OperationCode::NotEqualNumber => {
+                compare(Operand::Number(NumberConstant(d)), Comparison::Equal, true)
+            }
+            OperationCode::EqualPrimitive => compare(
+                Operand::Primitive(Primitive::decode(d)?),
+                Comparison::Equal,
+                false,
+            ),
+            OperationCode::NotEqualPrimitive => compare(
+                Operand::Primitive(Primitive::decode(d)?),
+                Comparison::Equal,
+                true,
+            ),
+            OperationCode::TestSetTrue => Self::TestSet {
+                destination: register(a),
+                value: register(d as u8),
+                invert: false,
+            },
+            OperationCode::TestSetFalse => Self::TestSet {
+                destination: register(a),
+                value: register(d as u8),
+                invert: true,
+            },
+            OperationCode::TestTrue => Self::Test {
+                value: register(d as u8),
+                invert: false,
+            },
+            OperationCode::TestFalse => Self::Test {
+                value: register(d as u8),
+                invert: true,
+            },
+            OperationCode::CheckType | OperationCode::CheckNumber => {
+                Self::CheckType { value: register(a) }
+            }
+            OperationCode::Move => Self::Move {
+                destination: register(a),
+                source: register(d as u8),
+            },
+            OperationCode::Not => unary(UnaryOperation::Not),
+            OperationCode::Minus => unary(UnaryOperation::Minus),
+            OperationCode::Length => unary(UnaryOperation::Length),
+            OperationCode::AddVN => variable_number(BinaryOperation::Add),
+            OperationCode::SubtractVN => variable_number(BinaryOperation::Sub),
+            OperationCode::MultiplyVN => variable_number(BinaryOperation::Mul),
+            OperationCode::DivideVN => variable_number(BinaryOperation::Div),
+            OperationCode::ModuloVN => variable_number(BinaryOperation::Mod),
+            OperationCode::AddNV => number_variable(BinaryOperation::Add),
+            OperationCode::SubtractNV => number_variable(BinaryOperation::Sub),
+            OperationCode::MultiplyNV => number_variable(BinaryOperation::Mul),
+            OperationCode::DivideNV => number_variable(BinaryOperation::Div),
+            OperationCode::ModuloNV => number_variable(BinaryOperation::Mod),
+            OperationCode::AddVV => variable_variable(BinaryOperation::Add),
+            OperationCode::SubtractVV => variable_variable(BinaryOperation::Sub),
+            OperationCode::MultiplyVV => variable_variable(BinaryOperation::Mul),
+            OperationCode::DivideVV => variable_variable(BinaryOperation::Div),
+            OperationCode::ModuloVV => variable_variable(BinaryOperation::Mod),
+            OperationCode::Power => variable_variable(BinaryOperation::Pow),
+            OperationCode::Concatenate => Self::Concatenate {
+                destination: register(a),
+                operands: (b..=c).map(register).collect(),
+            },
+            OperationCode::LoadString | OperationCode::LoadCData => Self::LoadConstant {
+                destination: register(a),
+                source: Constant(d),
+            },
+            OperationCode::LoadShort => Self::LoadInteger {
+                destination: register(a),
+                value: d as i16,
+            },
+            OperationCode::LoadNumber => Self::LoadNumber {
+                destination: register(a),
+                source: NumberConstant(d),
+            },
+            OperationCode::LoadPrimitive => Self::LoadPrimitive {
+                destination: register(a),
+                value: Primitive::decode(d)?,
+            },
+            OperationCode::LoadNil => Self::LoadNil((a..=d as u8).map(register).collect()),
+            OperationCode::GetUpvalue => Self::GetUpvalue {
+                destination: register(a),
+                upvalue: Upvalue(d as u8),
+            },
+            OperationCode::SetUpvalue => set_upvalue(Operand::Register(register(d as u8))),
+            OperationCode::SetUpvalueString => set_upvalue(Operand::Constant(Constant(d))),
+            OperationCode::SetUpvalueNumber => set_upvalue(Operand::Number(NumberConstant(d))),
+            OperationCode::SetUpvaluePrimitive => {
+                set_upvalue(Operand::Primitive(Primitive::decode(d)?))
+            }
+            OperationCode::CloseUpvalues => Self::Jump {
+                skip,
+                close: Some(register(a)),
+            },
+            OperationCode::Jump => Self::Jump { skip, close: None },
+            OperationCode::Closure => Self::Closure {
+                destination: register(a),
+                function: Constant(d),
+            },
+            OperationCode::NewTable => Self::NewTable {
+                destination: register(a),
+            },
+            OperationCode::DuplicateTable => Self::DuplicateTable {
+                destination: register(a),
+                template: Constant(d),
+            },
+            OperationCode::GetGlobal => Self::GetGlobal {
+                destination: register(a),
+                name: Constant(d),
+            },
+            OperationCode::SetGlobal => Self::SetGlobal {
+                name: Constant(d),
+                source: register(a),
+            },
+            OperationCode::GetIndex | OperationCode::GetIndexRaw => {
+                get_index(Operand::Register(register(c)))
+            }
+            OperationCode::GetIndexString => get_index(Operand::Constant(Constant(c as u16))),
+            OperationCode::GetIndexByte => get_index(Operand::Integer(c)),
+            OperationCode::SetIndex | OperationCode::SetIndexRaw => {
+                set_index(Operand::Register(register(c)))
+            }
+            OperationCode::SetIndexString => set_index(Operand::Constant(Constant(c as u16))),
+            OperationCode::SetIndexByte => set_index(Operand::Integer(c)),
+            OperationCode::SetIndexMultiple => Self::SetList {
+                table: register(a.wrapping_sub(1)),
+                first_index: NumberConstant(d),
+            },
+            // the C of CALLM is the number of arguments before the multiple values
+            OperationCode::CallMultiple => Self::Call {
+                function: register(a),
+                arguments: 0,
+                return_values: b,
+            },
+            OperationCode::Call => Self::Call {
+                function: register(a),
+                arguments: c,
+                return_values: b,
+            },
+            OperationCode::TailCallMultiple => Self::TailCall {
+                function: register(a),
+                arguments: 0,
+            },
+            OperationCode::TailCall => Self::TailCall {
+                function: register(a),
+                arguments: d,
+            },
+            // the generator, state and control are copied above the base and called there, with
+            // the variables being returned to the base
+            OperationCode::CallIterator | OperationCode::CallNext => Self::CallGenericForLoop {
+                generator: register(a.wrapping_sub(3)),
+                state: register(a.wrapping_sub(2)),
+                internal_control: register(a.wrapping_sub(1)),
+                vars: (0..b.saturating_sub(1))
+                    .map(|i| register(a.wrapping_add(i)))
+                    .collect(),
+            },
+            OperationCode::VarArg => Self::VarArg(register(a), b),
+            OperationCode::IsNext => Self::InitGenericForLoop { skip },
+            OperationCode::ReturnMultiple => Self::Return(register(a), 0),
+            OperationCode::Return | OperationCode::Return0 | OperationCode::Return1 => {
+                Self::Return(register(a), d)
+            }
+            // FORI skips past the FORL when the loop doesn't run
+            OperationCode::InitNumericForLoop => Self::InitNumericForLoop {
+                control: control(),
+                skip: skip - 1,
+            },
+            OperationCode::IterateNumericForLoop => Self::IterateNumericForLoop {
+                control: control(),
+                skip,
+            },
+            OperationCode::IterateGenericForLoop => Self::IterateGenericForLoop {
+                internal_control: register(a.wrapping_sub(1)),
+                external_control: register(a),
+                skip,
+            },
+            OperationCode::Loop => Self::Loop,
+            OperationCode::Trace => return None,
+        })
+    }
+}
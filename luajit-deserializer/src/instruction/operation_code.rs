@@ -0,0 +1,289 @@
+use crate::chunk::Version;
+
+// the operation codes of both versions, 2.1 inserted ISTYPE, ISNUM, TGETR and TSETR which moved
+// the ones after them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationCode {
+    // ISLT, ISGE, ISLE and ISGT, the second and fourth are the negation of the others so they
+    // hold for NaN
+    LessThan,
+    NotLessThan,
+    LessThanOrEqual,
+    NotLessThanOrEqual,
+    Equal,
+    NotEqual,
+    EqualString,
+    NotEqualString,
+    EqualNumber,
+    NotEqualNumber,
+    EqualPrimitive,
+    NotEqualPrimitive,
+    TestSetTrue,
+    TestSetFalse,
+    TestTrue,
+    TestFalse,
+    CheckType,
+    CheckNumber,
+    Move,
+    Not,
+    Minus,
+    Length,
+    // the suffixes tell whether the operands are a variable or a number constant
+    AddVN,
+    SubtractVN,
+    MultiplyVN,
+    DivideVN,
+    ModuloVN,
+    AddNV,
+    SubtractNV,
+    MultiplyNV,
+    DivideNV,
+    ModuloNV,
+    AddVV,
+    SubtractVV,
+    MultiplyVV,
+    DivideVV,
+    ModuloVV,
+    Power,
+    Concatenate,
+    LoadString,
+    LoadCData,
+    LoadShort,
+    LoadNumber,
+    LoadPrimitive,
+    LoadNil,
+    GetUpvalue,
+    SetUpvalue,
+    SetUpvalueString,
+    SetUpvalueNumber,
+    SetUpvaluePrimitive,
+    CloseUpvalues,
+    Closure,
+    NewTable,
+    DuplicateTable,
+    GetGlobal,
+    SetGlobal,
+    GetIndex,
+    GetIndexString,
+    GetIndexByte,
+    GetIndexRaw,
+    SetIndex,
+    SetIndexString,
+    SetIndexByte,
+    SetIndexMultiple,
+    SetIndexRaw,
+    CallMultiple,
+    Call,
+    TailCallMultiple,
+    TailCall,
+    CallIterator,
+    CallNext,
+    VarArg,
+    IsNext,
+    ReturnMultiple,
+    Return,
+    Return0,
+    Return1,
+    InitNumericForLoop,
+    IterateNumericForLoop,
+    IterateGenericForLoop,
+    Loop,
+    Jump,
+    // JFORL, JITERL and JLOOP enter a trace of the JIT compiler, the dumper writes the
+    // instruction they replaced instead
+    Trace,
+}
+
+use OperationCode::*;
+
+// the interpreter patches hot loops with I and J prefixed copies of FORI, FORL, ITERL and LOOP,
+// which decode to the originals. the function headers that follow JMP are never dumped
+const LUAJIT20: [OperationCode; 85] = [
+    LessThan,
+    NotLessThan,
+    LessThanOrEqual,
+    NotLessThanOrEqual,
+    Equal,
+    NotEqual,
+    EqualString,
+    NotEqualString,
+    EqualNumber,
+    NotEqualNumber,
+    EqualPrimitive,
+    NotEqualPrimitive,
+    TestSetTrue,
+    TestSetFalse,
+    TestTrue,
+    TestFalse,
+    Move,
+    Not,
+    Minus,
+    Length,
+    AddVN,
+    SubtractVN,
+    MultiplyVN,
+    DivideVN,
+    ModuloVN,
+    AddNV,
+    SubtractNV,
+    MultiplyNV,
+    DivideNV,
+    ModuloNV,
+    AddVV,
+    SubtractVV,
+    MultiplyVV,
+    DivideVV,
+    ModuloVV,
+    Power,
+    Concatenate,
+    LoadString,
+    LoadCData,
+    LoadShort,
+    LoadNumber,
+    LoadPrimitive,
+    LoadNil,
+    GetUpvalue,
+    SetUpvalue,
+    SetUpvalueString,
+    SetUpvalueNumber,
+    SetUpvaluePrimitive,
+    CloseUpvalues,
+    Closure,
+    NewTable,
+    DuplicateTable,
+    GetGlobal,
+    SetGlobal,
+    GetIndex,
+    GetIndexString,
+    GetIndexByte,
+    SetIndex,
+    SetIndexString,
+    SetIndexByte,
+    SetIndexMultiple,
+    CallMultiple,
+    Call,
+    TailCallMultiple,
+    TailCall,
+    CallIterator,
+    CallNext,
+    VarArg,
+    IsNext,
+    ReturnMultiple,
+    Return,
+    Return0,
+    Return1,
+    InitNumericForLoop,
+    InitNumericForLoop,
+    IterateNumericForLoop,
+    IterateNumericForLoop,
+    Trace,
+    IterateGenericForLoop,
+    IterateGenericForLoop,
+    Trace,
+    Loop,
+    Loop,
+    Trace,
+    Jump,
+];
+
+const LUAJIT21: [OperationCode; 89] = [
+    LessThan,
+    NotLessThan,
+    LessThanOrEqual,
+    NotLessThanOrEqual,
+    Equal,
+    NotEqual,
+    EqualString,
+    NotEqualString,
+    EqualNumber,
+    NotEqualNumber,
+    EqualPrimitive,
+    NotEqualPrimitive,
+    TestSetTrue,
+    TestSetFalse,
+    TestTrue,
+    TestFalse,
+    CheckType,
+    CheckNumber,
+    Move,
+    Not,
+    Minus,
+    Length,
+    AddVN,
+    SubtractVN,
+    MultiplyVN,
+    DivideVN,
+    ModuloVN,
+    AddNV,
+    SubtractNV,
+    MultiplyNV,
+    DivideNV,
+    ModuloNV,
+    AddVV,
+    SubtractVV,
+    MultiplyVV,
+    DivideVV,
+    ModuloVV,
+    Power,
+    Concatenate,
+    LoadString,
+    LoadCData,
+    LoadShort,
+    LoadNumber,
+    LoadPrimitive,
+    LoadNil,
+    GetUpvalue,
+    SetUpvalue,
+    SetUpvalueString,
+    SetUpvalueNumber,
+    SetUpvaluePrimitive,
+    CloseUpvalues,
+    Closure,
+    NewTable,
+    DuplicateTable,
+    GetGlobal,
+    SetGlobal,
+    GetIndex,
+    GetIndexString,
+    GetIndexByte,
+    GetIndexRaw,
+    SetIndex,
+    SetIndexString,
+    SetIndexByte,
+    SetIndexMultiple,
+    SetIndexRaw,
+    CallMultiple,
+    Call,
+    TailCallMultiple,
+    TailCall,
+    CallIterator,
+    CallNext,
+    VarArg,
+    IsNext,
+    ReturnMultiple,
+    Return,
+    Return0,
+    Return1,
+    InitNumericForLoop,
+    InitNumericForLoop,
+    IterateNumericForLoop,
+    IterateNumericForLoop,
+    Trace,
+    IterateGenericForLoop,
+    IterateGenericForLoop,
+    Trace,
+    Loop,
+    Loop,
+    Trace,
+    Jump,
+];
+
+impl OperationCode {
+    pub fn decode(instruction: u32, version: Version) -> Option<Self> {
+        let table: &[Self] = match version {
+            Version::LuaJit20 => &LUAJIT20,
+            Version::LuaJit21 => &LUAJIT21,
+        };
+        table.get((instruction & 0xFF) as usize).copied()
+    }
+}
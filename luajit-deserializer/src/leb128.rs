@@ -0,0 +1,38 @@
+use nom::{error::ErrorKind, number::complete::le_u8, IResult};
+
+use crate::chunk::header::fail;
+
+// least significant group of 7 bits first, every byte but the last has its high bit set
+pub(crate) fn parse_uleb128(mut input: &[u8]) -> IResult<&[u8], u32> {
+    let start = input;
+    let mut value = 0u32;
+    let mut shift = 0;
+    loop {
+        if shift >= 32 {
+            return fail(start, ErrorKind::TooLarge);
+        }
+        let (rest, byte) = le_u8(input)?;
+        input = rest;
+        value |= ((byte & 0x7F) as u32) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok((input, value));
+        }
+    }
+}
+
+// number constants are 33 bits wide, the lowest bit tells whether the rest is an integer or the
+// low half of a double
+pub(crate) fn parse_uleb128_33(input: &[u8]) -> IResult<&[u8], (u32, bool)> {
+    let (rest, first) = le_u8(input)?;
+    let is_double = first & 1 != 0;
+    let value = (first >> 1) as u32;
+    if first & 0x80 == 0 {
+        return Ok((rest, (value, is_double)));
+    }
+    let (rest, high) = parse_uleb128(rest)?;
+    if high >= 1 << 26 {
+        return fail(input, ErrorKind::TooLarge);
+    }
+    Ok((rest, (value & 0x3F | high << 6, is_double)))
+}
@@ -0,0 +1,10 @@
+pub use function::{Capture, Function};
+pub use instruction::{argument, Instruction};
+pub use value::{Number, Value};
+
+pub mod chunk;
+pub mod function;
+pub mod instruction;
+mod leb128;
+pub mod local;
+pub mod value;
@@ -0,0 +1,58 @@
+use std::ops::Range;
+
+use nom::{
+    bytes::complete::{tag, take_until},
+    number::complete::le_u8,
+    sequence::terminated,
+    IResult,
+};
+
+use crate::leb128::parse_uleb128;
+
+#[derive(Debug)]
+pub struct Local<'a> {
+    pub name: &'a [u8],
+    // the pcs the local is in scope for
+    pub range: Range<u32>,
+}
+
+// the hidden variables of for loops have their name stored as a byte between 1 and 6, a 0 byte
+// ends the list
+const INTERNAL_NAMES: [&[u8]; 6] = [
+    b"(for index)",
+    b"(for limit)",
+    b"(for step)",
+    b"(for generator)",
+    b"(for state)",
+    b"(for control)",
+];
+
+pub(crate) fn parse_name(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    terminated(take_until("\0"), tag("\0"))(input)
+}
+
+impl<'a> Local<'a> {
+    pub(crate) fn parse_list(mut input: &'a [u8]) -> IResult<&'a [u8], Vec<Self>> {
+        let mut locals = Vec::new();
+        // each local starts relative to the one before it
+        let mut start = 0u32;
+        loop {
+            let (rest, name) = match le_u8(input)? {
+                (rest, 0) => return Ok((rest, locals)),
+                (rest, code) if code as usize <= INTERNAL_NAMES.len() => {
+                    (rest, INTERNAL_NAMES[code as usize - 1])
+                }
+                _ => parse_name(input)?,
+            };
+            let (rest, start_offset) = parse_uleb128(rest)?;
+            let (rest, length) = parse_uleb128(rest)?;
+            start = start.wrapping_add(start_offset);
+            // the pcs count the function header, which isn't dumped
+            locals.push(Self {
+                name,
+                range: start.saturating_sub(1)..start.wrapping_add(length).saturating_sub(1),
+            });
+            input = rest;
+        }
+    }
+}
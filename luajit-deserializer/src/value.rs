@@ -0,0 +1,170 @@
+use enum_as_inner::EnumAsInner;
+use nom::{bytes::complete::take, multi::count, IResult};
+
+use crate::leb128::{parse_uleb128, parse_uleb128_33};
+
+// the garbage collected constants, which instructions index from the end of the list
+#[derive(Debug, EnumAsInner)]
+pub enum Value<'a> {
+    // an index into the closures of the function
+    Function(usize),
+    // the template of a table constructor whose keys and values are all constants
+    Table(Table<'a>),
+    Int64(i64),
+    UInt64(u64),
+    // the real and imaginary part
+    Complex(f64, f64),
+    String(&'a [u8]),
+}
+
+// the kinds of garbage collected constants, a string's kind also holds its length
+const KGC_CHILD: u32 = 0;
+const KGC_TAB: u32 = 1;
+const KGC_I64: u32 = 2;
+const KGC_U64: u32 = 3;
+const KGC_COMPLEX: u32 = 4;
+const KGC_STR: u32 = 5;
+
+fn parse_u64(input: &[u8]) -> IResult<&[u8], u64> {
+    let (input, low) = parse_uleb128(input)?;
+    let (input, high) = parse_uleb128(input)?;
+    Ok((input, (high as u64) << 32 | low as u64))
+}
+
+fn parse_f64(input: &[u8]) -> IResult<&[u8], f64> {
+    let (input, bits) = parse_u64(input)?;
+    Ok((input, f64::from_bits(bits)))
+}
+
+impl<'a> Value<'a> {
+    // a child prototype is returned as `Function(0)`, the caller knows which one it is
+    pub(crate) fn parse(input: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (input, kind) = parse_uleb128(input)?;
+        match kind {
+            KGC_CHILD => Ok((input, Self::Function(0))),
+            KGC_TAB => {
+                let (input, table) = Table::parse(input)?;
+                Ok((input, Self::Table(table)))
+            }
+            KGC_I64 => {
+                let (input, value) = parse_u64(input)?;
+                Ok((input, Self::Int64(value as i64)))
+            }
+            KGC_U64 => {
+                let (input, value) = parse_u64(input)?;
+                Ok((input, Self::UInt64(value)))
+            }
+            KGC_COMPLEX => {
+                let (input, real) = parse_f64(input)?;
+                let (input, imaginary) = parse_f64(input)?;
+                Ok((input, Self::Complex(real, imaginary)))
+            }
+            _ => {
+                let (input, string) = take(kind - KGC_STR)(input)?;
+                Ok((input, Self::String(string)))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Table<'a> {
+    // starts at index 0, which is nil unless the table was written with `[0] = ...`
+    pub array: Vec<TableValue<'a>>,
+    pub hash: Vec<(TableValue<'a>, TableValue<'a>)>,
+}
+
+impl<'a> Table<'a> {
+    fn parse(input: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (input, array_length) = parse_uleb128(input)?;
+        let (input, hash_length) = parse_uleb128(input)?;
+        let (input, array) = count(TableValue::parse, array_length as usize)(input)?;
+        let (input, hash) = count(
+            |i| {
+                let (i, key) = TableValue::parse(i)?;
+                let (i, value) = TableValue::parse(i)?;
+                Ok((i, (key, value)))
+            },
+            hash_length as usize,
+        )(input)?;
+
+        Ok((input, Self { array, hash }))
+    }
+}
+
+// the kinds of the keys and values of a template table
+const KTAB_NIL: u32 = 0;
+const KTAB_FALSE: u32 = 1;
+const KTAB_TRUE: u32 = 2;
+const KTAB_INT: u32 = 3;
+const KTAB_NUM: u32 = 4;
+const KTAB_STR: u32 = 5;
+
+#[derive(Debug, EnumAsInner)]
+pub enum TableValue<'a> {
+    Nil,
+    Boolean(bool),
+    Integer(i32),
+    Number(f64),
+    String(&'a [u8]),
+}
+
+impl<'a> TableValue<'a> {
+    fn parse(input: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (rest, kind) = parse_uleb128(input)?;
+        match kind {
+            KTAB_NIL => Ok((rest, Self::Nil)),
+            KTAB_FALSE => Ok((rest, Self::Boolean(false))),
+            KTAB_TRUE => Ok((rest, Self::Boolean(true))),
+            KTAB_INT => {
+                let (rest, value) = parse_uleb128(rest)?;
+                Ok((rest, Self::Integer(value as i32)))
+            }
+            KTAB_NUM => {
+                let (rest, value) = parse_f64(rest)?;
+                Ok((rest, Self::Number(value)))
+            }
+            _ => {
+                let (rest, string) = take(kind - KTAB_STR)(rest)?;
+                Ok((rest, Self::String(string)))
+            }
+        }
+    }
+}
+
+// numbers that fit in an `int32_t` are stored as one, but are still doubles to lua
+#[derive(Debug, Clone, Copy, EnumAsInner)]
+pub enum Number {
+    Integer(i32),
+    Float(f64),
+}
+
+impl Number {
+    pub(crate) fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, (low, is_double)) = parse_uleb128_33(input)?;
+        if is_double {
+            let (input, high) = parse_uleb128(input)?;
+            Ok((
+                input,
+                Self::Float(f64::from_bits((high as u64) << 32 | low as u64)),
+            ))
+        } else {
+            Ok((input, Self::Integer(low as i32)))
+        }
+    }
+
+    pub fn value(self) -> f64 {
+        match self {
+            Self::Integer(value) => value as f64,
+            Self::Float(value) => value,
+        }
+    }
+
+    // the low 32 bits, TSETM keeps its first index there
+    pub fn low_bits(self) -> u32 {
+        match self {
+            Self::Integer(value) => value as u32,
+            Self::Float(value) => value.to_bits() as u32,
+        }
+    }
+}
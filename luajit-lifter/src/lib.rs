@@ -0,0 +1,162 @@
+mod lifter;
+
+use std::time::Instant;
+
+use lifter::Lifter;
+use luajit_deserializer::chunk::Chunk;
+use nom::error::ErrorKind;
+use thiserror::Error;
+
+pub use decompiler::{
+    DecompileOutput, Fallback, FunctionReport, FunctionStatus, Limits, LineMode, Timings,
+};
+
+#[cfg(feature = "dhat-heap")]
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+#[derive(Debug, Error)]
+pub enum DecompileError {
+    #[error("malformed bytecode at offset {offset}: {}", kind.description())]
+    Malformed { offset: usize, kind: ErrorKind },
+    #[error("unexpected end of bytecode")]
+    UnexpectedEnd,
+}
+
+impl DecompileError {
+    // `err` is from parsing `bytecode`, its input is what was left
+    pub fn new(bytecode: &[u8], err: nom::Err<nom::error::Error<&[u8]>>) -> Self {
+        match err {
+            nom::Err::Error(err) | nom::Err::Failure(err) => Self::Malformed {
+                offset: bytecode.len() - err.input.len(),
+                kind: err.code,
+            },
+            nom::Err::Incomplete(_) => Self::UnexpectedEnd,
+        }
+    }
+}
+
+pub fn decompile_bytecode(
+    bytecode: &[u8],
+    limits: &Limits,
+) -> Result<DecompileOutput, DecompileError> {
+    let now = Instant::now();
+    let (_, chunk) = Chunk::parse(bytecode).map_err(|e| DecompileError::new(bytecode, e))?;
+    let deserialize_time = now.elapsed();
+
+    let mut output = decompiler::decompile::<Lifter>(&chunk, limits);
+    output.timings.deserialize = deserialize_time;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decompile_output(fixture: &str) -> DecompileOutput {
+        let path = format!(
+            "{}/../luajit-deserializer/fixtures/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            fixture
+        );
+        let output = decompile_bytecode(&std::fs::read(path).unwrap(), &Limits::default()).unwrap();
+        for function in &output.functions {
+            assert_eq!(function.status, FunctionStatus::Ok, "{}", output.source);
+        }
+        output
+    }
+
+    fn decompile(fixture: &str) -> String {
+        decompile_output(fixture).source
+    }
+
+    #[test]
+    fn reports_malformed_bytecode() {
+        let path = format!(
+            "{}/../luajit-deserializer/fixtures/sample.ljbc",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut bytecode = std::fs::read(path).unwrap();
+        bytecode[0] = b'#';
+        assert!(matches!(
+            decompile_bytecode(&bytecode, &Limits::default()),
+            Err(DecompileError::Malformed { offset: 0, .. })
+        ));
+        bytecode.truncate(40);
+        assert!(decompile_bytecode(&bytecode, &Limits::default()).is_err());
+    }
+
+    #[test]
+    fn lifts_dumps() {
+        for fixture in ["sample.ljbc", "sample_stripped.ljbc"] {
+            let source = decompile(fixture);
+            for expected in [
+                "function(...)",
+                "9223372036854775807LL",
+                "18446744073709551615ULL",
+                "< \"z\"",
+            ] {
+                assert!(
+                    source.contains(expected),
+                    "{}: missing {:?} in:\n{}",
+                    fixture,
+                    expected,
+                    source
+                );
+            }
+        }
+
+        let source = decompile("sample.ljbc");
+        for expected in [
+            "local greeting = \"hello\"",
+            "for k, v in pairs(t) do",
+            "for k, v in ipairs(t) do",
+            "-- upvalues: (ref) count",
+            "shared = counter()",
+        ] {
+            assert!(
+                source.contains(expected),
+                "missing {:?} in:\n{}",
+                expected,
+                source
+            );
+        }
+    }
+
+    // the condition of a loop is lifted as an `if` and keeps its instructions once restructured
+    #[test]
+    fn maps_loop_conditions() {
+        let (source, source_map) =
+            decompile_output("sample.ljbc").render_with_source_map(LineMode::Compact);
+        let line = source
+            .lines()
+            .position(|line| line == "while shared() < 10 do")
+            .unwrap()
+            + 1;
+        assert!(
+            source_map
+                .mappings
+                .iter()
+                .any(|m| m.start.line == line && m.start.column == 1 && !m.pcs.is_empty()),
+            "{:?}",
+            source_map
+        );
+    }
+
+    #[test]
+    fn lifts_complex_cdata() {
+        let source = decompile("complex.ljbc");
+        for expected in [
+            "local z = 2i",
+            "local w = -1.5i",
+            "print(z * w, -z, 1e999i)",
+        ] {
+            assert!(
+                source.contains(expected),
+                "missing {:?} in:\n{}",
+                expected,
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,949 @@
+use by_address::ByAddress;
+use cfg::block::{BlockEdge, BranchType};
+
+use itertools::Itertools;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+
+use ast::{RcLocal, Statement};
+use cfg::function::Function;
+use decompiler::{
+    debug_name, function_count, local_variables, BlockMap, LiftedFunction, RegisterWrites,
+};
+
+use luajit_deserializer::{
+    argument::{Constant, NumberConstant, Operand, Primitive, Register},
+    chunk::Chunk,
+    instruction::{BinaryOperation, Comparison, UnaryOperation},
+    value::{Table, TableValue},
+    Function as BytecodeFunction, Instruction, Value,
+};
+
+use petgraph::stable_graph::NodeIndex;
+
+use triomphe::Arc;
+
+pub struct Lifter<'a> {
+    bytecode: &'a BytecodeFunction<'a>,
+    // 1 with the two-slot frame layout, where calls keep their frame link after the function
+    frame_slot: u8,
+    blocks: BlockMap,
+    insert_between: FxHashMap<NodeIndex, (NodeIndex, Statement)>,
+    locals: FxHashMap<Register, RcLocal>,
+    function: Function,
+    upvalues: Vec<RcLocal>,
+    // the prototypes are paired with their id, which is their index in a preorder traversal of
+    // the function tree
+    child_functions: FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, Prototype<'a>>,
+    register_writes: RegisterWrites<Register>,
+}
+
+type Prototype<'a> = (usize, &'a BytecodeFunction<'a>);
+
+// the control flow of the bytecode, which `BlockMap` splits into blocks
+struct Code<'a>(&'a BytecodeFunction<'a>);
+
+impl decompiler::Code for Code<'_> {
+    fn successors(&self, pc: usize) -> Vec<(isize, BranchType)> {
+        let next = pc as isize + 1;
+        match self.0.code[pc] {
+            Instruction::Compare { .. }
+            | Instruction::Test { .. }
+            | Instruction::TestSet { .. } => {
+                vec![(next, BranchType::Then), (next + 1, BranchType::Else)]
+            }
+            Instruction::IterateNumericForLoop { skip, .. }
+            | Instruction::IterateGenericForLoop { skip, .. } => vec![
+                (next + skip as isize, BranchType::Then),
+                (next, BranchType::Else),
+            ],
+            Instruction::Jump { skip, .. }
+            | Instruction::InitNumericForLoop { skip, .. }
+            | Instruction::InitGenericForLoop { skip } => {
+                vec![(next + skip as isize, BranchType::Unconditional)]
+            }
+            Instruction::Return(..) | Instruction::TailCall { .. } => Vec::new(),
+            _ => vec![(next, BranchType::Unconditional)],
+        }
+    }
+
+    fn is_valid_pc(&self, pc: isize) -> bool {
+        usize::try_from(pc).is_ok_and(|pc| pc < self.0.code.len())
+    }
+}
+
+impl<'a> Lifter<'a> {
+    fn allocate_locals(&mut self) {
+        self.upvalues.reserve(self.bytecode.captures.len());
+        for i in 0..self.bytecode.captures.len() {
+            let name = self.bytecode.upvalues.get(i).and_then(|&n| debug_name(n));
+            self.upvalues.push(RcLocal::new(ast::Local::new(name)));
+        }
+
+        self.locals.reserve(self.bytecode.frame_size as usize);
+        for i in 0..self.bytecode.frame_size {
+            let local = if i < self.bytecode.number_of_parameters {
+                // the parameters are the first locals
+                let name = self
+                    .bytecode
+                    .locals
+                    .get(i as usize)
+                    .and_then(|l| debug_name(l.name));
+                let local = RcLocal::new(ast::Local::new(name));
+                self.function.parameters.push(local.clone());
+                local
+            } else {
+                RcLocal::default()
+            };
+            self.locals.insert(Register(i), local);
+        }
+        self.function.is_variadic = self.bytecode.is_variadic;
+    }
+
+    // tampered bytecode can refer to a constant that doesn't exist or has the wrong kind for the
+    // instruction, which is read as nil after a comment saying so
+    fn constant(&self, constant: Constant, statements: &mut Vec<Statement>) -> ast::RValue {
+        match self.bytecode.constants.get(constant.0 as usize) {
+            Some(&Value::Int64(v)) => ast::Literal::Int64(v).into(),
+            Some(&Value::UInt64(v)) => ast::Literal::UInt64(v).into(),
+            Some(&Value::Complex(real, imaginary)) => ast::Literal::Complex(real, imaginary).into(),
+            Some(Value::String(v)) => ast::Literal::String(v.to_vec()).into(),
+            Some(Value::Table(table)) => template_table(table).into(),
+            Some(Value::Function(_)) | None => {
+                invalid_constant("constant", constant.0, statements);
+                ast::Literal::Nil.into()
+            }
+        }
+    }
+
+    // luajit only has doubles, whether a number was stored as an integer doesn't matter
+    fn number(&self, number: NumberConstant, statements: &mut Vec<Statement>) -> ast::RValue {
+        match self.bytecode.numbers.get(number.0 as usize) {
+            Some(value) => ast::Literal::Number(value.value()).into(),
+            None => {
+                invalid_constant("number constant", number.0, statements);
+                ast::Literal::Nil.into()
+            }
+        }
+    }
+
+    fn operand(&self, operand: Operand, statements: &mut Vec<Statement>) -> ast::RValue {
+        match operand {
+            Operand::Register(register) => self.locals[&register].clone().into(),
+            Operand::Constant(constant) => self.constant(constant, statements),
+            Operand::Number(number) => self.number(number, statements),
+            Operand::Integer(value) => ast::Literal::Number(value as f64).into(),
+            Operand::Primitive(value) => primitive(value).into(),
+        }
+    }
+
+    fn global(&self, name: Constant, statements: &mut Vec<Statement>) -> Option<ast::Global> {
+        match self.bytecode.constants.get(name.0 as usize) {
+            Some(Value::String(name)) => Some(ast::Global::new(name.to_vec())),
+            _ => {
+                invalid_constant("global name", name.0, statements);
+                None
+            }
+        }
+    }
+
+    // a closure of the prototype `function` refers to, which is queued to be lifted
+    fn closure(&mut self, function: Constant, statements: &mut Vec<Statement>) -> ast::RValue {
+        let closure = match self.bytecode.constants.get(function.0 as usize) {
+            Some(&Value::Function(index)) => self
+                .bytecode
+                .closures
+                .get(index)
+                .map(|closure| (index, closure)),
+            _ => None,
+        };
+        let Some((index, closure)) = closure else {
+            invalid_constant("prototype", function.0, statements);
+            return ast::Literal::Nil.into();
+        };
+
+        let upvalues_passed = closure
+            .captures
+            .iter()
+            .map(|capture| {
+                let upvalue = if capture.in_stack {
+                    self.locals.get(&Register(capture.index))
+                } else {
+                    self.upvalues.get(capture.index as usize)
+                };
+                // a capture out of range is passed a local that's never assigned
+                upvalue.cloned().unwrap_or_else(|| {
+                    statements
+                        .push(ast::Comment::new("invalid upvalue capture".to_string()).into());
+                    RcLocal::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let ast_function = Arc::<Mutex<_>>::default();
+
+        let closure_id = self.function.id
+            + 1
+            + self.bytecode.closures[..index]
+                .iter()
+                .map(|closure| function_count(closure, |f| &f.closures))
+                .sum::<usize>();
+        self.child_functions
+            .insert(ByAddress(ast_function.clone()), (closure_id, closure));
+
+        ast::Closure {
+            function: ByAddress(ast_function),
+            upvalues: upvalues_passed.into_iter().map(ast::Upvalue::Ref).collect(),
+        }
+        .into()
+    }
+
+    // the arguments of a call to the function in `function`, which start after its frame slot
+    fn arguments(
+        &self,
+        function: Register,
+        arguments: u16,
+        top: &mut Option<(ast::RValue, u8)>,
+    ) -> Vec<ast::RValue> {
+        let first = function.0 + 1 + self.frame_slot;
+        if arguments != 0 {
+            (first..first + (arguments - 1) as u8)
+                .map(|r| self.locals[&Register(r)].clone().into())
+                .collect()
+        } else {
+            let top = top.take().unwrap();
+            (first..top.1)
+                .map(|r| self.locals[&Register(r)].clone().into())
+                .chain(std::iter::once(top.0))
+                .collect()
+        }
+    }
+
+    // generic for loops start with a jump to the generator call, or an ISNEXT when the generator
+    // is expected to be `next`, and the loop jumps back to the instruction after either
+    fn generic_for_init(&self, pc: usize, skip: i32) -> Option<ast::GenericForInit> {
+        let call_pc = (pc + 1).checked_add_signed(skip as isize)?;
+        let Some(Instruction::CallGenericForLoop {
+            generator,
+            state,
+            internal_control,
+            ..
+        }) = self.bytecode.code.get(call_pc)
+        else {
+            return None;
+        };
+        let Some(&Instruction::IterateGenericForLoop { skip, .. }) =
+            self.bytecode.code.get(call_pc + 1)
+        else {
+            return None;
+        };
+        if (call_pc + 2).checked_add_signed(skip as isize) != Some(pc + 1) {
+            return None;
+        }
+        Some(ast::GenericForInit::new(
+            self.locals[generator].clone(),
+            self.locals[state].clone(),
+            self.locals[internal_control].clone(),
+        ))
+    }
+
+    fn lift_instruction(&mut self, start: usize, end: usize, statements: &mut Vec<Statement>) {
+        if end > start {
+            statements.reserve(end - start + 1);
+        }
+        let mut top: Option<(ast::RValue, u8)> = None;
+        let mut iter = self.bytecode.code[start..=end].iter();
+        // the first instruction that isn't part of a statement yet
+        let mut first_pc = start;
+        while let Some(instruction) = iter.next() {
+            let pc = end - iter.len();
+            let statement_count = statements.len();
+            match instruction {
+                Instruction::Move {
+                    destination,
+                    source,
+                } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[destination].clone().into()],
+                            vec![self.locals[source].clone().into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::LoadConstant {
+                    destination,
+                    source,
+                } => {
+                    let value = self.constant(source, statements);
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![value],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::LoadNumber {
+                    destination,
+                    source,
+                } => {
+                    let value = self.number(source, statements);
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![value],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::LoadInteger { destination, value } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![ast::Literal::Number(value as f64).into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::LoadPrimitive { destination, value } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![primitive(value).into()],
+                        )
+                        .into(),
+                    );
+                }
+                Instruction::LoadNil(registers) => {
+                    for register in registers {
+                        statements.push(
+                            ast::Assign::new(
+                                vec![self.locals[register].clone().into()],
+                                vec![ast::Literal::Nil.into()],
+                            )
+                            .into(),
+                        );
+                    }
+                }
+                &Instruction::GetUpvalue {
+                    destination,
+                    upvalue,
+                } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![self.upvalues[upvalue.0 as usize].clone().into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::SetUpvalue {
+                    destination,
+                    source,
+                } => {
+                    let value = self.operand(source, statements);
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.upvalues[destination.0 as usize].clone().into()],
+                            vec![value],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::GetGlobal { destination, name } => {
+                    let value = self
+                        .global(name, statements)
+                        .map_or(ast::Literal::Nil.into(), Into::into);
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![value],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::SetGlobal { name, source } => {
+                    if let Some(global) = self.global(name, statements) {
+                        statements.push(
+                            ast::Assign::new(
+                                vec![global.into()],
+                                vec![self.locals[&source].clone().into()],
+                            )
+                            .into(),
+                        );
+                    }
+                }
+                &Instruction::GetIndex {
+                    destination,
+                    object,
+                    key,
+                } => {
+                    let key = self.operand(key, statements);
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![ast::Index::new(self.locals[&object].clone().into(), key).into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::SetIndex { object, key, value } => {
+                    let key = self.operand(key, statements);
+                    statements.push(
+                        ast::Assign::new(
+                            vec![ast::Index {
+                                left: Box::new(self.locals[&object].clone().into()),
+                                right: Box::new(key),
+                            }
+                            .into()],
+                            vec![self.locals[&value].clone().into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::Test { value, invert } => {
+                    let value = self.locals[&value].clone().into();
+                    let condition = if invert {
+                        ast::Unary::new(value, ast::UnaryOperation::Not).into()
+                    } else {
+                        value
+                    };
+                    statements.push(
+                        ast::If::new(condition, ast::Block::default(), ast::Block::default())
+                            .into(),
+                    )
+                }
+                &Instruction::Unary {
+                    destination,
+                    operand,
+                    operation,
+                } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![ast::Unary::new(
+                                self.locals[&operand].clone().into(),
+                                match operation {
+                                    UnaryOperation::Minus => ast::UnaryOperation::Negate,
+                                    UnaryOperation::Not => ast::UnaryOperation::Not,
+                                    UnaryOperation::Length => ast::UnaryOperation::Length,
+                                },
+                            )
+                            .into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::Return(values, count) => {
+                    let values = if count != 0 {
+                        (values.0..values.0 + (count - 1) as u8)
+                            .map(|r| self.locals[&Register(r)].clone().into())
+                            .collect()
+                    } else {
+                        let (tail, end) = top.take().unwrap();
+                        (values.0..end)
+                            .map(|r| self.locals[&Register(r)].clone().into())
+                            .chain(std::iter::once(tail))
+                            .collect()
+                    };
+                    statements.push(ast::Return::new(values).into());
+                }
+                &Instruction::Jump { skip, close } => {
+                    if let Some(start) = close {
+                        let locals = (start.0..self.bytecode.frame_size)
+                            .map(|i| self.locals[&Register(i)].clone())
+                            .collect();
+                        statements.push(ast::Close { locals }.into());
+                    }
+                    if let Some(init) = self.generic_for_init(pc, skip) {
+                        statements.push(init.into());
+                    }
+                }
+                &Instruction::InitGenericForLoop { skip } => {
+                    if let Some(init) = self.generic_for_init(pc, skip) {
+                        statements.push(init.into());
+                    }
+                }
+                Instruction::IterateGenericForLoop { .. }
+                | Instruction::CheckType { .. }
+                | Instruction::Loop => {}
+                &Instruction::Binary {
+                    destination,
+                    lhs,
+                    rhs,
+                    operation,
+                } => {
+                    let lhs = self.operand(lhs, statements);
+                    let rhs = self.operand(rhs, statements);
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![ast::Binary::new(
+                                lhs,
+                                rhs,
+                                match operation {
+                                    BinaryOperation::Add => ast::BinaryOperation::Add,
+                                    BinaryOperation::Sub => ast::BinaryOperation::Sub,
+                                    BinaryOperation::Mul => ast::BinaryOperation::Mul,
+                                    BinaryOperation::Div => ast::BinaryOperation::Div,
+                                    BinaryOperation::Mod => ast::BinaryOperation::Mod,
+                                    BinaryOperation::Pow => ast::BinaryOperation::Pow,
+                                },
+                            )
+                            .into()],
+                        )
+                        .into(),
+                    );
+                }
+                Instruction::Concatenate {
+                    destination,
+                    operands,
+                } => {
+                    assert!(operands.len() >= 2);
+                    let mut operands = operands.iter().rev();
+
+                    let right = operands.next().unwrap();
+                    let left = operands.next().unwrap();
+                    let mut concat = ast::Binary::new(
+                        self.locals[left].clone().into(),
+                        self.locals[right].clone().into(),
+                        ast::BinaryOperation::Concat,
+                    );
+                    for r in operands {
+                        concat = ast::Binary::new(
+                            self.locals[r].clone().into(),
+                            concat.into(),
+                            ast::BinaryOperation::Concat,
+                        );
+                    }
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[destination].clone().into()],
+                            vec![concat.into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::Compare {
+                    lhs,
+                    rhs,
+                    operation,
+                    invert,
+                } => {
+                    let rhs = self.operand(rhs, statements);
+                    let value = ast::Binary::new(
+                        self.locals[&lhs].clone().into(),
+                        rhs,
+                        match operation {
+                            Comparison::Equal => ast::BinaryOperation::Equal,
+                            Comparison::LessThan => ast::BinaryOperation::LessThan,
+                            Comparison::LessThanOrEqual => ast::BinaryOperation::LessThanOrEqual,
+                        },
+                    )
+                    .into();
+                    let condition = if invert {
+                        ast::Unary::new(value, ast::UnaryOperation::Not).into()
+                    } else {
+                        value
+                    };
+                    statements.push(
+                        ast::If::new(condition, ast::Block::default(), ast::Block::default())
+                            .into(),
+                    )
+                }
+                Instruction::TestSet {
+                    destination,
+                    value,
+                    invert,
+                } => {
+                    let value: ast::RValue = self.locals[value].clone().into();
+                    statements.push(
+                        ast::If::new(
+                            if *invert {
+                                ast::Unary {
+                                    value: Box::new(value.clone()),
+                                    operation: ast::UnaryOperation::Not,
+                                }
+                                .into()
+                            } else {
+                                value.clone()
+                            },
+                            ast::Block::default(),
+                            ast::Block::default(),
+                        )
+                        .into(),
+                    );
+
+                    let assign = ast::Assign::new(
+                        vec![self.locals[destination].clone().into()],
+                        vec![value.clone()],
+                    );
+
+                    self.function
+                        .block_mut(self.blocks.target(end as isize + 1))
+                        .unwrap()
+                        .push(assign.into());
+                }
+                // CALLT returns the results itself, there's no return after it
+                &Instruction::TailCall {
+                    function,
+                    arguments,
+                } => {
+                    let arguments = self.arguments(function, arguments, &mut top);
+                    let call = ast::Call::new(self.locals[&function].clone().into(), arguments);
+                    statements.push(ast::Return::new(vec![call.into()]).into());
+                }
+                &Instruction::Call {
+                    function,
+                    arguments,
+                    return_values,
+                } => {
+                    let arguments = self.arguments(function, arguments as u16, &mut top);
+                    let call = ast::Call::new(self.locals[&function].clone().into(), arguments);
+
+                    if return_values != 0 {
+                        if return_values == 1 {
+                            statements.push(call.into());
+                        } else {
+                            statements.push(
+                                ast::Assign::new(
+                                    (function.0..function.0 + return_values - 1)
+                                        .map(|r| self.locals[&Register(r)].clone().into())
+                                        .collect_vec(),
+                                    vec![ast::RValue::Select(call.into())],
+                                )
+                                .into(),
+                            );
+                        }
+                    } else {
+                        top = Some((call.into(), function.0));
+                    }
+                }
+                &Instruction::VarArg(destination, b) => {
+                    let vararg = ast::VarArg {};
+                    if b != 0 {
+                        statements.push(
+                            ast::Assign::new(
+                                (destination.0..destination.0 + b - 1)
+                                    .map(|r| self.locals[&Register(r)].clone().into())
+                                    .collect(),
+                                vec![ast::RValue::Select(vararg.into())],
+                            )
+                            .into(),
+                        );
+                    } else {
+                        top = Some((vararg.into(), destination.0));
+                    }
+                }
+                &Instruction::Closure {
+                    destination,
+                    function,
+                } => {
+                    let value = self.closure(function, statements);
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![value],
+                        )
+                        .into(),
+                    );
+                }
+                Instruction::NewTable { destination } => {
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[destination].clone().into()],
+                            vec![ast::Table::default().into()],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::DuplicateTable {
+                    destination,
+                    template,
+                } => {
+                    let value = match self.bytecode.constants.get(template.0 as usize) {
+                        Some(Value::Table(table)) => template_table(table).into(),
+                        _ => {
+                            invalid_constant("table template", template.0, statements);
+                            ast::Literal::Nil.into()
+                        }
+                    };
+                    statements.push(
+                        ast::Assign::new(
+                            vec![self.locals[&destination].clone().into()],
+                            vec![value],
+                        )
+                        .into(),
+                    );
+                }
+                &Instruction::SetList { table, first_index } => {
+                    let first_index = self
+                        .bytecode
+                        .numbers
+                        .get(first_index.0 as usize)
+                        .unwrap()
+                        .low_bits();
+                    let top = top.take().unwrap();
+                    statements.push(
+                        ast::SetList::new(
+                            self.locals[&table].clone(),
+                            first_index as usize,
+                            (table.0 + 1..top.1)
+                                .map(|r| self.locals[&Register(r)].clone().into())
+                                .collect(),
+                            Some(top.0),
+                        )
+                        .into(),
+                    );
+                }
+                Instruction::InitNumericForLoop { control, .. } => {
+                    let (internal_counter, limit, step) = (
+                        self.locals[&control[0]].clone(),
+                        self.locals[&control[1]].clone(),
+                        self.locals[&control[2]].clone(),
+                    );
+                    statements.push(ast::NumForInit::new(internal_counter, limit, step).into());
+                }
+                &Instruction::IterateNumericForLoop { ref control, skip } => {
+                    let (internal_counter, limit, step, external_counter) = (
+                        self.locals[&control[0]].clone(),
+                        self.locals[&control[1]].clone(),
+                        self.locals[&control[2]].clone(),
+                        self.locals[&control[3]].clone(),
+                    );
+                    statements.push(
+                        ast::NumForNext::new(internal_counter.clone(), limit.into(), step.into())
+                            .into(),
+                    );
+
+                    let body_node = self.blocks.target(end as isize + 1 + skip as isize);
+                    assert!(self
+                        .insert_between
+                        .insert(
+                            self.blocks.node(start),
+                            (
+                                body_node,
+                                ast::Assign::new(
+                                    vec![external_counter.into()],
+                                    vec![internal_counter.into()],
+                                )
+                                .into()
+                            )
+                        )
+                        .is_none());
+                }
+                // the ITERL after it tests the first variable and assigns it to the internal
+                // control, which is what `GenericForNext` does
+                Instruction::CallGenericForLoop {
+                    generator,
+                    state,
+                    vars,
+                    ..
+                } => {
+                    statements.push(
+                        ast::GenericForNext::new(
+                            vars.iter().map(|x| self.locals[x].clone()).collect(),
+                            self.locals[generator].clone().into(),
+                            self.locals[state].clone(),
+                        )
+                        .into(),
+                    );
+                }
+            }
+
+            // the loop variables are written by the generator call, but only in scope at the
+            // start of the body, which the ITERL after it goes to
+            let written_pc = match (instruction, self.bytecode.code.get(pc + 1)) {
+                (
+                    Instruction::CallGenericForLoop { .. },
+                    Some(&Instruction::IterateGenericForLoop { skip, .. }),
+                ) => (pc + 2).checked_add_signed(skip as isize).unwrap_or(pc),
+                _ => pc,
+            };
+            let node = self.blocks.node(start);
+            for (statement_index, statement) in statements.iter().enumerate().skip(statement_count)
+            {
+                self.register_writes.record(
+                    &self.locals,
+                    node,
+                    statement_index,
+                    statement,
+                    written_pc,
+                );
+            }
+
+            if statements.len() > statement_count {
+                let end_pc = end + 1 - iter.len();
+                let origin = ast::Origin::new(
+                    self.bytecode.lines.get(pc).map(|&line| line as usize),
+                    first_pc..end_pc,
+                );
+                for statement in &mut statements[statement_count..] {
+                    if let Some(statement_origin) = statement.origin_mut() {
+                        *statement_origin = origin.clone();
+                    }
+                }
+                first_pc = end_pc;
+            }
+
+            if matches!(
+                instruction,
+                Instruction::Return { .. } | Instruction::TailCall { .. }
+            ) {
+                break;
+            }
+        }
+    }
+
+    fn lift_blocks(&mut self) {
+        let code = Code(self.bytecode);
+        for (start, end) in self.blocks.ranges(&code) {
+            // the block may already hold statements, see `TestSet`
+            let mut statements =
+                std::mem::take(self.function.block_mut(self.blocks.node(start)).unwrap());
+            self.lift_instruction(start, end, &mut statements);
+            *self.function.block_mut(self.blocks.node(start)).unwrap() = statements;
+
+            let edges = self.blocks.edges(&code, end);
+            self.function.set_edges(self.blocks.node(start), edges);
+        }
+    }
+}
+
+fn primitive(value: Primitive) -> ast::Literal {
+    match value {
+        Primitive::Nil => ast::Literal::Nil,
+        Primitive::False => ast::Literal::Boolean(false),
+        Primitive::True => ast::Literal::Boolean(true),
+    }
+}
+
+// a table constructor whose keys and values are all constants is dumped as a template
+fn template_table(Table { array, hash }: &Table) -> ast::Table {
+    // index 0 is only set by `[0] = ...`, the array part is positional after it up to the
+    // last value
+    let length = array
+        .iter()
+        .rposition(|v| !v.is_nil())
+        .map_or(0, |last| last + 1);
+    let zero = array.first().filter(|v| !v.is_nil()).map(|v| {
+        (
+            Some(ast::Literal::Number(0.0).into()),
+            table_value(v).into(),
+        )
+    });
+    let positional = array
+        .get(1..length)
+        .unwrap_or_default()
+        .iter()
+        .map(|v| (None, table_value(v).into()));
+    let keyed = hash
+        .iter()
+        .map(|(k, v)| (Some(table_value(k).into()), table_value(v).into()));
+    ast::Table(zero.into_iter().chain(positional).chain(keyed).collect())
+}
+
+fn invalid_constant(kind: &str, index: u16, statements: &mut Vec<Statement>) {
+    statements.push(ast::Comment::new(format!("invalid {} {}", kind, index)).into());
+}
+
+fn table_value(value: &TableValue) -> ast::Literal {
+    match *value {
+        TableValue::Nil => ast::Literal::Nil,
+        TableValue::Boolean(v) => ast::Literal::Boolean(v),
+        TableValue::Integer(v) => ast::Literal::Number(v as f64),
+        TableValue::Number(v) => ast::Literal::Number(v),
+        TableValue::String(v) => ast::Literal::String(v.to_vec()),
+    }
+}
+
+impl<'a> decompiler::Lifter<'a> for Lifter<'a> {
+    type Chunk = Chunk<'a>;
+    type Prototype = Prototype<'a>;
+
+    const STRUCTURE_METHOD_CALLS: bool = true;
+
+    fn main(chunk: &'a Chunk<'a>) -> Self::Prototype {
+        (0, &chunk.function)
+    }
+
+    fn id((id, _): &Self::Prototype) -> usize {
+        *id
+    }
+
+    fn disassemble(_: &'a Chunk<'a>, (_, bytecode): &Self::Prototype) -> Vec<String> {
+        bytecode
+            .code
+            .iter()
+            .enumerate()
+            .map(|(pc, instruction)| format!("{:>4} {:?}", pc, instruction))
+            .collect()
+    }
+
+    fn lift(
+        chunk: &'a Chunk<'a>,
+        (id, bytecode): Self::Prototype,
+    ) -> LiftedFunction<Self::Prototype> {
+        let mut function = Function::new(id);
+        let blocks = BlockMap::new(&Code(bytecode), &mut function);
+        let mut context = Self {
+            bytecode,
+            frame_slot: chunk.header.two_slot_frame as u8,
+            blocks,
+            insert_between: FxHashMap::default(),
+            locals: FxHashMap::default(),
+            function,
+            upvalues: Vec::new(),
+            child_functions: FxHashMap::default(),
+            register_writes: RegisterWrites::default(),
+        };
+
+        context.allocate_locals();
+        context.lift_blocks();
+
+        let stack_init_node = context.function.new_block();
+        let stack_init_block = context.function.block_mut(stack_init_node).unwrap();
+        stack_init_block.reserve(context.locals.len());
+        for local in context.locals.values() {
+            if !context.function.parameters.contains(local) {
+                let stack_init_block = context.function.block_mut(stack_init_node).unwrap();
+                stack_init_block.push(
+                    ast::Assign::new(vec![local.clone().into()], vec![ast::Literal::Nil.into()])
+                        .into(),
+                )
+            }
+        }
+        context.function.set_edges(
+            stack_init_node,
+            vec![(
+                context.blocks.target(0),
+                BlockEdge::new(BranchType::Unconditional),
+            )],
+        );
+        context.function.set_entry(stack_init_node);
+
+        context.blocks.insert_between(
+            &mut context.function,
+            std::mem::take(&mut context.insert_between),
+            &mut context.register_writes,
+            &context.locals,
+        );
+        let local_variables = local_variables(
+            context
+                .bytecode
+                .locals
+                .iter()
+                .map(|l| (l.name, l.range.clone())),
+        );
+        context
+            .register_writes
+            .name(&local_variables, &mut context.function);
+
+        LiftedFunction {
+            function: context.function,
+            upvalues: context.upvalues,
+            child_functions: context.child_functions,
+        }
+    }
+}
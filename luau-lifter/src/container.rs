@@ -0,0 +1,176 @@
+use std::borrow::Cow;
+
+use base64::prelude::*;
+use ruzstd::decoding::FrameDecoder;
+use thiserror::Error;
+use xxhash_rust::xxh32::xxh32;
+
+const RSB1_MAGIC: &[u8; 4] = b"RSB1";
+const ZSTD_MAGIC: &[u8; 4] = &[0x28, 0xB5, 0x2F, 0xFD];
+const HASH_SEED: u32 = 42;
+const KEY_MULTIPLIER: u8 = 41;
+// the decompressed size comes from the input, so don't trust it with more than this
+const MAX_DECOMPRESSED_SIZE: usize = 1 << 28;
+// base64 of hex of a container is plausible, anything deeper is garbage
+const MAX_DEPTH: usize = 4;
+
+#[derive(Debug, Error)]
+pub enum ContainerError {
+    #[error("RSB1 container declares {0} bytes, more than can be decompressed")]
+    TooLarge(usize),
+    #[error("RSB1 payload is not valid zstd: {0}")]
+    Decompress(String),
+    #[error("RSB1 container declares {expected} bytes but decompressed to {actual}")]
+    SizeMismatch { expected: usize, actual: usize },
+    #[error("RSB1 container hash is {expected:#010x} but its contents hash to {actual:#010x}")]
+    HashMismatch { expected: u32, actual: u32 },
+    #[error("input is nested in more than {MAX_DEPTH} containers")]
+    TooDeep,
+}
+
+// how the bytecode was wrapped, outermost first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    // `RSB1` header and size, zstd payload, and the whole thing xored with a key derived
+    // from its xxhash. the format the roblox client stores bytecode in
+    Rsb1,
+    Base64,
+    Hex,
+}
+
+// the key is the little endian hash, each byte offset by a multiple of its position
+fn mask(input: &mut [u8], hash: u32) {
+    let key = hash.to_le_bytes();
+    for (index, byte) in input.iter_mut().enumerate() {
+        *byte ^= key[index % 4].wrapping_add((index as u8).wrapping_mul(KEY_MULTIPLIER));
+    }
+}
+
+// the first four bytes are always `RSB1`, which is enough to recover the key
+fn rsb1_hash(input: &[u8]) -> Option<u32> {
+    if input.len() < 12 {
+        return None;
+    }
+    let mut key = [0; 4];
+    for (index, byte) in key.iter_mut().enumerate() {
+        *byte = (input[index] ^ RSB1_MAGIC[index])
+            .wrapping_sub((index as u8).wrapping_mul(KEY_MULTIPLIER));
+    }
+    let hash = u32::from_le_bytes(key);
+    // any four bytes give a key, the payload starting with zstd's magic confirms it
+    let mut header = input[..12].to_vec();
+    mask(&mut header, hash);
+    (&header[8..] == ZSTD_MAGIC).then_some(hash)
+}
+
+fn unwrap_rsb1(input: &[u8], hash: u32) -> Result<Vec<u8>, ContainerError> {
+    let mut input = input.to_vec();
+    mask(&mut input, hash);
+    let actual = xxh32(&input, HASH_SEED);
+    if actual != hash {
+        return Err(ContainerError::HashMismatch {
+            expected: hash,
+            actual,
+        });
+    }
+
+    let expected = u32::from_le_bytes(input[4..8].try_into().unwrap()) as usize;
+    if expected > MAX_DECOMPRESSED_SIZE {
+        return Err(ContainerError::TooLarge(expected));
+    }
+    let mut output = vec![0; expected];
+    let actual = FrameDecoder::new()
+        .decode_all(&input[8..], &mut output)
+        .map_err(|e| ContainerError::Decompress(e.to_string()))?;
+    if actual != expected {
+        return Err(ContainerError::SizeMismatch { expected, actual });
+    }
+    Ok(output)
+}
+
+// the text of a base64 or hex container, with surrounding whitespace and line breaks removed
+fn text(input: &[u8]) -> Option<Vec<u8>> {
+    let text = input
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect::<Vec<_>>();
+    (!text.is_empty() && text.iter().all(u8::is_ascii_graphic)).then_some(text)
+}
+
+fn unwrap_layer(input: &[u8]) -> Result<Option<(Container, Vec<u8>)>, ContainerError> {
+    if let Some(hash) = rsb1_hash(input) {
+        return Ok(Some((Container::Rsb1, unwrap_rsb1(input, hash)?)));
+    }
+    // bytecode starts with its version, which is never printable
+    let Some(text) = text(input) else {
+        return Ok(None);
+    };
+    if let Ok(output) = hex::decode(&text) {
+        return Ok(Some((Container::Hex, output)));
+    }
+    if let Ok(output) = BASE64_STANDARD.decode(&text) {
+        return Ok(Some((Container::Base64, output)));
+    }
+    Ok(None)
+}
+
+// strips every container around the bytecode, returning them outermost first. input that
+// isn't in a container is returned as is, it's up to the deserializer to reject it
+pub fn unwrap(input: &[u8]) -> Result<(Cow<[u8]>, Vec<Container>), ContainerError> {
+    let mut output = Cow::Borrowed(input);
+    let mut containers = Vec::new();
+    while let Some((container, unwrapped)) = unwrap_layer(&output)? {
+        if containers.len() == MAX_DEPTH {
+            return Err(ContainerError::TooDeep);
+        }
+        containers.push(container);
+        output = Cow::Owned(unwrapped);
+    }
+    Ok((output, containers))
+}
+
+#[cfg(test)]
+mod tests {
+    use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+
+    use super::*;
+
+    const BYTECODE: &[u8] = include_bytes!("../fixtures/control_flow.bin");
+
+    fn rsb1(bytecode: &[u8]) -> Vec<u8> {
+        let mut output = RSB1_MAGIC.to_vec();
+        output.extend_from_slice(&(bytecode.len() as u32).to_le_bytes());
+        output.extend(compress_to_vec(bytecode, CompressionLevel::Fastest));
+        let hash = xxh32(&output, HASH_SEED);
+        mask(&mut output, hash);
+        output
+    }
+
+    #[test]
+    fn unwraps_nested_containers() {
+        let hex = hex::encode(rsb1(BYTECODE));
+        let base64 = BASE64_STANDARD.encode(&hex);
+        let (output, containers) = unwrap(base64.as_bytes()).unwrap();
+        assert_eq!(
+            containers,
+            [Container::Base64, Container::Hex, Container::Rsb1]
+        );
+        assert!(output == BYTECODE);
+
+        let (output, containers) = unwrap(BYTECODE).unwrap();
+        assert!(containers.is_empty());
+        assert!(output == BYTECODE);
+    }
+
+    #[test]
+    fn rejects_corrupt_rsb1() {
+        let mut container = rsb1(BYTECODE);
+        let last = container.len() - 1;
+        container[last] ^= 1;
+        assert!(matches!(
+            unwrap(&container),
+            Err(ContainerError::HashMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::{container, deserializer, op_code::OpCode, validator};
+
+// maps the opcode byte of an encoded instruction to the real opcode
+pub trait OpcodeDecoder: fmt::Debug + Send + Sync {
+    fn decode(&self, op_code: u8) -> u8;
+}
+
+// op = op * key % 256, roblox client bytecode uses 203
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultiplicativeDecoder(pub u8);
+
+impl OpcodeDecoder for MultiplicativeDecoder {
+    fn decode(&self, op_code: u8) -> u8 {
+        op_code.wrapping_mul(self.0)
+    }
+}
+
+// op = op ^ key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XorDecoder(pub u8);
+
+impl OpcodeDecoder for XorDecoder {
+    fn decode(&self, op_code: u8) -> u8 {
+        op_code ^ self.0
+    }
+}
+
+// an arbitrary table from encoded to real opcodes
+#[derive(Clone, PartialEq, Eq)]
+pub struct PermutationDecoder {
+    table: [u8; 256],
+}
+
+#[derive(Debug, Error)]
+pub enum PermutationError {
+    #[error("line {line}: expected an opcode name or number followed by its encoded value")]
+    Syntax { line: usize },
+    #[error("line {line}: unknown opcode {name}")]
+    UnknownOpCode { line: usize, name: String },
+    #[error("line {line}: {value} is not a byte")]
+    InvalidValue { line: usize, value: String },
+    #[error("line {line}: encoded value {encoded} is already used by opcode {op_code}")]
+    Duplicate {
+        line: usize,
+        encoded: u8,
+        op_code: u8,
+    },
+    #[error("line {line}: opcode {op_code} is already encoded as {encoded}")]
+    DuplicateOpCode {
+        line: usize,
+        op_code: u8,
+        encoded: u8,
+    },
+}
+
+impl PermutationDecoder {
+    // `encoded[op]` is the byte opcode `op` is encoded as
+    pub fn new(encoded: &[u8]) -> Self {
+        // bytes that no opcode is encoded as decode to an invalid opcode
+        let mut table = [u8::MAX; 256];
+        for (op_code, &encoded) in encoded.iter().enumerate() {
+            table[encoded as usize] = op_code as u8;
+        }
+        Self { table }
+    }
+
+    // parses lines of `<opcode> <encoded>`, where the opcode is a number or a name like `CALL`
+    // or `LOP_CALL` and values are decimal or 0x-prefixed hex. `#` starts a comment
+    pub fn parse(source: &str) -> Result<Self, PermutationError> {
+        let mut table = [u8::MAX; 256];
+        // each opcode has one encoding, so that the table is a permutation
+        let mut encodings = [None; 256];
+        for (line, text) in source.lines().enumerate() {
+            let line = line + 1;
+            let text = text.split('#').next().unwrap().trim();
+            if text.is_empty() {
+                continue;
+            }
+            let (op_code, encoded) = match text.split_whitespace().collect::<Vec<_>>()[..] {
+                [op_code, encoded] => (op_code, encoded),
+                _ => return Err(PermutationError::Syntax { line }),
+            };
+            let op_code = match parse_byte(op_code) {
+                Some(op_code) => op_code,
+                None => {
+                    op_code_by_name(op_code).ok_or_else(|| PermutationError::UnknownOpCode {
+                        line,
+                        name: op_code.to_string(),
+                    })?
+                }
+            };
+            let encoded = parse_byte(encoded).ok_or_else(|| PermutationError::InvalidValue {
+                line,
+                value: encoded.to_string(),
+            })?;
+            if table[encoded as usize] != u8::MAX {
+                return Err(PermutationError::Duplicate {
+                    line,
+                    encoded,
+                    op_code: table[encoded as usize],
+                });
+            }
+            if let Some(encoded) = encodings[op_code as usize] {
+                return Err(PermutationError::DuplicateOpCode {
+                    line,
+                    op_code,
+                    encoded,
+                });
+            }
+            table[encoded as usize] = op_code;
+            encodings[op_code as usize] = Some(encoded);
+        }
+        Ok(Self { table })
+    }
+}
+
+impl fmt::Debug for PermutationDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.table
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &op_code)| op_code != u8::MAX),
+            )
+            .finish()
+    }
+}
+
+impl OpcodeDecoder for PermutationDecoder {
+    fn decode(&self, op_code: u8) -> u8 {
+        self.table[op_code as usize]
+    }
+}
+
+fn parse_byte(text: &str) -> Option<u8> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn op_code_by_name(name: &str) -> Option<u8> {
+    let name = name.strip_prefix("LOP_").unwrap_or(name);
+    (0..OpCode::LOP__COUNT as u8).find(|&op_code| {
+        format!("{:?}", OpCode::try_from(op_code).unwrap()) == format!("LOP_{}", name)
+    })
+}
+
+// tries the multiplicative and xor keys, starting with the common ones, and returns the
+// first decoder that deserializes the bytecode into well-formed functions.
+// a wrong key usually produces an opcode stream with invalid jumps, registers or constants
+pub fn detect(bytecode: &[u8]) -> Option<Box<dyn OpcodeDecoder>> {
+    let (bytecode, _) = container::unwrap(bytecode).ok()?;
+    let multiplicative = [1, 203]
+        .into_iter()
+        .chain((1..=u8::MAX).step_by(2).filter(|&k| k != 1 && k != 203))
+        .map(|key| Box::new(MultiplicativeDecoder(key)) as Box<dyn OpcodeDecoder>);
+    // xor with 0 is the same as multiplying by 1
+    let xor = (1..=u8::MAX).map(|key| Box::new(XorDecoder(key)) as Box<dyn OpcodeDecoder>);
+    multiplicative.chain(xor).find(|decoder| {
+        deserializer::deserialize(&bytecode, decoder.as_ref())
+            .is_ok_and(|chunk| validator::errors(validator::validate(&chunk)).is_empty())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserializer::deserialize, serializer::serialize};
+
+    const FIXTURES: [&[u8]; 4] = [
+        include_bytes!("../fixtures/control_flow.bin"),
+        include_bytes!("../fixtures/control_flow_stripped.bin"),
+        include_bytes!("../fixtures/native.bin"),
+        include_bytes!("../fixtures/typed.bin"),
+    ];
+
+    #[test]
+    fn detects_the_key_a_fixture_was_encoded_with() {
+        let decoders: [&dyn OpcodeDecoder; 3] = [
+            &MultiplicativeDecoder(1),
+            &MultiplicativeDecoder(203),
+            &XorDecoder(0x5a),
+        ];
+        for (index, bytecode) in FIXTURES.into_iter().enumerate() {
+            let chunk = deserialize(bytecode, &MultiplicativeDecoder(1)).unwrap();
+            for decoder in decoders {
+                let encoded = serialize(&chunk, decoder).unwrap();
+                let detected = detect(&encoded).map(|d| format!("{:?}", d));
+                assert_eq!(
+                    detected,
+                    Some(format!("{:?}", decoder)),
+                    "fixture {}",
+                    index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parses_permutations() {
+        let decoder =
+            PermutationDecoder::parse("CALL 0x9f\n\n# comment\nLOP_RETURN 3 # end\n0 7\n").unwrap();
+        assert_eq!(decoder.decode(0x9f), OpCode::LOP_CALL as u8);
+        assert_eq!(decoder.decode(3), OpCode::LOP_RETURN as u8);
+        assert_eq!(decoder.decode(7), 0);
+        assert_eq!(decoder.decode(8), u8::MAX);
+    }
+
+    #[test]
+    fn rejects_malformed_permutations() {
+        assert!(matches!(
+            PermutationDecoder::parse("CALL"),
+            Err(PermutationError::Syntax { line: 1 })
+        ));
+        assert!(matches!(
+            PermutationDecoder::parse("CALL 1\nRETURN 2 3"),
+            Err(PermutationError::Syntax { line: 2 })
+        ));
+        assert!(matches!(
+            PermutationDecoder::parse("NOT_AN_OPCODE 1"),
+            Err(PermutationError::UnknownOpCode { line: 1, .. })
+        ));
+        for value in ["256", "-1", "0xzz"] {
+            assert!(matches!(
+                PermutationDecoder::parse(&format!("CALL {}", value)),
+                Err(PermutationError::InvalidValue { line: 1, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_permutations_that_are_not_bijective() {
+        let call = OpCode::LOP_CALL as u8;
+        assert!(matches!(
+            PermutationDecoder::parse("CALL 1\nRETURN 1"),
+            Err(PermutationError::Duplicate { line: 2, encoded: 1, op_code }) if op_code == call
+        ));
+        assert!(matches!(
+            PermutationDecoder::parse("CALL 1\nLOP_CALL 2"),
+            Err(PermutationError::DuplicateOpCode { line: 2, op_code, encoded: 1 }) if op_code == call
+        ));
+    }
+}
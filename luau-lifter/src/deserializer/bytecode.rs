@@ -1,29 +1,25 @@
-use nom::{bytes::complete::take, number::complete::le_u8, IResult};
+use nom::number::complete::le_u8;
 
-use super::chunk::Chunk;
+use crate::decoder::OpcodeDecoder;
 
-#[derive(Debug)]
-pub enum Bytecode {
-    Error(String),
-    Chunk(Chunk),
-}
+use super::{
+    chunk::Chunk,
+    error::{DeserializeErrorKind, ParseError, ParseResult},
+};
 
-impl Bytecode {
-    pub fn parse(input: &[u8], encode_key: u8) -> IResult<&[u8], Bytecode> {
-        let (input, status_code) = le_u8(input)?;
-        match status_code {
-            0 => {
-                let (input, error_msg) = take(input.len())(input)?;
-                Ok((
-                    input,
-                    Bytecode::Error(String::from_utf8_lossy(error_msg).to_string()),
-                ))
-            }
-            4..=6 => {
-                let (input, chunk) = Chunk::parse(input, encode_key, status_code)?;
-                Ok((input, Bytecode::Chunk(chunk)))
-            }
-            _ => panic!("Unsupported bytecode version: {}", status_code),
-        }
+pub(crate) fn parse<'a>(
+    input: &'a [u8],
+    decoder: &dyn OpcodeDecoder,
+    lenient: bool,
+) -> ParseResult<'a, Chunk> {
+    let (rest, status_code) = le_u8(input)?;
+    match status_code {
+        // the compiler emits a zero version followed by the error message when compilation fails
+        0 => ParseError::fail(
+            rest,
+            DeserializeErrorKind::Compilation(String::from_utf8_lossy(rest).to_string()),
+        ),
+        4..=6 => Chunk::parse(rest, decoder, status_code, lenient),
+        _ => ParseError::fail(input, DeserializeErrorKind::UnsupportedVersion(status_code)),
     }
 }
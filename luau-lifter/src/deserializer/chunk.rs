@@ -1,43 +1,228 @@
-use super::{function::Function, list::parse_list, parse_string};
-use nom::character::complete::char;
-use nom::multi::many_till;
+use super::{
+    error::{DeserializeErrorKind, ParseError, ParseResult},
+    function::Function,
+    list::parse_list,
+    parse_string,
+};
+use std::collections::BTreeSet;
+
+use crate::{decoder::OpcodeDecoder, validator};
 use nom::number::complete::le_u8;
-use nom::IResult;
 use nom_leb128::leb128_usize;
 
+// how far past the start of a corrupt function the next one is looked for
+const MAX_RESYNC_DISTANCE: usize = 1 << 20;
+
 #[derive(Debug)]
 pub struct Chunk {
+    pub version: u8,
+    pub types_version: u8,
     pub string_table: Vec<Vec<u8>>,
+    // the index and name of each userdata type referred to by type info.
+    // indices start at 1, a tagged userdata type refers to the index one above its tag
+    pub userdata_types: Vec<(u8, usize)>,
     pub functions: Vec<Function>,
+    // functions that couldn't be recovered when deserializing leniently, their entries in
+    // `functions` are placeholders unless they parsed but failed validation
+    pub missing_functions: BTreeSet<usize>,
     pub main: usize,
 }
 
 impl Chunk {
-    pub(crate) fn parse(input: &[u8], encode_key: u8, version: u8) -> IResult<&[u8], Self> {
-        let (input, types_version) = if version >= 4 {
+    pub fn is_missing(&self, function_id: usize) -> bool {
+        self.missing_functions.contains(&function_id)
+    }
+
+    fn parse_function<'a>(
+        input: &'a [u8],
+        decoder: &dyn OpcodeDecoder,
+        types_version: u8,
+        function_count: usize,
+    ) -> ParseResult<'a, Function> {
+        let (rest, function) = Function::parse(input, decoder, types_version)?;
+        if let Some(&child) = function.functions.iter().find(|&&f| f >= function_count) {
+            return ParseError::fail(input, DeserializeErrorKind::InvalidFunction(child));
+        }
+        Ok((rest, function))
+    }
+
+    // functions aren't prefixed with their length, so after one fails to parse we continue from
+    // the next offset that parses into a plausible function and assume it is the next one.
+    // the search is bounded and most offsets are ruled out by the header alone, a full parse
+    // at every offset of a large corrupt region would take quadratic time
+    fn resync<'a>(
+        input: &'a [u8],
+        decoder: &dyn OpcodeDecoder,
+        types_version: u8,
+        function_count: usize,
+    ) -> &'a [u8] {
+        (1..input.len().min(MAX_RESYNC_DISTANCE))
+            .map(|offset| &input[offset..])
+            .find(|input| {
+                // the parameters are in registers and `is_vararg` is a bool
+                matches!(input, &[max_stack_size, num_parameters, _, is_vararg, ..]
+                    if num_parameters <= max_stack_size && is_vararg <= 1)
+                    && Self::parse_function(input, decoder, types_version, function_count)
+                        .is_ok_and(|(_, function)| {
+                            function
+                                .instructions
+                                .last()
+                                .is_some_and(|&i| validator::is_terminator(i))
+                        })
+            })
+            .unwrap_or_default()
+    }
+
+    // when `lenient`, functions that fail to parse are replaced with placeholders and marked
+    // as missing instead of failing the whole chunk
+    pub(crate) fn parse<'a>(
+        input: &'a [u8],
+        decoder: &dyn OpcodeDecoder,
+        version: u8,
+        lenient: bool,
+    ) -> ParseResult<'a, Self> {
+        let (rest, types_version) = if version >= 4 {
             le_u8(input)?
         } else {
             (input, 0)
         };
         if types_version > 3 {
-            panic!("unsupported types version");
+            return ParseError::fail(
+                input,
+                DeserializeErrorKind::UnsupportedTypesVersion(types_version),
+            );
         }
+        let input = rest;
         let (input, string_table) = parse_list(input, parse_string)?;
-        let input = if types_version == 3 {
-            many_till(leb128_usize, char('\0'))(input)?.0
+        let (input, userdata_types) = if types_version == 3 {
+            parse_userdata_types(input)?
         } else {
-            input
+            (input, Vec::new())
+        };
+
+        let (mut input, function_count) = leb128_usize(input)?;
+        // every function takes more than a byte, so a larger count can't be from a truncated
+        // chunk and would only fill memory with placeholders
+        if lenient && function_count > input.len() {
+            return ParseError::fail(
+                input,
+                DeserializeErrorKind::InvalidFunctionCount(function_count),
+            );
+        }
+        let mut functions = Vec::new();
+        let mut missing_functions = BTreeSet::new();
+        for index in 0..function_count {
+            match Self::parse_function(input, decoder, types_version, function_count) {
+                Ok((rest, function)) => {
+                    functions.push(function);
+                    input = rest;
+                }
+                Err(_) if lenient => {
+                    functions.push(Function::placeholder());
+                    missing_functions.insert(index);
+                    input = Self::resync(input, decoder, types_version, function_count);
+                }
+                Err(err) => return Err(err.map(|e| e.in_function(index))),
+            }
+        }
+
+        let (rest, main) = match leb128_usize(input) {
+            Ok((rest, main)) if main < function_count => (rest, main),
+            // the compiler writes the main function last
+            _ if lenient && function_count > 0 => (&input[input.len()..], function_count - 1),
+            Ok((_, main)) => {
+                return ParseError::fail(input, DeserializeErrorKind::InvalidFunction(main))
+            }
+            Err(err) => return Err(err),
         };
-        let (input, functions) = parse_list(input, |i| Function::parse(i, encode_key))?;
-        let (input, main) = leb128_usize(input)?;
 
         Ok((
-            input,
+            rest,
             Self {
+                version,
+                types_version,
                 string_table,
+                userdata_types,
                 functions,
+                missing_functions,
                 main,
             },
         ))
     }
 }
+
+// a list of (index, name) pairs terminated by a zero index
+fn parse_userdata_types(mut input: &[u8]) -> ParseResult<Vec<(u8, usize)>> {
+    let mut userdata_types = Vec::new();
+    loop {
+        let (rest, index) = le_u8(input)?;
+        if index == 0 {
+            return Ok((rest, userdata_types));
+        }
+        let (rest, name) = leb128_usize(rest)?;
+        userdata_types.push((index, name));
+        input = rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decoder::MultiplicativeDecoder, decompile_bytecode_lenient,
+        deserializer::deserialize_lenient, Limits,
+    };
+
+    // functions 0 and 2 are `greet` and `count`, 1 is the closure `count` returns and 3 is main
+    const BYTECODE: &[u8] = include_bytes!("../../fixtures/control_flow.bin");
+
+    // where each function starts, followed by where the last one ends
+    fn function_offsets() -> Vec<usize> {
+        let decoder = MultiplicativeDecoder(1);
+        let (input, types_version) = le_u8::<_, ParseError>(&BYTECODE[1..]).unwrap();
+        let (input, _) = parse_list(input, parse_string).unwrap();
+        let (input, _) = if types_version == 3 {
+            parse_userdata_types(input).unwrap()
+        } else {
+            (input, Vec::new())
+        };
+        let (mut input, function_count) = leb128_usize::<ParseError>(input).unwrap();
+        let mut offsets = vec![BYTECODE.len() - input.len()];
+        for _ in 0..function_count {
+            (input, _) = Function::parse(input, &decoder, types_version).unwrap();
+            offsets.push(BYTECODE.len() - input.len());
+        }
+        offsets
+    }
+
+    fn decompile(bytecode: &[u8]) -> String {
+        decompile_bytecode_lenient(bytecode, &MultiplicativeDecoder(1), &Limits::default())
+            .unwrap()
+            .source
+    }
+
+    #[test]
+    fn skips_corrupt_functions() {
+        let offsets = function_offsets();
+        let mut bytecode = BYTECODE.to_vec();
+        bytecode[offsets[1]..offsets[2]].fill(0xff);
+        let chunk = deserialize_lenient(&bytecode, &MultiplicativeDecoder(1)).unwrap();
+        assert_eq!(chunk.missing_functions, BTreeSet::from([1]));
+        assert_eq!(chunk.main, 3);
+        let source = decompile(&bytecode);
+        assert!(source.contains("--[[ missing proto #1 ]]"), "{}", source);
+    }
+
+    #[test]
+    fn keeps_functions_before_truncation() {
+        let offsets = function_offsets();
+        let bytecode = &BYTECODE[..(offsets[2] + offsets[3]) / 2];
+        let chunk = deserialize_lenient(bytecode, &MultiplicativeDecoder(1)).unwrap();
+        assert_eq!(chunk.missing_functions, BTreeSet::from([2, 3]));
+        assert_eq!(chunk.main, 3);
+        assert!(!chunk.functions[0].instructions.is_empty());
+        let source = decompile(bytecode);
+        assert!(source.contains("missing proto #3"), "{}", source);
+        assert!(source.contains("recovered proto #0"), "{}", source);
+    }
+}
@@ -1,8 +1,8 @@
-use super::list::parse_list;
-use nom::{
-    number::complete::{le_f32, le_f64, le_u32, le_u8},
-    IResult,
+use super::{
+    error::{DeserializeErrorKind, ParseError, ParseResult},
+    list::parse_list,
 };
+use nom::number::complete::{le_f32, le_f64, le_u32, le_u8};
 use nom_leb128::leb128_usize;
 
 const CONSTANT_NIL: u8 = 0;
@@ -27,8 +27,8 @@ pub enum Constant {
 }
 
 impl Constant {
-    pub(crate) fn parse(input: &[u8]) -> IResult<&[u8], Self> {
-        let (input, tag) = le_u8(input)?;
+    pub(crate) fn parse(start: &[u8]) -> ParseResult<Self> {
+        let (input, tag) = le_u8(start)?;
         match tag {
             CONSTANT_NIL => Ok((input, Constant::Nil)),
             CONSTANT_BOOLEAN => {
@@ -62,7 +62,7 @@ impl Constant {
                 let (input, w) = le_f32(input)?;
                 Ok((input, Constant::Vector(x, y, z, w)))
             }
-            _ => panic!("{}", tag),
+            _ => ParseError::fail(start, DeserializeErrorKind::UnknownConstant(tag)),
         }
     }
 }
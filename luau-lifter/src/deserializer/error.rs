@@ -0,0 +1,119 @@
+use std::fmt;
+
+use nom::{
+    error::{ErrorKind, FromExternalError},
+    IResult,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DeserializeErrorKind {
+    #[error("compilation failed: {0}")]
+    Compilation(String),
+    #[error("unsupported bytecode version {0}")]
+    UnsupportedVersion(u8),
+    #[error("unsupported types version {0}")]
+    UnsupportedTypesVersion(u8),
+    #[error("unknown constant tag {0}")]
+    UnknownConstant(u8),
+    #[error("invalid opcode {0}")]
+    InvalidOpCode(u8),
+    #[error("missing aux word")]
+    MissingAux,
+    #[error("invalid line gap {0}")]
+    InvalidLineGap(u8),
+    #[error("invalid function index {0}")]
+    InvalidFunction(usize),
+    #[error("invalid function count {0}")]
+    InvalidFunctionCount(usize),
+    #[error("unexpected end of input")]
+    UnexpectedEnd,
+    #[error("malformed input ({0:?})")]
+    Malformed(ErrorKind),
+}
+
+#[derive(Debug, Error)]
+#[error("{kind} at offset {offset}{}", Location(*.function, *.pc))]
+pub struct DeserializeError {
+    pub kind: DeserializeErrorKind,
+    pub offset: usize,
+    pub function: Option<usize>,
+    pub pc: Option<usize>,
+}
+
+struct Location(Option<usize>, Option<usize>);
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(function) = self.0 {
+            write!(f, " in function {}", function)?;
+        }
+        if let Some(pc) = self.1 {
+            write!(f, " at pc {}", pc)?;
+        }
+        Ok(())
+    }
+}
+
+// the error type used by the parsers, the offset is computed from the remaining input once parsing fails
+#[derive(Debug)]
+pub(crate) struct ParseError<'a> {
+    pub input: &'a [u8],
+    pub kind: DeserializeErrorKind,
+    pub function: Option<usize>,
+    pub pc: Option<usize>,
+}
+
+pub(crate) type ParseResult<'a, T> = IResult<&'a [u8], T, ParseError<'a>>;
+
+impl<'a> ParseError<'a> {
+    pub fn new(input: &'a [u8], kind: DeserializeErrorKind) -> Self {
+        Self {
+            input,
+            kind,
+            function: None,
+            pc: None,
+        }
+    }
+
+    // fails parsing without backtracking
+    pub fn fail<T>(input: &'a [u8], kind: DeserializeErrorKind) -> ParseResult<'a, T> {
+        Err(nom::Err::Failure(Self::new(input, kind)))
+    }
+
+    pub fn in_function(mut self, function: usize) -> Self {
+        self.function.get_or_insert(function);
+        self
+    }
+
+    pub fn into_error(self, bytecode: &[u8]) -> DeserializeError {
+        DeserializeError {
+            kind: self.kind,
+            offset: bytecode.len() - self.input.len(),
+            function: self.function,
+            pc: self.pc,
+        }
+    }
+}
+
+impl<'a> nom::error::ParseError<&'a [u8]> for ParseError<'a> {
+    fn from_error_kind(input: &'a [u8], kind: ErrorKind) -> Self {
+        Self::new(
+            input,
+            match kind {
+                ErrorKind::Eof => DeserializeErrorKind::UnexpectedEnd,
+                _ => DeserializeErrorKind::Malformed(kind),
+            },
+        )
+    }
+
+    fn append(_: &'a [u8], _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<'a, E> FromExternalError<&'a [u8], E> for ParseError<'a> {
+    fn from_external_error(input: &'a [u8], kind: ErrorKind, _: E) -> Self {
+        <Self as nom::error::ParseError<&'a [u8]>>::from_error_kind(input, kind)
+    }
+}
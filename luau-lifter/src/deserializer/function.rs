@@ -1,18 +1,124 @@
 use core::num;
 
 use nom::{
-    complete::take,
+    bytes::complete::take,
     number::complete::{le_u32, le_u8},
-    IResult,
 };
 use nom_leb128::leb128_usize;
 
 use super::{
     constant::Constant,
+    error::{DeserializeErrorKind, ParseError, ParseResult},
     list::{parse_list, parse_list_len},
 };
 
-use crate::{instruction::*, op_code::OpCode};
+use crate::{decoder::OpcodeDecoder, instruction::*, op_code::OpCode};
+
+#[derive(Debug)]
+pub struct LocalVariable {
+    pub name: usize,
+    pub start_pc: usize,
+    pub end_pc: usize,
+    pub register: u8,
+}
+
+impl LocalVariable {
+    fn parse(input: &[u8]) -> ParseResult<Self> {
+        let (input, name) = leb128_usize(input)?;
+        let (input, start_pc) = leb128_usize(input)?;
+        let (input, end_pc) = leb128_usize(input)?;
+        let (input, register) = le_u8(input)?;
+        Ok((
+            input,
+            Self {
+                name,
+                start_pc,
+                end_pc,
+                register,
+            },
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct TypedLocal {
+    pub r#type: u8,
+    pub register: u8,
+    pub start_pc: usize,
+    pub length: usize,
+}
+
+impl TypedLocal {
+    fn parse(input: &[u8]) -> ParseResult<Self> {
+        let (input, r#type) = le_u8(input)?;
+        let (input, register) = le_u8(input)?;
+        let (input, start_pc) = leb128_usize(input)?;
+        let (input, length) = leb128_usize(input)?;
+        Ok((
+            input,
+            Self {
+                r#type,
+                register,
+                start_pc,
+                length,
+            },
+        ))
+    }
+}
+
+// types are encoded as a byte, see LuauBytecodeType
+#[derive(Debug, Default)]
+pub struct TypeInfo {
+    // LBC_TYPE_FUNCTION followed by the number of parameters and the type of each one
+    pub function: Vec<u8>,
+    pub upvalues: Vec<u8>,
+    pub locals: Vec<TypedLocal>,
+}
+
+impl TypeInfo {
+    fn parse(input: &[u8], types_version: u8) -> ParseResult<Self> {
+        let (input, size) = leb128_usize(input)?;
+        let (input, data) = take(size)(input)?;
+        let type_info = match types_version {
+            // before version 2 only the function type was encoded
+            0 | 1 => Self {
+                function: data.to_vec(),
+                ..Default::default()
+            },
+            _ if data.is_empty() => Self::default(),
+            _ => {
+                let (data, function_size) = leb128_usize(data)?;
+                let (data, upvalue_count) = leb128_usize(data)?;
+                let (data, local_count) = leb128_usize(data)?;
+                let (data, function) = take(function_size)(data)?;
+                let (data, upvalues) = take(upvalue_count)(data)?;
+                let (_, locals) = parse_list_len(data, TypedLocal::parse, local_count)?;
+                Self {
+                    function: function.to_vec(),
+                    upvalues: upvalues.to_vec(),
+                    locals,
+                }
+            }
+        };
+        Ok((input, type_info))
+    }
+
+    // the types of the parameters, if they were recorded
+    pub fn parameters(&self) -> Option<&[u8]> {
+        match self.function[..] {
+            // LBC_TYPE_FUNCTION
+            [5, count, ref parameters @ ..] if parameters.len() == count as usize => {
+                Some(parameters)
+            }
+            _ => None,
+        }
+    }
+}
+
+// LPF_NATIVE_MODULE, set on the main function of a `--!native` chunk
+pub const NATIVE_MODULE: u8 = 1 << 0;
+// LPF_NATIVE_FUNCTION, set on functions with the `@native` attribute
+pub const NATIVE_FUNCTION: u8 = 1 << 2;
 
 #[derive(Debug)]
 pub struct Function {
@@ -20,6 +126,8 @@ pub struct Function {
     pub num_parameters: u8,
     pub num_upvalues: u8,
     pub is_vararg: bool,
+    pub flags: u8,
+    pub type_info: TypeInfo,
     //pub instructions: Vec<u32>,
     pub instructions: Vec<Instruction>,
     pub constants: Vec<Constant>,
@@ -29,47 +137,53 @@ pub struct Function {
     pub line_gap_log2: Option<u8>,
     pub line_info_delta: Option<Vec<u8>>,
     pub abs_line_info_delta: Option<Vec<u32>>,
+    // whether local and upvalue names were emitted, they can be present but empty
+    pub has_debug_info: bool,
+    pub local_variables: Vec<LocalVariable>,
+    pub upvalue_names: Vec<usize>,
 }
 
 impl Function {
-    fn parse_instructions(vec: &Vec<u32>, encode_key: u8) -> Vec<Instruction> {
+    // stands in for a function that couldn't be deserialized
+    pub(crate) fn placeholder() -> Self {
+        Self {
+            max_stack_size: 0,
+            num_parameters: 0,
+            num_upvalues: 0,
+            is_vararg: true,
+            flags: 0,
+            type_info: TypeInfo::default(),
+            instructions: Vec::new(),
+            constants: Vec::new(),
+            functions: Vec::new(),
+            line_defined: 0,
+            function_name: 0,
+            line_gap_log2: None,
+            line_info_delta: None,
+            abs_line_info_delta: None,
+            has_debug_info: false,
+            local_variables: Vec::new(),
+            upvalue_names: Vec::new(),
+        }
+    }
+
+    // returns the pc of the offending instruction on failure
+    fn parse_instructions(
+        vec: &[u32],
+        decoder: &dyn OpcodeDecoder,
+    ) -> Result<Vec<Instruction>, (usize, DeserializeErrorKind)> {
         let mut v: Vec<Instruction> = Vec::new();
         let mut pc = 0;
 
-        loop {
-            let ins = Instruction::parse(vec[pc], encode_key).unwrap();
-            let op = match ins {
-                Instruction::BC { op_code, .. } => op_code,
-                Instruction::AD { op_code, .. } => op_code,
-                Instruction::E { op_code, .. } => op_code,
-            };
-
+        while pc < vec.len() {
+            let ins = Instruction::parse(vec[pc], decoder)
+                .map_err(|op_code| (pc, DeserializeErrorKind::InvalidOpCode(op_code)))?;
             // handle ops with aux values
-            match op {
-                OpCode::LOP_GETGLOBAL
-                | OpCode::LOP_SETGLOBAL
-                | OpCode::LOP_GETIMPORT
-                | OpCode::LOP_GETTABLEKS
-                | OpCode::LOP_SETTABLEKS
-                | OpCode::LOP_NAMECALL
-                | OpCode::LOP_JUMPIFEQ
-                | OpCode::LOP_JUMPIFLE
-                | OpCode::LOP_JUMPIFLT
-                | OpCode::LOP_JUMPIFNOTEQ
-                | OpCode::LOP_JUMPIFNOTLE
-                | OpCode::LOP_JUMPIFNOTLT
-                | OpCode::LOP_NEWTABLE
-                | OpCode::LOP_SETLIST
-                | OpCode::LOP_FORGLOOP
-                | OpCode::LOP_LOADKX
-                | OpCode::LOP_FASTCALL2
-                | OpCode::LOP_FASTCALL2K
-                | OpCode::LOP_FASTCALL3
-                | OpCode::LOP_JUMPXEQKNIL
-                | OpCode::LOP_JUMPXEQKB
-                | OpCode::LOP_JUMPXEQKN
-                | OpCode::LOP_JUMPXEQKS => {
-                    let aux = vec[pc + 1];
+            match ins.op_code() {
+                op if op.has_aux() => {
+                    let aux = *vec
+                        .get(pc + 1)
+                        .ok_or((pc, DeserializeErrorKind::MissingAux))?;
                     pc += 2;
                     match ins {
                         Instruction::BC {
@@ -101,27 +215,37 @@ impl Function {
                     pc += 1;
                 }
             }
-
-            if pc == vec.len() {
-                break;
-            }
         }
 
-        v
+        Ok(v)
     }
 
-    pub(crate) fn parse(input: &[u8], encode_key: u8) -> IResult<&[u8], Self> {
+    pub(crate) fn parse<'a>(
+        input: &'a [u8],
+        decoder: &dyn OpcodeDecoder,
+        types_version: u8,
+    ) -> ParseResult<'a, Self> {
         let (input, max_stack_size) = le_u8(input)?;
         let (input, num_parameters) = le_u8(input)?;
         let (input, num_upvalues) = le_u8(input)?;
         let (input, is_vararg) = le_u8(input)?;
 
         let (input, flags) = le_u8(input)?;
-        let (input, _) = parse_list(input, le_u8)?;
+        let (input, type_info) = TypeInfo::parse(input, types_version)?;
 
-        let (input, u32_instructions) = parse_list(input, le_u32)?;
+        let (input, instruction_count) = leb128_usize(input)?;
+        let code = input;
+        let (input, u32_instructions) = parse_list_len(input, le_u32, instruction_count)?;
         //let (input, instructions) = parse_list(input, Function::parse_instrution)?;
-        let instructions = Self::parse_instructions(&u32_instructions, encode_key);
+        let instructions =
+            Self::parse_instructions(&u32_instructions, decoder).map_err(|(pc, kind)| {
+                nom::Err::Failure(ParseError {
+                    input: &code[pc * 4..],
+                    kind,
+                    function: None,
+                    pc: Some(pc),
+                })
+            })?;
         let (input, constants) = parse_list(input, Constant::parse)?;
         let (input, functions) = parse_list(input, leb128_usize)?;
         let (input, line_defined) = leb128_usize(input)?;
@@ -130,8 +254,14 @@ impl Function {
         let (input, line_gap_log2) = match has_line_info {
             0 => (input, None),
             _ => {
-                let (input, line_gap_log2) = le_u8(input)?;
-                (input, Some(line_gap_log2))
+                let (rest, line_gap_log2) = le_u8(input)?;
+                if u32::from(line_gap_log2) >= usize::BITS {
+                    return ParseError::fail(
+                        input,
+                        DeserializeErrorKind::InvalidLineGap(line_gap_log2),
+                    );
+                }
+                (rest, Some(line_gap_log2))
             }
         };
         let (input, line_info_delta) = match has_line_info {
@@ -145,30 +275,21 @@ impl Function {
         let (input, abs_line_info_delta) = match has_line_info {
             0 => (input, None),
             _ => {
-                let (input, abs_line_info_delta) = parse_list_len(
-                    input,
-                    le_u32,
-                    ((u32_instructions.len() - 1) >> line_gap_log2.unwrap()) + 1,
-                )?;
+                let intervals = u32_instructions
+                    .len()
+                    .checked_sub(1)
+                    .map_or(0, |last| (last >> line_gap_log2.unwrap()) + 1);
+                let (input, abs_line_info_delta) = parse_list_len(input, le_u32, intervals)?;
                 (input, Some(abs_line_info_delta))
             }
         };
-        let input = match le_u8(input)? {
-            (input, 0) => input,
-            (input, _) => {
-                panic!("we have debug info");
-                let (mut input, num_locvars) = leb128_usize(input)?;
-                for _ in 0..num_locvars {
-                    (input, _) = leb128_usize(input)?;
-                    (input, _) = leb128_usize(input)?;
-                    (input, _) = leb128_usize(input)?;
-                    (input, _) = le_u8(input)?;
-                }
-                let (mut input, num_upvalues) = leb128_usize(input)?;
-                for _ in 0..num_upvalues {
-                    (input, _) = leb128_usize(input)?;
-                }
-                input
+        let (input, has_debug_info) = le_u8(input)?;
+        let (input, (local_variables, upvalue_names)) = match has_debug_info {
+            0 => (input, (Vec::new(), Vec::new())),
+            _ => {
+                let (input, local_variables) = parse_list(input, LocalVariable::parse)?;
+                let (input, upvalue_names) = parse_list(input, leb128_usize)?;
+                (input, (local_variables, upvalue_names))
             }
         };
         Ok((
@@ -178,6 +299,8 @@ impl Function {
                 num_parameters,
                 num_upvalues,
                 is_vararg: is_vararg != 0u8,
+                flags,
+                type_info,
                 instructions,
                 constants,
                 functions,
@@ -186,7 +309,40 @@ impl Function {
                 line_gap_log2,
                 line_info_delta,
                 abs_line_info_delta,
+                has_debug_info: has_debug_info != 0,
+                local_variables,
+                upvalue_names,
             },
         ))
     }
+
+    // the source line of each instruction, if the function was compiled with line info.
+    // both lists are deltas, the line of an instruction is the absolute line of its interval
+    // plus its offset
+    pub fn lines(&self) -> Option<Vec<usize>> {
+        let line_gap_log2 = self.line_gap_log2?;
+        let abs_lines = self
+            .abs_line_info_delta
+            .as_ref()?
+            .iter()
+            .scan(0u32, |line, &delta| {
+                *line = line.wrapping_add(delta);
+                Some(*line)
+            })
+            .collect::<Vec<_>>();
+        let offsets = self
+            .line_info_delta
+            .as_ref()?
+            .iter()
+            .scan(0u8, |offset, &delta| {
+                *offset = offset.wrapping_add(delta);
+                Some(*offset)
+            });
+        Some(
+            offsets
+                .enumerate()
+                .map(|(pc, offset)| abs_lines[pc >> line_gap_log2] as usize + offset as usize)
+                .collect(),
+        )
+    }
 }
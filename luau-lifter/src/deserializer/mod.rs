@@ -1,22 +1,54 @@
-use nom::{bytes::complete::take, IResult};
+use nom::bytes::complete::take;
 use nom_leb128::leb128_usize;
 
 pub mod bytecode;
 pub mod chunk;
 pub mod constant;
+mod error;
 pub mod function;
 mod list;
 
-fn parse_string(input: &[u8]) -> IResult<&[u8], Vec<u8>> {
+pub use error::{DeserializeError, DeserializeErrorKind};
+
+use crate::decoder::OpcodeDecoder;
+use error::ParseResult;
+
+fn parse_string(input: &[u8]) -> ParseResult<Vec<u8>> {
     let (input, length) = leb128_usize(input)?;
     let (input, bytes) = take(length)(input)?;
     Ok((input, bytes.to_owned()))
 }
 
-pub fn deserialize(bytecode: &[u8], encode_key: u8) -> Result<bytecode::Bytecode, String> {
-    match bytecode::Bytecode::parse(bytecode, encode_key) {
-        Ok((_, deserialized_bytecode)) => Ok(deserialized_bytecode),
-        Err(err) => Err(err.to_string()),
+pub fn deserialize(
+    bytecode: &[u8],
+    decoder: &dyn OpcodeDecoder,
+) -> Result<chunk::Chunk, DeserializeError> {
+    parse(bytecode, decoder, false)
+}
+
+// keeps the functions that parse when others don't, for truncated or partially corrupt chunks.
+// only fails if the header or string table can't be read
+pub fn deserialize_lenient(
+    bytecode: &[u8],
+    decoder: &dyn OpcodeDecoder,
+) -> Result<chunk::Chunk, DeserializeError> {
+    parse(bytecode, decoder, true)
+}
+
+fn parse(
+    bytecode: &[u8],
+    decoder: &dyn OpcodeDecoder,
+    lenient: bool,
+) -> Result<chunk::Chunk, DeserializeError> {
+    match bytecode::parse(bytecode, decoder, lenient) {
+        Ok((_, chunk)) => Ok(chunk),
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => Err(err.into_error(bytecode)),
+        Err(nom::Err::Incomplete(_)) => Err(DeserializeError {
+            kind: DeserializeErrorKind::UnexpectedEnd,
+            offset: bytecode.len(),
+            function: None,
+            pc: None,
+        }),
     }
 }
 
@@ -0,0 +1,354 @@
+use std::fmt::Write;
+
+use itertools::Itertools;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    deserializer::{chunk::Chunk, constant::Constant, function::Function},
+    instruction::Instruction,
+    op_code::OpCode,
+};
+
+// how far past the next instruction an instruction may jump
+pub(crate) fn jump_offset(instruction: Instruction) -> Option<isize> {
+    Some(match instruction {
+        Instruction::BC {
+            op_code: OpCode::LOP_LOADB,
+            c,
+            ..
+        } if c != 0 => c.into(),
+        Instruction::AD {
+            op_code:
+                OpCode::LOP_JUMP
+                | OpCode::LOP_JUMPBACK
+                | OpCode::LOP_JUMPIF
+                | OpCode::LOP_JUMPIFNOT
+                | OpCode::LOP_JUMPIFEQ
+                | OpCode::LOP_JUMPIFLE
+                | OpCode::LOP_JUMPIFLT
+                | OpCode::LOP_JUMPIFNOTEQ
+                | OpCode::LOP_JUMPIFNOTLE
+                | OpCode::LOP_JUMPIFNOTLT
+                | OpCode::LOP_JUMPXEQKNIL
+                | OpCode::LOP_JUMPXEQKB
+                | OpCode::LOP_JUMPXEQKN
+                | OpCode::LOP_JUMPXEQKS
+                | OpCode::LOP_FORNPREP
+                | OpCode::LOP_FORNLOOP
+                | OpCode::LOP_FORGPREP
+                | OpCode::LOP_FORGPREP_NEXT
+                | OpCode::LOP_FORGPREP_INEXT
+                | OpCode::LOP_FORGLOOP,
+            d,
+            ..
+        } => d.into(),
+        Instruction::E {
+            op_code: OpCode::LOP_JUMPX,
+            e,
+        } => e.try_into().ok()?,
+        _ => return None,
+    })
+}
+
+// the pc an instruction may jump to, other than the next instruction
+pub(crate) fn jump_target(pc: usize, instruction: Instruction) -> Option<usize> {
+    (pc + 1).checked_add_signed(jump_offset(instruction)?)
+}
+
+fn raw_string(chunk: &Chunk, index: usize) -> Option<String> {
+    // string indices are 1-based, 0 means no string
+    index
+        .checked_sub(1)
+        .and_then(|i| chunk.string_table.get(i))
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+}
+
+fn string(chunk: &Chunk, index: usize) -> Option<String> {
+    raw_string(chunk, index).map(|s| format!("{:?}", s))
+}
+
+fn import_path(chunk: &Chunk, function: &Function, import: u32) -> String {
+    let len = (import >> 30) as usize;
+    [(import >> 20) & 1023, (import >> 10) & 1023, import & 1023][..len.min(3)]
+        .iter()
+        .map(|&k| match function.constants.get(k as usize) {
+            Some(&Constant::String(s)) => chunk
+                .string_table
+                .get(s.wrapping_sub(1))
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+                .unwrap_or_else(|| "?".into()),
+            _ => "?".into(),
+        })
+        .join(".")
+}
+
+fn constant(chunk: &Chunk, function: &Function, index: usize) -> String {
+    match function.constants.get(index) {
+        None => format!("<invalid constant {}>", index),
+        Some(Constant::Nil) => "nil".into(),
+        Some(Constant::Boolean(v)) => v.to_string(),
+        Some(Constant::Number(v)) => v.to_string(),
+        Some(&Constant::String(s)) => {
+            string(chunk, s).unwrap_or_else(|| format!("<invalid string {}>", s))
+        }
+        Some(&Constant::Import(import)) => import_path(chunk, function, import as u32),
+        Some(Constant::Table(keys)) => format!("{{{}}}", keys.len()),
+        Some(Constant::Closure(f)) => format!("function {}", f),
+        Some(Constant::Vector(x, y, z, w)) => format!("vector({}, {}, {}, {})", x, y, z, w),
+    }
+}
+
+// describes the operands of an instruction that refer to something other than registers
+fn annotation(chunk: &Chunk, function: &Function, instruction: Instruction) -> Option<String> {
+    let constant = |index: usize| constant(chunk, function, index);
+    Some(match instruction {
+        Instruction::BC {
+            op_code, b, c, aux, ..
+        } => match op_code {
+            OpCode::LOP_LOADB => (b != 0).to_string(),
+            OpCode::LOP_GETGLOBAL
+            | OpCode::LOP_SETGLOBAL
+            | OpCode::LOP_GETTABLEKS
+            | OpCode::LOP_SETTABLEKS
+            | OpCode::LOP_NAMECALL
+            | OpCode::LOP_LOADKX => constant(aux as usize),
+            OpCode::LOP_GETTABLEN | OpCode::LOP_SETTABLEN => (c as usize + 1).to_string(),
+            OpCode::LOP_ADDK
+            | OpCode::LOP_SUBK
+            | OpCode::LOP_MULK
+            | OpCode::LOP_DIVK
+            | OpCode::LOP_MODK
+            | OpCode::LOP_POWK
+            | OpCode::LOP_ANDK
+            | OpCode::LOP_ORK
+            | OpCode::LOP_IDIVK
+            | OpCode::LOP_FASTCALL2K => constant(c as usize),
+            OpCode::LOP_SUBRK | OpCode::LOP_DIVRK => constant(b as usize),
+            _ => return None,
+        },
+        Instruction::AD {
+            op_code, d, aux, ..
+        } => match op_code {
+            OpCode::LOP_LOADN => d.to_string(),
+            OpCode::LOP_LOADK | OpCode::LOP_DUPTABLE | OpCode::LOP_DUPCLOSURE => {
+                constant(d as u16 as usize)
+            }
+            OpCode::LOP_GETIMPORT => import_path(chunk, function, aux),
+            OpCode::LOP_NEWCLOSURE => match function.functions.get(d as u16 as usize) {
+                Some(&f) => format!("function {}", f),
+                None => format!("<invalid child {}>", d),
+            },
+            OpCode::LOP_JUMPXEQKNIL => format!("{}nil", if aux >> 31 != 0 { "not " } else { "" }),
+            OpCode::LOP_JUMPXEQKB => format!(
+                "{}{}",
+                if aux >> 31 != 0 { "not " } else { "" },
+                aux & 1 != 0
+            ),
+            OpCode::LOP_JUMPXEQKN | OpCode::LOP_JUMPXEQKS => format!(
+                "{}{}",
+                if aux >> 31 != 0 { "not " } else { "" },
+                constant((aux & 0xFFFFFF) as usize)
+            ),
+            _ => return None,
+        },
+        Instruction::E { .. } => return None,
+    })
+}
+
+// like `constant`, but says what kind of constant it is where that isn't obvious
+fn describe_constant(chunk: &Chunk, function: &Function, index: usize) -> String {
+    match &function.constants[index] {
+        &Constant::Import(import) => {
+            format!("import {}", import_path(chunk, function, import as u32))
+        }
+        Constant::Table(keys) => format!(
+            "table {{{}}}",
+            keys.iter()
+                .map(|&key| constant(chunk, function, key))
+                .join(", ")
+        ),
+        _ => constant(chunk, function, index),
+    }
+}
+
+// the function's signature, constants and children as comments
+fn header(chunk: &Chunk, function_id: usize) -> Vec<String> {
+    let function = &chunk.functions[function_id];
+    let mut title = format!("function {}", function_id);
+    if let Some(name) = raw_string(chunk, function.function_name) {
+        write!(title, " ({})", name).unwrap();
+    }
+    if function_id == chunk.main {
+        title.push_str(" (main)");
+    }
+    if function.line_defined != 0 {
+        write!(title, " at line {}", function.line_defined).unwrap();
+    }
+
+    if chunk.is_missing(function_id) {
+        return vec![format!("{}:", title), "; missing".to_string()];
+    }
+
+    let mut lines = vec![
+        format!("{}:", title),
+        format!(
+            "; params: {}, upvalues: {}, vararg: {}, max stack: {}",
+            function.num_parameters,
+            function.num_upvalues,
+            function.is_vararg,
+            function.max_stack_size
+        ),
+    ];
+    for index in 0..function.constants.len() {
+        lines.push(format!(
+            "; K{}: {}",
+            index,
+            describe_constant(chunk, function, index)
+        ));
+    }
+    if !function.functions.is_empty() {
+        lines.push(format!(
+            "; children: {}",
+            function.functions.iter().join(", ")
+        ));
+    }
+    lines
+}
+
+// the listing of every function in the chunk, in the order they are stored
+pub fn disassemble(chunk: &Chunk) -> String {
+    let mut output = String::new();
+    for function_id in 0..chunk.functions.len() {
+        if function_id != 0 {
+            output.push('\n');
+        }
+        for line in header(chunk, function_id)
+            .into_iter()
+            .chain(disassemble_function(chunk, function_id))
+        {
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+    output
+}
+
+// renders the instructions of a function one per line, with constants resolved
+// and jump targets replaced by labels
+pub fn disassemble_function(chunk: &Chunk, function_id: usize) -> Vec<String> {
+    let function = &chunk.functions[function_id];
+    let instructions = &function.instructions;
+
+    // the deserializer places a nop after every instruction with an aux word
+    let is_aux = |pc: usize| pc > 0 && instructions[pc - 1].op_code().has_aux();
+
+    let labels = instructions
+        .iter()
+        .enumerate()
+        .filter(|&(pc, _)| !is_aux(pc))
+        .filter_map(|(pc, &instruction)| jump_target(pc, instruction))
+        .sorted_unstable()
+        .dedup()
+        .enumerate()
+        .map(|(i, pc)| (pc, format!("L{}", i)))
+        .collect::<FxHashMap<_, _>>();
+
+    let mut lines = Vec::with_capacity(instructions.len() + labels.len());
+    for (pc, &instruction) in instructions.iter().enumerate() {
+        if let Some(label) = labels.get(&pc) {
+            lines.push(format!("{}:", label));
+        }
+        if is_aux(pc) {
+            continue;
+        }
+
+        let op_code = instruction.op_code();
+        let mut line = format!(
+            "{:>4} {:<16}",
+            pc,
+            format!("{:?}", op_code).trim_start_matches("LOP_")
+        );
+        match instruction {
+            Instruction::BC { a, b, c, aux, .. } => {
+                write!(line, " {} {} {}", a, b, c).unwrap();
+                if op_code.has_aux() {
+                    write!(line, " [{}]", aux).unwrap();
+                }
+            }
+            Instruction::AD { a, d, aux, .. } => {
+                write!(line, " {} {}", a, d).unwrap();
+                if op_code.has_aux() {
+                    write!(line, " [{}]", aux).unwrap();
+                }
+            }
+            Instruction::E { e, .. } => write!(line, " {}", e).unwrap(),
+        }
+
+        let mut annotations = Vec::new();
+        if let Some(annotation) = annotation(chunk, function, instruction) {
+            annotations.push(annotation);
+        }
+        if let Some(target) = jump_target(pc, instruction) {
+            annotations.push(match labels.get(&target) {
+                Some(label) if target < instructions.len() => format!("to {}", label),
+                _ => format!("to <invalid pc {}>", target),
+            });
+        }
+        if !annotations.is_empty() {
+            write!(line, " ; {}", annotations.join(", ")).unwrap();
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decoder::MultiplicativeDecoder, deserializer::deserialize};
+
+    fn listing() -> String {
+        let chunk = deserialize(
+            include_bytes!("../fixtures/control_flow.bin"),
+            &MultiplicativeDecoder(1),
+        )
+        .unwrap();
+        disassemble(&chunk)
+    }
+
+    #[test]
+    fn disassembles_functions() {
+        let listing = listing();
+        let leaf = "\
+function 1 at line 20:
+; params: 0, upvalues: 2, vararg: false, max stack: 2
+; K0: \"total\"
+   0 GETUPVAL         1 0 0
+   1 GETTABLEKS       0 1 81 [0] ; \"total\"
+   3 GETUPVAL         1 1 0
+   4 RETURN           0 3 0
+";
+        assert!(listing.contains(leaf), "{}", listing);
+        assert!(listing.contains("function 2 (count) at line 17:\n"));
+        assert!(listing.contains("  16 NEWCLOSURE       1 0 ; function 1\n"));
+    }
+
+    // jump targets get labels, and aux words aren't listed as instructions
+    #[test]
+    fn labels_jump_targets() {
+        let listing = listing();
+        for expected in [
+            "   4 JUMPXEQKS        0 7 [2147483649] ; not \"x\", to L0\n   6 GETIMPORT",
+            "  11 JUMP             0 10 ; to L1\nL0:\n  12 LENGTH",
+            "  25 FORNPREP         2 6 ; to L3\nL2:\n",
+            "  31 FORNLOOP         2 -6 ; to L2\nL3:\n  32 RETURN",
+        ] {
+            assert!(
+                listing.contains(expected),
+                "missing {:?} in:\n{}",
+                expected,
+                listing
+            );
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::convert::TryFrom;
 
-use crate::op_code::OpCode;
+use crate::{decoder::OpcodeDecoder, op_code::OpCode};
 
 /*
 
@@ -113,9 +113,10 @@ pub enum Instruction {
 }
 
 impl Instruction {
-    pub fn parse(insn: u32, encode_key: u8) -> Result<Instruction, nom::error::ErrorKind> {
-        let op_code = (insn & 0xFF) as u8;
-        let op_code = op_code.wrapping_mul(encode_key);
+    // returns the encoded opcode if it doesn't decode to a valid one
+    pub fn parse(insn: u32, decoder: &dyn OpcodeDecoder) -> Result<Instruction, u8> {
+        let encoded = (insn & 0xFF) as u8;
+        let op_code = decoder.decode(encoded);
         match op_code {
             0
             | 1
@@ -170,7 +171,34 @@ impl Instruction {
                 c: 0,
                 aux: 0,
             }),
-            _ => unreachable!("{}", op_code),
+            _ => Err(encoded),
+        }
+    }
+
+    pub fn op_code(&self) -> OpCode {
+        match *self {
+            Self::BC { op_code, .. } | Self::AD { op_code, .. } | Self::E { op_code, .. } => {
+                op_code
+            }
+        }
+    }
+
+    pub fn aux(&self) -> u32 {
+        match *self {
+            Self::BC { aux, .. } | Self::AD { aux, .. } => aux,
+            Self::E { .. } => 0,
+        }
+    }
+
+    // the instruction word with `op_code` in place of the real opcode, without the aux word
+    pub fn encode(&self, op_code: u8) -> u32 {
+        let op_code = op_code as u32;
+        match *self {
+            Self::BC { a, b, c, .. } => {
+                op_code | (a as u32) << 8 | (b as u32) << 16 | (c as u32) << 24
+            }
+            Self::AD { a, d, .. } => op_code | (a as u32) << 8 | (d as u16 as u32) << 16,
+            Self::E { e, .. } => op_code | (e as u32) << 8,
         }
     }
 
@@ -7,24 +7,31 @@ use parking_lot::Mutex;
 use petgraph::stable_graph::NodeIndex;
 
 use rustc_hash::FxHashMap;
+use std::collections::{BTreeMap, BTreeSet};
 use triomphe::Arc;
 
 use super::{
     deserializer::{
-        constant::Constant as BytecodeConstant, function::Function as BytecodeFunction,
+        chunk::Chunk,
+        constant::Constant as BytecodeConstant,
+        function::{self as bytecode_function, Function as BytecodeFunction},
     },
+    disassembler,
     instruction::Instruction,
     op_code::OpCode,
 };
-use ast::{self};
+use ast::{self, formatter::Formatter, type_system::Type};
 use cfg::{
     block::{BlockEdge, BranchType},
     function::Function,
 };
+use decompiler::{LiftedFunction, RegisterWrites};
 
 pub struct Lifter<'a> {
     function_list: &'a Vec<BytecodeFunction>,
+    missing_functions: &'a BTreeSet<usize>,
     string_table: &'a Vec<Vec<u8>>,
+    userdata_types: &'a Vec<(u8, usize)>,
     blocks: FxHashMap<usize, NodeIndex>,
     function: Function,
     child_functions: FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
@@ -32,21 +39,57 @@ pub struct Lifter<'a> {
     constant_map: FxHashMap<usize, ast::Literal>,
     current_node: Option<NodeIndex>,
     upvalues: Vec<ast::RcLocal>,
+    register_writes: RegisterWrites<usize>,
+    // the source line of each instruction, empty without line info
+    lines: Vec<usize>,
 }
 
-impl<'a> Lifter<'a> {
-    pub fn lift(
-        f_list: &'a Vec<BytecodeFunction>,
-        str_list: &'a Vec<Vec<u8>>,
-        function_id: usize,
-    ) -> (
-        Function,
-        Vec<ast::RcLocal>,
-        FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
-    ) {
+impl<'a> decompiler::Lifter<'a> for Lifter<'a> {
+    type Chunk = Chunk;
+    type Prototype = usize;
+
+    // we can't structure method calls like this because of __namecall
+    const STRUCTURE_METHOD_CALLS: bool = false;
+
+    fn main(chunk: &'a Chunk) -> usize {
+        chunk.main
+    }
+
+    fn id(&function_id: &usize) -> usize {
+        function_id
+    }
+
+    fn disassemble(chunk: &'a Chunk, &function_id: &usize) -> Vec<String> {
+        disassembler::disassemble_function(chunk, function_id)
+    }
+
+    fn is_missing(chunk: &'a Chunk, &function_id: &usize) -> bool {
+        chunk.is_missing(function_id)
+    }
+
+    fn orphans(chunk: &'a Chunk) -> Vec<usize> {
+        let children = chunk
+            .functions
+            .iter()
+            .enumerate()
+            .filter(|&(function_id, _)| !chunk.is_missing(function_id))
+            .flat_map(|(_, function)| function.functions.iter().copied())
+            .collect::<BTreeSet<_>>();
+        (0..chunk.functions.len())
+            .filter(|&function_id| {
+                function_id != chunk.main
+                    && !chunk.is_missing(function_id)
+                    && !children.contains(&function_id)
+            })
+            .collect()
+    }
+
+    fn lift(chunk: &'a Chunk, function_id: usize) -> LiftedFunction<usize> {
         let mut context = Self {
-            function_list: f_list,
-            string_table: str_list,
+            function_list: &chunk.functions,
+            missing_functions: &chunk.missing_functions,
+            string_table: &chunk.string_table,
+            userdata_types: &chunk.userdata_types,
             blocks: FxHashMap::default(),
             function: Function::new(function_id),
             child_functions: FxHashMap::default(),
@@ -54,12 +97,32 @@ impl<'a> Lifter<'a> {
             constant_map: FxHashMap::default(),
             current_node: None,
             upvalues: Vec::new(),
+            register_writes: RegisterWrites::default(),
+            lines: chunk.functions[function_id].lines().unwrap_or_default(),
         };
 
+        let function = &chunk.functions[function_id];
+        context.function.is_native = if function_id == chunk.main {
+            function.flags & bytecode_function::NATIVE_MODULE != 0
+        } else {
+            function.flags & bytecode_function::NATIVE_FUNCTION != 0
+        } || matches!(
+            function.instructions.first(),
+            Some(Instruction::AD {
+                op_code: OpCode::LOP_NATIVECALL,
+                ..
+            })
+        );
         context.lift_function();
-        (context.function, context.upvalues, context.child_functions)
+        LiftedFunction {
+            function: context.function,
+            upvalues: context.upvalues,
+            child_functions: context.child_functions,
+        }
     }
+}
 
+impl<'a> Lifter<'a> {
     fn lift_function(&mut self) {
         self.discover_blocks().unwrap();
 
@@ -91,17 +154,40 @@ impl<'a> Lifter<'a> {
             )
             .1;
 
-        for _ in 0..self.function_list[self.function.id].num_upvalues {
-            self.upvalues.push(ast::RcLocal::default());
+        for i in 0..self.function_list[self.function.id].num_upvalues {
+            let name = self.function_list[self.function.id]
+                .upvalue_names
+                .get(i as usize)
+                .and_then(|&name| self.local_name(name));
+            self.upvalues.push(ast::RcLocal::new(ast::Local::new(name)));
         }
 
+        // parameters are in scope from the start, or after PREPVARARGS in variadic functions
+        let parameters_start = usize::from(self.function_list[self.function.id].is_vararg);
         for i in 0..self.function_list[self.function.id].num_parameters {
-            let parameter = ast::RcLocal::default();
+            let name = self.function_list[self.function.id]
+                .local_variables
+                .iter()
+                .find(|l| l.register == i && l.start_pc <= parameters_start)
+                .and_then(|l| self.local_name(l.name));
+            let parameter = ast::RcLocal::new(ast::Local::new(name));
             self.function.parameters.push(parameter.clone());
             self.register_map.insert(i as usize, parameter);
         }
 
+        if let Some(parameter_types) = self.function_list[self.function.id]
+            .type_info
+            .parameters()
+            .filter(|types| types.len() == self.function.parameters.len())
+        {
+            self.function.parameter_types = parameter_types
+                .iter()
+                .map(|&t| self.bytecode_type(t))
+                .collect();
+        }
+
         self.function.is_variadic = self.function_list[self.function.id].is_vararg;
+        self.function.name = self.string(self.function_list[self.function.id].function_name);
 
         for (start_pc, end_pc) in block_ranges {
             self.current_node = Some(self.block_to_node(start_pc));
@@ -110,6 +196,7 @@ impl<'a> Lifter<'a> {
             block.0.extend(statements);
             self.function.set_edges(self.current_node.unwrap(), edges);
         }
+        self.name_register_writes();
 
         let entry_node = self.function.new_block();
         self.function.set_edges(
@@ -250,12 +337,15 @@ impl<'a> Lifter<'a> {
         let mut edges = Vec::new();
 
         let mut top: Option<(ast::RValue, u8)> = None;
+        // the first instruction that isn't part of a statement yet
+        let mut first_pc = block_start;
 
         let mut iter = self.function_list[self.function.id].instructions[block_start..=block_end]
             .iter()
             .enumerate();
 
         while let Some((index, instruction)) = iter.next() {
+            let statement_count = statements.len();
             match *instruction {
                 Instruction::BC {
                     op_code,
@@ -266,6 +356,16 @@ impl<'a> Lifter<'a> {
                 } => match op_code {
                     // TODO: do we want to nil initialize all registers here?
                     OpCode::LOP_PREPVARARGS => {}
+                    // a debugger breakpoint, it has no effect on the program
+                    OpCode::LOP_BREAK => {}
+                    OpCode::LOP_LOADKX => {
+                        let constant = self.constant(aux as _);
+                        let target = self.register(a as _);
+                        let statement = ast::Assign::new(vec![target.into()], vec![constant]);
+                        statements.push(statement.into());
+                    }
+                    // captures are consumed by the closure instruction before them
+                    OpCode::LOP_CAPTURE => panic!("CAPTURE not preceded by a closure"),
                     OpCode::LOP_MOVE => {
                         let a = self.register(a as _);
                         let b = self.register(b as _);
@@ -315,7 +415,7 @@ impl<'a> Lifter<'a> {
                     }
                     OpCode::LOP_GETGLOBAL => {
                         let value = self.register(a as _);
-                        let global_name = self.constant(aux as _).into_string().unwrap();
+                        let global_name = self.literal(aux as _).into_string().unwrap();
                         statements.push(
                             ast::Assign::new(
                                 vec![value.into()],
@@ -326,7 +426,7 @@ impl<'a> Lifter<'a> {
                     }
                     OpCode::LOP_SETGLOBAL => {
                         let value = self.register(a as _);
-                        let global_name = self.constant(aux as _).into_string().unwrap();
+                        let global_name = self.literal(aux as _).into_string().unwrap();
                         statements.push(
                             ast::Assign::new(
                                 vec![ast::Global::new(global_name).into()],
@@ -354,7 +454,7 @@ impl<'a> Lifter<'a> {
                         statements.push(
                             ast::Assign::new(
                                 vec![target.into()],
-                                vec![ast::Index::new(table.into(), key.into()).into()],
+                                vec![ast::Index::new(table.into(), key).into()],
                             )
                             .into(),
                         );
@@ -389,7 +489,7 @@ impl<'a> Lifter<'a> {
                         let key = self.constant(aux as _);
                         statements.push(
                             ast::Assign::new(
-                                vec![ast::Index::new(table.into(), key.into()).into()],
+                                vec![ast::Index::new(table.into(), key).into()],
                                 vec![value.into()],
                             )
                             .into(),
@@ -458,7 +558,7 @@ impl<'a> Lifter<'a> {
                         statements.push(
                             ast::Assign::new(
                                 vec![target.into()],
-                                vec![ast::Binary::new(left.into(), right.into(), op).into()],
+                                vec![ast::Binary::new(left.into(), right, op).into()],
                             )
                             .into(),
                         );
@@ -492,7 +592,13 @@ impl<'a> Lifter<'a> {
                                 .chain(std::iter::once(tail))
                                 .collect()
                         };
-                        statements.push(ast::Return::new(values).into());
+                        let mut r#return = ast::Return::new(values);
+                        r#return.origin = ast::Origin::new(
+                            self.lines.get(block_start + index).copied(),
+                            first_pc..block_start + index + 1,
+                        );
+                        statements.push(r#return.into());
+                        first_pc = block_start + index + 1;
                         break;
                     }
                     OpCode::LOP_FASTCALL
@@ -503,7 +609,7 @@ impl<'a> Lifter<'a> {
                     OpCode::LOP_NAMECALL => {
                         let namecall_base = a;
                         let namecall_object = self.register(b as _);
-                        let namecall_method = match self.constant(aux as usize) {
+                        let namecall_method = match self.literal(aux as usize) {
                             ast::Literal::String(string) => String::from_utf8(string).unwrap(),
                             _ => unreachable!(),
                         };
@@ -670,7 +776,7 @@ impl<'a> Lifter<'a> {
                             vec![self.register(a as _).into()],
                             vec![ast::Binary::new(
                                 self.register(b as _).into(),
-                                self.constant(c as _).into(),
+                                self.constant(c as _),
                                 ast::BinaryOperation::And,
                             )
                             .into()],
@@ -694,7 +800,7 @@ impl<'a> Lifter<'a> {
                             vec![self.register(a as _).into()],
                             vec![ast::Binary::new(
                                 self.register(b as _).into(),
-                                self.constant(c as _).into(),
+                                self.constant(c as _),
                                 ast::BinaryOperation::Or,
                             )
                             .into()],
@@ -730,7 +836,7 @@ impl<'a> Lifter<'a> {
                         statements.push(
                             ast::Assign::new(
                                 vec![target.into()],
-                                vec![ast::Binary::new(left.into(), right.into(), op).into()],
+                                vec![ast::Binary::new(left, right.into(), op).into()],
                             )
                             .into(),
                         );
@@ -741,10 +847,11 @@ impl<'a> Lifter<'a> {
                     OpCode::LOP_LOADK => {
                         let constant = self.constant(d as _);
                         let target = self.register(a as _);
-                        let statement =
-                            ast::Assign::new(vec![target.into()], vec![constant.into()]);
+                        let statement = ast::Assign::new(vec![target.into()], vec![constant]);
                         statements.push(statement.into());
                     }
+                    // only ever constructed by the vm to enter native code
+                    OpCode::LOP_NATIVECALL => {}
                     OpCode::LOP_LOADN => {
                         let target = self.register(a as _);
                         let statement = ast::Assign::new(
@@ -755,28 +862,7 @@ impl<'a> Lifter<'a> {
                     }
                     OpCode::LOP_GETIMPORT => {
                         let target = self.register(a as _);
-                        let import_len = (aux >> 30) & 3;
-                        assert!(import_len <= 3);
-                        let mut import_expression: ast::RValue = ast::Global::new(
-                            self.constant(((aux >> 20) & 1023) as usize)
-                                .into_string()
-                                .unwrap(),
-                        )
-                        .into();
-                        if import_len > 1 {
-                            import_expression = ast::Index::new(
-                                import_expression,
-                                self.constant(((aux >> 10) & 1023) as usize).into(),
-                            )
-                            .into();
-                        }
-                        if import_len > 2 {
-                            import_expression = ast::Index::new(
-                                import_expression,
-                                self.constant((aux & 1023) as usize).into(),
-                            )
-                            .into();
-                        }
+                        let import_expression = self.import(aux);
                         let assign = ast::Assign::new(vec![target.into()], vec![import_expression]);
                         statements.push(assign.into());
                     }
@@ -1068,12 +1154,8 @@ impl<'a> Lifter<'a> {
                         let literal = self.constant((aux & ((1 << 24) - 1)) as _);
                         statements.push(
                             ast::If::new(
-                                ast::Binary::new(
-                                    a.into(),
-                                    literal.into(),
-                                    ast::BinaryOperation::Equal,
-                                )
-                                .into(),
+                                ast::Binary::new(a.into(), literal, ast::BinaryOperation::Equal)
+                                    .into(),
                                 ast::Block::default(),
                                 ast::Block::default(),
                             )
@@ -1215,19 +1297,18 @@ impl<'a> Lifter<'a> {
                             },
                             _ => unreachable!(),
                         };
-                        let func_name_index = self.function_list[func_index].function_name;
-                        let func_name = if func_name_index == 0 {
-                            None
+
+                        // a missing function's upvalue count is lost, but every upvalue is
+                        // still captured right after the closure is created
+                        let upvalue_count = if self.missing_functions.contains(&func_index) {
+                            iter.clone()
+                                .take_while(|(_, i)| i.op_code() == OpCode::LOP_CAPTURE)
+                                .count()
                         } else {
-                            Some(
-                                String::from_utf8_lossy(&self.string_table[func_name_index - 1])
-                                    .into_owned(),
-                            )
+                            self.function_list[func_index].num_upvalues.into()
                         };
-
-                        let func = &self.function_list[func_index];
-                        let mut upvalues_passed = Vec::with_capacity(func.num_upvalues.into());
-                        for _ in 0..func.num_upvalues {
+                        let mut upvalues_passed = Vec::with_capacity(upvalue_count);
+                        for _ in 0..upvalue_count {
                             let local = match iter.next().as_ref().unwrap().1 {
                                 &Instruction::BC {
                                     op_code: OpCode::LOP_CAPTURE,
@@ -1248,25 +1329,16 @@ impl<'a> Lifter<'a> {
                             upvalues_passed.push(local);
                         }
 
-                        let function = Arc::<Mutex<_>>::default();
-                        self.child_functions
-                            .insert(ByAddress(function.clone()), func_index);
-                        function.lock().name = func_name;
+                        let closure = self.closure(func_index, upvalues_passed);
                         statements.push(
-                            ast::Assign::new(
-                                vec![dest_local.into()],
-                                vec![ast::Closure {
-                                    function: ByAddress(function),
-                                    upvalues: upvalues_passed,
-                                }
-                                .into()],
-                            )
-                            .into(),
+                            ast::Assign::new(vec![dest_local.into()], vec![closure.into()]).into(),
                         );
                     }
                     _ => unreachable!("{:?}", instruction),
                 },
                 Instruction::E { op_code, e } => match op_code {
+                    // coverage counters don't affect the program
+                    OpCode::LOP_COVERAGE => {}
                     OpCode::LOP_JUMPX => {
                         edges.push((
                             self.block_to_node(
@@ -1277,7 +1349,46 @@ impl<'a> Lifter<'a> {
                     }
                     _ => unreachable!("{:?}", instruction),
                 },
-                _ => unimplemented!("{:?}", instruction),
+            }
+
+            // the values written by a loop instruction are read at the start of the loop body
+            let written_pc = match *instruction {
+                Instruction::AD {
+                    op_code: OpCode::LOP_FORNLOOP | OpCode::LOP_FORGLOOP,
+                    d,
+                    ..
+                } => ((block_start + index + 1) as isize + d as isize) as usize,
+                _ => block_start + index,
+            };
+            let node = self.current_node.unwrap();
+            for (statement_index, statement) in statements.iter().enumerate().skip(statement_count)
+            {
+                self.register_writes.record(
+                    &self.register_map,
+                    node,
+                    statement_index,
+                    statement,
+                    written_pc,
+                );
+            }
+            if statements.len() > statement_count {
+                // the aux word belongs to the instruction before it
+                let aux = usize::from(instruction.op_code().has_aux());
+                let end_pc = iter
+                    .clone()
+                    .map(|(i, _)| block_start + i)
+                    .find(|&pc| pc > block_start + index + aux)
+                    .unwrap_or(block_end + 1);
+                let origin = ast::Origin::new(
+                    self.lines.get(block_start + index).copied(),
+                    first_pc..end_pc,
+                );
+                for statement in &mut statements[statement_count..] {
+                    if let Some(statement_origin) = statement.origin_mut() {
+                        *statement_origin = origin.clone();
+                    }
+                }
+                first_pc = end_pc;
             }
         }
 
@@ -1285,6 +1396,13 @@ impl<'a> Lifter<'a> {
             .next()
             .map(|(i, _)| block_start + i - 1)
             .unwrap_or(block_end);
+        // trailing jumps belong to the last statement
+        match statements.last_mut().and_then(|s| s.origin_mut()) {
+            Some(origin) if first_pc <= last_index => {
+                origin.merge(ast::Origin::new(None, first_pc..last_index + 1))
+            }
+            _ => {}
+        }
         if edges.is_empty()
             && !Self::is_terminator(self.function_list[self.function.id].instructions[last_index])
         {
@@ -1306,8 +1424,79 @@ impl<'a> Lifter<'a> {
         self.register_map.entry(index).or_default().clone()
     }
 
-    fn constant(&mut self, index: usize) -> ast::Literal {
-        let converted_constant = match self.function_list[self.function.id]
+    fn string(&self, index: usize) -> Option<String> {
+        // string indices are 1-based, 0 means no string
+        index
+            .checked_sub(1)
+            .and_then(|i| self.string_table.get(i))
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+    }
+
+    // tampered bytecode can name locals with keywords or nothing at all, those are left to be
+    // generated instead
+    fn local_name(&self, index: usize) -> Option<String> {
+        self.string(index)
+            .filter(|name| Formatter::<String>::is_valid_name(name.as_bytes()))
+    }
+
+    // see LuauBytecodeType
+    fn bytecode_type(&self, r#type: u8) -> Type {
+        // LBC_TYPE_OPTIONAL_BIT
+        if r#type & 0x80 != 0 {
+            return match self.bytecode_type(r#type & !0x80) {
+                Type::Any => Type::Any,
+                r#type => Type::Optional(Box::new(r#type)),
+            };
+        }
+        match r#type {
+            0 => Type::Nil,
+            1 => Type::Boolean,
+            2 => Type::Number,
+            3 => Type::String,
+            4 => Type::Table {
+                indexer: Box::new((Type::Number, Type::Any)),
+                fields: BTreeMap::new(),
+            },
+            5 => Type::Function(vec![Type::VarArg], vec![Type::VarArg]),
+            6 => Type::Thread,
+            7 => Type::Userdata,
+            8 => Type::Vector,
+            9 => Type::Buffer,
+            // LBC_TYPE_TAGGED_USERDATA_BASE..LBC_TYPE_TAGGED_USERDATA_END
+            64..96 => {
+                let index = r#type - 64 + 1;
+                self.userdata_types
+                    .iter()
+                    .find(|&&(i, _)| i == index)
+                    .and_then(|&(_, name)| self.string(name))
+                    .map_or(Type::Userdata, Type::Named)
+            }
+            // LBC_TYPE_ANY and anything we don't know about
+            _ => Type::Any,
+        }
+    }
+
+    fn name_register_writes(&mut self) {
+        let string_table = self.string_table;
+        let local_variables = self.function_list[self.function.id]
+            .local_variables
+            .iter()
+            .filter_map(|l| {
+                // string indices are 1-based, 0 means no string
+                let name = string_table.get(l.name.checked_sub(1)?)?;
+                Formatter::<String>::is_valid_name(name).then_some((
+                    l.register as usize,
+                    l.start_pc..l.end_pc,
+                    name.as_slice(),
+                ))
+            })
+            .collect_vec();
+        std::mem::take(&mut self.register_writes).name(&local_variables, &mut self.function);
+    }
+
+    fn constant(&mut self, index: usize) -> ast::RValue {
+        let function_list = self.function_list;
+        let converted_constant = match function_list[self.function.id]
             .constants
             .get(index)
             .unwrap()
@@ -1320,12 +1509,59 @@ impl<'a> Lifter<'a> {
                 ast::Literal::String(self.string_table[*v - 1].clone())
             }
             BytecodeConstant::Vector(x, y, z, _) => ast::Literal::Vector(*x, *y, *z),
-            _ => unimplemented!(),
+            // the vm resolves imports when the chunk is loaded
+            &BytecodeConstant::Import(import) => return self.import(import as u32),
+            // table constants are the templates used by DUPTABLE, their values are always nil
+            BytecodeConstant::Table(_) => return ast::Table::default().into(),
+            &BytecodeConstant::Closure(function) => {
+                return self.closure(function, Vec::new()).into()
+            }
         };
         self.constant_map
             .entry(index)
             .or_insert(converted_constant)
             .clone()
+            .into()
+    }
+
+    fn literal(&mut self, index: usize) -> ast::Literal {
+        match self.constant(index) {
+            ast::RValue::Literal(literal) => literal,
+            constant => panic!("constant {} is not a literal: {}", index, constant),
+        }
+    }
+
+    // the expression for an import path, a global optionally indexed up to twice
+    fn import(&mut self, import: u32) -> ast::RValue {
+        let import_len = (import >> 30) & 3;
+        let mut import_expression: ast::RValue = ast::Global::new(
+            self.literal(((import >> 20) & 1023) as usize)
+                .into_string()
+                .unwrap(),
+        )
+        .into();
+        if import_len > 1 {
+            import_expression = ast::Index::new(
+                import_expression,
+                self.constant(((import >> 10) & 1023) as usize),
+            )
+            .into();
+        }
+        if import_len > 2 {
+            import_expression =
+                ast::Index::new(import_expression, self.constant((import & 1023) as usize)).into();
+        }
+        import_expression
+    }
+
+    fn closure(&mut self, function_id: usize, upvalues: Vec<ast::Upvalue>) -> ast::Closure {
+        let function = Arc::<Mutex<_>>::default();
+        self.child_functions
+            .insert(ByAddress(function.clone()), function_id);
+        ast::Closure {
+            function: ByAddress(function),
+            upvalues,
+        }
     }
 
     fn block_to_node(&self, insn_index: usize) -> NodeIndex {
@@ -1366,3 +1602,347 @@ impl<'a> Lifter<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{panic, time::Duration};
+
+    use super::*;
+    use crate::{
+        decoder::MultiplicativeDecoder,
+        decompile_bytecode,
+        deserializer::{deserialize, function::TypeInfo},
+        serializer::serialize,
+        Fallback, FunctionStatus, Limits,
+    };
+
+    fn function(instructions: Vec<Instruction>) -> BytecodeFunction {
+        BytecodeFunction {
+            max_stack_size: u8::MAX,
+            num_parameters: 0,
+            num_upvalues: 1,
+            is_vararg: true,
+            flags: 0,
+            type_info: TypeInfo::default(),
+            instructions,
+            constants: vec![BytecodeConstant::String(1), BytecodeConstant::Closure(1)],
+            functions: vec![1],
+            line_defined: 0,
+            function_name: 0,
+            line_gap_log2: None,
+            line_info_delta: None,
+            abs_line_info_delta: None,
+            has_debug_info: false,
+            local_variables: Vec::new(),
+            upvalue_names: Vec::new(),
+        }
+    }
+
+    fn bc(op_code: OpCode, a: u8, b: u8, c: u8) -> Instruction {
+        Instruction::BC {
+            op_code,
+            a,
+            b,
+            c,
+            aux: 0,
+        }
+    }
+
+    fn ad(op_code: OpCode, a: u8, d: i16) -> Instruction {
+        Instruction::AD {
+            op_code,
+            a,
+            d,
+            aux: 0,
+        }
+    }
+
+    // `op_code` with operands that are valid for it, and the instructions it needs around it.
+    // the functions have one upvalue, a string constant and a closure constant with one upvalue
+    fn valid_use(op_code: OpCode) -> Vec<Instruction> {
+        let nop = bc(OpCode::LOP_NOP, 0, 0, 0);
+        // captures the upvalue of the closure before it
+        let capture = bc(OpCode::LOP_CAPTURE, 0, 0, 0);
+        // a loop over an empty body, prepare jumps to the loop instruction and it jumps back
+        let generic_for = |prepare| {
+            vec![
+                ad(prepare, 0, 1),
+                nop,
+                Instruction::AD {
+                    op_code: OpCode::LOP_FORGLOOP,
+                    a: 0,
+                    d: -2,
+                    aux: 1,
+                },
+            ]
+        };
+        match op_code {
+            OpCode::LOP_CALL => vec![bc(op_code, 0, 1, 1)],
+            // calls the method it looks up
+            OpCode::LOP_NAMECALL => vec![bc(op_code, 0, 1, 0), bc(OpCode::LOP_CALL, 0, 2, 1)],
+            OpCode::LOP_RETURN => vec![bc(op_code, 0, 2, 0)],
+            OpCode::LOP_CONCAT => vec![bc(op_code, 0, 1, 2)],
+            OpCode::LOP_SETLIST => vec![Instruction::BC {
+                op_code,
+                a: 0,
+                b: 1,
+                c: 2,
+                aux: 1,
+            }],
+            OpCode::LOP_NEWCLOSURE => vec![ad(op_code, 0, 0), capture],
+            OpCode::LOP_DUPCLOSURE => vec![ad(op_code, 0, 1), capture],
+            OpCode::LOP_CAPTURE => vec![ad(OpCode::LOP_NEWCLOSURE, 0, 0), capture],
+            // prepare skips past the loop instruction, which jumps back to the body
+            OpCode::LOP_FORNPREP | OpCode::LOP_FORNLOOP => vec![
+                ad(OpCode::LOP_FORNPREP, 0, 2),
+                nop,
+                ad(OpCode::LOP_FORNLOOP, 0, -2),
+            ],
+            OpCode::LOP_FORGPREP | OpCode::LOP_FORGPREP_INEXT | OpCode::LOP_FORGPREP_NEXT => {
+                generic_for(op_code)
+            }
+            OpCode::LOP_FORGLOOP => generic_for(OpCode::LOP_FORGPREP),
+            _ => vec![Instruction::parse(op_code as u32, &MultiplicativeDecoder(1)).unwrap()],
+        }
+    }
+
+    // lifts a function made of `instructions` followed by a return, with a nop for each aux
+    // word, returning the panic message if lifting panics
+    fn lift(instructions: Vec<Instruction>) -> Option<String> {
+        let r#return = bc(OpCode::LOP_RETURN, 0, 1, 0);
+        let mut function_instructions = Vec::new();
+        for instruction in instructions {
+            function_instructions.push(instruction);
+            if instruction.op_code().has_aux() {
+                function_instructions.push(bc(OpCode::LOP_NOP, 0, 0, 0));
+            }
+        }
+        function_instructions.push(r#return);
+        let chunk = Chunk {
+            version: 6,
+            types_version: 3,
+            string_table: vec![b"x".to_vec()],
+            userdata_types: Vec::new(),
+            functions: vec![function(function_instructions), function(vec![r#return])],
+            missing_functions: Default::default(),
+            main: 0,
+        };
+        panic::catch_unwind(|| <Lifter as decompiler::Lifter>::lift(&chunk, 0))
+            .err()
+            .map(|e| match e.downcast::<String>() {
+                Ok(v) => *v,
+                Err(e) => e
+                    .downcast::<&str>()
+                    .map_or_else(|_| String::new(), |v| v.to_string()),
+            })
+    }
+
+    #[test]
+    fn every_op_code_is_lifted() {
+        // silence the panics, the hook is restored before anything is asserted
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        let messages = (0..OpCode::LOP__COUNT as u8)
+            .map(|op_code| {
+                let op_code = OpCode::try_from(op_code).unwrap();
+                (op_code, lift(valid_use(op_code)))
+            })
+            .collect::<Vec<_>>();
+        panic::set_hook(hook);
+        for (op_code, message) in messages {
+            assert_eq!(message, None, "{:?} is not lifted", op_code);
+        }
+    }
+
+    #[test]
+    fn names_locals_parameters_and_upvalues() {
+        let source = decompile_bytecode(
+            include_bytes!("../fixtures/control_flow.bin"),
+            &MultiplicativeDecoder(1),
+            &Limits::default(),
+        )
+        .unwrap()
+        .source;
+        for expected in [
+            // a parameter of a variadic function
+            "function(name, ...)",
+            "local message = \"hello \" .. name",
+            "message = message:sub(1, 10)",
+            "for i = 1, 3 do",
+            "function(player)",
+            "-- upvalues: (ref) counters, (copy) player",
+        ] {
+            assert!(
+                source.contains(expected),
+                "missing {:?} in:\n{}",
+                expected,
+                source
+            );
+        }
+    }
+
+    // the children of native.bin are `@native`, its main function isn't
+    #[test]
+    fn lifts_native_attributes() {
+        let decoder = MultiplicativeDecoder(1);
+        let decompile = |bytecode: &[u8]| {
+            decompile_bytecode(bytecode, &decoder, &Limits::default())
+                .unwrap()
+                .source
+        };
+        let bytecode = include_bytes!("../fixtures/native.bin");
+        let source = decompile(bytecode);
+        assert!(!source.starts_with("--!native"), "{}", source);
+        for expected in ["@native function t.h(p1)", "return @native function(p2)"] {
+            assert!(
+                source.contains(expected),
+                "missing {:?} in:\n{}",
+                expected,
+                source
+            );
+        }
+
+        let mut chunk = deserialize(bytecode, &decoder).unwrap();
+        chunk.functions[chunk.main].flags |= bytecode_function::NATIVE_MODULE;
+        let source = decompile(&serialize(&chunk, &decoder).unwrap());
+        assert!(source.starts_with("--!native\nt = {}\n"), "{}", source);
+    }
+
+    // typed.bin declares a userdata type `f`, and a function with a parameter of it, a table,
+    // a string and a function
+    #[test]
+    fn lifts_parameter_types() {
+        let source = decompile_bytecode(
+            include_bytes!("../fixtures/typed.bin"),
+            &MultiplicativeDecoder(1),
+            &Limits::default(),
+        )
+        .unwrap()
+        .source;
+        assert!(
+            source.contains(
+                "return function(p1: f, p2: {any}?, p3: string, p4: ((...any) -> (...any))?)"
+            ),
+            "{}",
+            source
+        );
+    }
+
+    // debug names that can't be written in source are replaced with generated ones
+    #[test]
+    fn ignores_invalid_debug_names() {
+        let decoder = MultiplicativeDecoder(1);
+        let mut chunk =
+            deserialize(include_bytes!("../fixtures/control_flow.bin"), &decoder).unwrap();
+        for string in &mut chunk.string_table {
+            match &string[..] {
+                b"message" => *string = b"end".to_vec(),
+                b"name" => string.clear(),
+                _ => {}
+            }
+        }
+        let bytecode = serialize(&chunk, &decoder).unwrap();
+        let source = decompile_bytecode(&bytecode, &decoder, &Limits::default())
+            .unwrap()
+            .source;
+        for invalid in ["local end", "end ..", "(, ...)"] {
+            assert!(!source.contains(invalid), "{:?} in:\n{}", invalid, source);
+        }
+    }
+
+    // functions out of time are left partly structured, and reported main first
+    #[test]
+    fn reports_timed_out_functions() {
+        let limits = Limits {
+            function_timeout: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        let output = decompile_bytecode(
+            include_bytes!("../fixtures/control_flow.bin"),
+            &MultiplicativeDecoder(1),
+            &limits,
+        )
+        .unwrap();
+        let reports = output
+            .functions
+            .iter()
+            .map(|f| (f.id, f.name.as_deref(), &f.status, f.fallback))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reports,
+            [
+                (3, None, &FunctionStatus::TimedOut, None),
+                (2, Some("count"), &FunctionStatus::TimedOut, None),
+                (1, None, &FunctionStatus::TimedOut, None),
+                (0, Some("greet"), &FunctionStatus::TimedOut, None),
+            ]
+        );
+        assert!(
+            output.source.contains("local function greet(name, ...)"),
+            "{}",
+            output.source
+        );
+    }
+
+    // the main function of control_flow.bin panics while being structured, so it is emitted
+    // unstructured below the panic message and the rest of the chunk is unaffected
+    #[test]
+    fn falls_back_for_panicking_functions() {
+        let output = decompile_bytecode(
+            include_bytes!("../fixtures/control_flow.bin"),
+            &MultiplicativeDecoder(1),
+            &Limits::default(),
+        )
+        .unwrap();
+        let main = &output.functions[0];
+        assert_eq!(main.id, 3);
+        assert!(
+            matches!(&main.status, FunctionStatus::Panicked { message, .. } if !message.is_empty())
+        );
+        assert_eq!(main.fallback, Some(Fallback::Unstructured));
+        for function in &output.functions[1..] {
+            assert_eq!(function.status, FunctionStatus::Ok);
+            assert_eq!(function.fallback, None);
+        }
+        assert_eq!(output.failed_functions().count(), 1);
+        assert!(
+            output
+                .source
+                .starts_with("-- failed to decompile\n-- function 3 panicked at '"),
+            "{}",
+            output.source
+        );
+        for expected in ["::l0::", "v2 = function(name, ...)"] {
+            assert!(
+                output.source.contains(expected),
+                "missing {:?} in:\n{}",
+                expected,
+                output.source
+            );
+        }
+    }
+
+    // nothing is lifted once the chunk is out of time
+    #[test]
+    fn disassembles_after_chunk_deadline() {
+        let limits = Limits {
+            chunk_timeout: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        let output = decompile_bytecode(
+            include_bytes!("../fixtures/control_flow.bin"),
+            &MultiplicativeDecoder(1),
+            &limits,
+        )
+        .unwrap();
+        for function in &output.functions {
+            assert_eq!(function.status, FunctionStatus::TimedOut);
+            assert_eq!(function.fallback, Some(Fallback::Disassembly));
+        }
+        assert!(
+            output.source.contains("timed out before it was lifted"),
+            "{}",
+            output.source
+        );
+    }
+}
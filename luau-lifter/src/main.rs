@@ -1,10 +1,651 @@
-fn main() {
-    let file_name = std::env::args().nth(1).expect("expected exactly one file");
-    let key = std::env::args()
-        .nth(2)
-        .or_else(|| None)
-        .map(|s| if s == "-e" { 203 } else { panic!() })
-        .unwrap_or(1);
-    let bytecode = std::fs::read(file_name).expect("failed to read file");
-    println!("{}", luau_lifter::decompile_bytecode(&bytecode, key));
+use std::{
+    collections::{HashMap, HashSet},
+    fs, panic,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context};
+use clap::{ArgGroup, Parser, ValueEnum};
+use itertools::Itertools;
+use luau_lifter::{
+    decoder::{self, MultiplicativeDecoder, OpcodeDecoder, PermutationDecoder, XorDecoder},
+    decompile_bytecode, decompile_bytecode_lenient, disassemble_bytecode,
+    disassemble_bytecode_lenient,
+    place::{self, ScriptContents},
+    FunctionReport, FunctionStatus, Limits, LineMode,
+};
+use rayon::prelude::*;
+use serde_json::json;
+use walkdir::WalkDir;
+
+#[derive(Parser, Debug)]
+#[clap(about, version, author)]
+#[clap(group = ArgGroup::new("decoder").multiple(false))]
+struct Args {
+    /// Files or directories to decompile, each holding bytecode either raw, in a Roblox `RSB1`
+    /// container, or encoded as base64 or hex. The scripts in Roblox model and place files
+    /// (`.rbxm`, `.rbxl`, `.rbxmx`, `.rbxlx`) are written to a directory named after the file
+    #[clap(required = true)]
+    paths: Vec<PathBuf>,
+    /// Number of threads to use (0 = automatic)
+    #[clap(short, long, default_value_t = 0)]
+    threads: usize,
+    /// op = op * key % 256
+    /// For Roblox client bytecode, use 203
+    #[clap(short, long, default_value_t = 1, group = "decoder")]
+    key: u8,
+    /// op = op ^ key
+    #[clap(long, group = "decoder")]
+    xor: Option<u8>,
+    /// File with one `<opcode> <encoded value>` pair per line, e.g. `CALL 0x9f`
+    #[clap(long, group = "decoder")]
+    permutation: Option<PathBuf>,
+    /// Try every multiplicative and xor key on each file and use the one that decodes cleanly
+    #[clap(long, group = "decoder")]
+    detect: bool,
+    /// Decompile files in subdirectories of the given directories
+    #[clap(short, long)]
+    recursive: bool,
+    /// Directory to write the decompiled files to instead of next to each input
+    #[clap(short, long)]
+    out_dir: Option<PathBuf>,
+    #[clap(short, long)]
+    verbose: bool,
+    /// Maximum number of structuring passes over each function
+    #[clap(long)]
+    max_iterations: Option<usize>,
+    /// Seconds each function may spend being structured
+    #[clap(long, value_parser = parse_seconds)]
+    function_timeout: Option<Duration>,
+    /// Seconds each file may spend being structured
+    #[clap(long, value_parser = parse_seconds)]
+    chunk_timeout: Option<Duration>,
+    /// Use the bytecode's line info to annotate or lay out statements
+    #[clap(long, value_enum)]
+    line_info: Option<LineInfo>,
+    /// Write a `.luau.map.json` next to each output mapping statements back to instructions
+    #[clap(long)]
+    source_map: bool,
+    /// Write an instruction listing of each file to a `.disasm` file instead of decompiling it
+    #[clap(long)]
+    disasm: bool,
+    /// Keep the functions of truncated or corrupt files that can be read and emit placeholders
+    /// for the rest
+    #[clap(long)]
+    lenient: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LineInfo {
+    /// Precede statements with `-- line N` comments
+    Annotate,
+    /// Pad with blank lines so statements land on their original line
+    Preserve,
+}
+
+struct Input {
+    path: PathBuf,
+    // path of the output relative to the output directory
+    relative: PathBuf,
+}
+
+// seconds as a duration, rejecting the negative, nan and overflowing values that
+// `Duration::from_secs_f64` would panic on
+fn parse_seconds(seconds: &str) -> Result<Duration, String> {
+    let seconds = seconds.parse::<f64>().map_err(|e| e.to_string())?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+fn collect_inputs(args: &Args) -> anyhow::Result<Vec<Input>> {
+    let mut inputs = Vec::new();
+    for path in &args.paths {
+        if path.is_dir() {
+            let max_depth = if args.recursive { usize::MAX } else { 1 };
+            for entry in WalkDir::new(path).max_depth(max_depth).sort_by_file_name() {
+                let entry = entry?;
+                // skip our own output so running twice over a directory doesn't decompile it
+                if !entry.file_type().is_file()
+                    || entry
+                        .path()
+                        .extension()
+                        .is_some_and(|e| e == "luau" || e == "disasm")
+                    || [".luau.map.json", ".manifest.json"]
+                        .iter()
+                        .any(|s| entry.file_name().to_string_lossy().ends_with(s))
+                {
+                    continue;
+                }
+                inputs.push(Input {
+                    path: entry.path().to_path_buf(),
+                    relative: entry.path().strip_prefix(path)?.to_path_buf(),
+                });
+            }
+        } else if path.is_file() {
+            inputs.push(Input {
+                path: path.clone(),
+                relative: PathBuf::from(path.file_name().unwrap()),
+            });
+        } else {
+            return Err(anyhow!("{} does not exist", path.display()));
+        }
+    }
+    Ok(inputs)
+}
+
+fn output_path(input: &Input, out_dir: Option<&Path>, extension: &str) -> PathBuf {
+    match out_dir {
+        Some(out_dir) => out_dir.join(&input.relative),
+        None => input.path.clone(),
+    }
+    .with_extension(extension)
+}
+
+// the output of each input, and the earlier input that already writes to it. `a.bin` and `a.txt`
+// both become `a.luau`, and so do files of the same name in different directories given
+// `--out-dir`
+fn output_paths<'a>(
+    inputs: &'a [Input],
+    out_dir: Option<&Path>,
+    extension: &str,
+) -> Vec<(PathBuf, Option<&'a Path>)> {
+    let mut used = HashMap::new();
+    inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            // the scripts in place files go in a directory named after the file
+            let extension = if is_place(&input.path) { "" } else { extension };
+            let output = output_path(input, out_dir, extension);
+            // file systems may ignore case
+            let owner = *used
+                .entry(output.to_string_lossy().to_lowercase())
+                .or_insert(index);
+            let collision = (owner != index).then(|| inputs[owner].path.as_path());
+            (output, collision)
+        })
+        .collect()
+}
+
+// `None` if a decoder was given
+fn detect_decoder(
+    bytecode: &[u8],
+    decoder: Option<&dyn OpcodeDecoder>,
+) -> anyhow::Result<Option<Box<dyn OpcodeDecoder>>> {
+    match decoder {
+        Some(_) => Ok(None),
+        None => decoder::detect(bytecode)
+            .map(Some)
+            .ok_or_else(|| anyhow!("could not detect the opcode encoding")),
+    }
+}
+
+// how each input is processed, shared by loose files and the scripts in place files
+struct Options<'a> {
+    // detected from each file's bytecode if `None`
+    decoder: Option<&'a dyn OpcodeDecoder>,
+    limits: Limits,
+    line_mode: LineMode,
+    source_map: bool,
+    disasm: bool,
+    lenient: bool,
+}
+
+impl Options<'_> {
+    fn extension(&self) -> &'static str {
+        if self.disasm {
+            "disasm"
+        } else {
+            "luau"
+        }
+    }
+}
+
+fn disassemble(bytecode: &[u8], output: &Path, options: &Options) -> anyhow::Result<()> {
+    let detected = detect_decoder(bytecode, options.decoder)?;
+    let decoder = options.decoder.or(detected.as_deref()).unwrap();
+    let listing = if options.lenient {
+        disassemble_bytecode_lenient(bytecode, decoder)?
+    } else {
+        disassemble_bytecode(bytecode, decoder)?
+    };
+    write(output, listing)
+}
+
+// returns the functions that failed to decompile
+fn decompile(
+    bytecode: &[u8],
+    output: &Path,
+    options: &Options,
+) -> anyhow::Result<Vec<FunctionReport>> {
+    let detected = detect_decoder(bytecode, options.decoder)?;
+    let decoder = options.decoder.or(detected.as_deref()).unwrap();
+    let limits = &options.limits;
+    let decompiled = panic::catch_unwind(panic::AssertUnwindSafe(|| -> anyhow::Result<_> {
+        if options.lenient {
+            Ok(decompile_bytecode_lenient(bytecode, decoder, limits)?)
+        } else {
+            Ok(decompile_bytecode(bytecode, decoder, limits)?)
+        }
+    }))
+    .map_err(|e| {
+        let message = match e.downcast::<String>() {
+            Ok(v) => *v,
+            Err(e) => match e.downcast::<&str>() {
+                Ok(v) => v.to_string(),
+                _ => "Unknown Source of Error".to_owned(),
+            },
+        };
+        anyhow!("panicked: {}", message)
+    })??;
+    let rendered;
+    let source = match (options.line_mode, options.source_map) {
+        (LineMode::Compact, false) => &decompiled.source,
+        (line_mode, false) => {
+            rendered = decompiled.render(line_mode);
+            &rendered
+        }
+        (line_mode, true) => {
+            let source_map;
+            (rendered, source_map) = decompiled.render_with_source_map(line_mode);
+            write(
+                &output.with_extension("luau.map.json"),
+                source_map.to_json(),
+            )?;
+            &rendered
+        }
+    };
+    write(output, source)?;
+    Ok(decompiled.failed_functions().cloned().collect())
+}
+
+fn write(path: &Path, contents: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
+
+// why a file only partly decompiled, `None` if nothing failed
+fn failure_reason(failed_functions: &[FunctionReport]) -> Option<String> {
+    let with_status = |status: fn(&FunctionStatus) -> bool| {
+        failed_functions
+            .iter()
+            .filter(|f| status(&f.status))
+            .collect::<Vec<_>>()
+    };
+    let reasons = [
+        (
+            with_status(|s| matches!(s, FunctionStatus::Panicked { .. })),
+            "failed to decompile",
+        ),
+        (with_status(|s| *s == FunctionStatus::TimedOut), "timed out"),
+        (with_status(|s| *s == FunctionStatus::Missing), "missing"),
+    ]
+    .into_iter()
+    .filter(|(functions, _)| !functions.is_empty())
+    .map(|(functions, reason)| {
+        format!(
+            "functions {} {}",
+            functions.iter().map(|f| f.id).join(", "),
+            reason
+        )
+    })
+    .join(", ");
+    (!reasons.is_empty()).then_some(reasons)
+}
+
+// writes the bytecode to `output` decompiled or disassembled, returns why it partly failed
+fn process(bytecode: &[u8], output: &Path, options: &Options) -> anyhow::Result<Option<String>> {
+    if options.disasm {
+        disassemble(bytecode, output, options)?;
+        Ok(None)
+    } else {
+        Ok(failure_reason(&decompile(bytecode, output, options)?))
+    }
+}
+
+fn process_file(input: &Path, output: &Path, options: &Options) -> anyhow::Result<Option<String>> {
+    // an explicit `.luau` input, or `--out-dir` pointing back at the inputs
+    if output == input
+        || fs::canonicalize(output)
+            .is_ok_and(|output| fs::canonicalize(input).is_ok_and(|input| input == output))
+    {
+        return Err(anyhow!(
+            "the output would overwrite the input, use --out-dir to write it elsewhere"
+        ));
+    }
+    let bytecode =
+        fs::read(input).with_context(|| format!("failed to read {}", input.display()))?;
+    process(&bytecode, output, options)
+}
+
+fn is_place(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| ["rbxm", "rbxl", "rbxmx", "rbxlx"].iter().any(|p| e == *p))
+}
+
+// a file name that's valid everywhere, instance names can be anything
+fn sanitize(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    match name.trim() {
+        "" | "." | ".." => "_".to_string(),
+        _ => name,
+    }
+}
+
+// writes each script into a directory tree mirroring the instance hierarchy under `output`,
+// and a manifest next to it mapping instance paths to the files
+fn process_place(input: &Path, output: &Path, options: &Options) -> anyhow::Result<Option<String>> {
+    let file = fs::read(input).with_context(|| format!("failed to read {}", input.display()))?;
+    let scripts = place::extract_scripts(&file)?;
+    let manifest_path = output.with_extension("manifest.json");
+    let root = output.file_name().map(PathBuf::from).unwrap_or_default();
+
+    let mut used = HashSet::new();
+    let mut entries = Vec::new();
+    let mut failures = Vec::new();
+    for script in &scripts {
+        let mut relative = script.path.iter().map(|n| sanitize(n)).collect::<PathBuf>();
+        let name = relative.file_name().unwrap().to_string_lossy().into_owned();
+        // siblings can share a name, and file systems may ignore case
+        let mut index = 1;
+        loop {
+            let extension = match script.contents {
+                ScriptContents::Source(_) => "luau",
+                ScriptContents::Bytecode(_) => options.extension(),
+            };
+            let file_name = match index {
+                1 => format!("{}.{}", name, extension),
+                _ => format!("{} ({}).{}", name, index, extension),
+            };
+            relative.set_file_name(file_name);
+            if used.insert(relative.to_string_lossy().to_lowercase()) {
+                break;
+            }
+            index += 1;
+        }
+
+        let (status, error) = match &script.contents {
+            ScriptContents::Source(source) => {
+                write(&output.join(&relative), source)?;
+                ("source", None)
+            }
+            ScriptContents::Bytecode(bytecode) => {
+                match process(bytecode, &output.join(&relative), options) {
+                    Ok(None) => ("ok", None),
+                    Ok(Some(reason)) => ("partial", Some(reason)),
+                    Err(err) => ("failed", Some(format!("{:#}", err))),
+                }
+            }
+        };
+        let mut entry = json!({
+            "path": script.full_name(),
+            "class": script.class,
+            "file": root.join(&relative).to_string_lossy().replace('\\', "/"),
+            "status": status,
+        });
+        if let Some(error) = error {
+            failures.push(format!("{}: {}", script.full_name(), error));
+            entry["error"] = error.into();
+        }
+        entries.push(entry);
+    }
+    write(
+        &manifest_path,
+        format!("{}\n", json!({ "scripts": entries })),
+    )?;
+    Ok((!failures.is_empty()).then(|| failures.join("; ")))
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    #[cfg(feature = "dhat-heap")]
+    let _profiler = dhat::Profiler::new_heap();
+
+    let args = Args::parse();
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build_global()?;
+
+    let limits = Limits {
+        max_iterations: args.max_iterations,
+        function_timeout: args.function_timeout,
+        chunk_timeout: args.chunk_timeout,
+    };
+    let line_mode = match args.line_info {
+        None => LineMode::Compact,
+        Some(LineInfo::Annotate) => LineMode::Annotate,
+        Some(LineInfo::Preserve) => LineMode::Preserve,
+    };
+    let decoder: Option<Box<dyn OpcodeDecoder>> = if args.detect {
+        None
+    } else if let Some(path) = &args.permutation {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let permutation = PermutationDecoder::parse(&source)
+            .with_context(|| format!("invalid permutation {}", path.display()))?;
+        Some(Box::new(permutation))
+    } else if let Some(key) = args.xor {
+        Some(Box::new(XorDecoder(key)))
+    } else {
+        Some(Box::new(MultiplicativeDecoder(args.key)))
+    };
+    let options = Options {
+        decoder: decoder.as_deref(),
+        limits,
+        line_mode,
+        source_map: args.source_map,
+        disasm: args.disasm,
+        lenient: args.lenient,
+    };
+    let inputs = collect_inputs(&args)?;
+    let start = Instant::now();
+    let results = inputs
+        .par_iter()
+        .zip(output_paths(
+            &inputs,
+            args.out_dir.as_deref(),
+            options.extension(),
+        ))
+        .map(|(input, (output, collision))| {
+            let start = Instant::now();
+            let result = if let Some(other) = collision {
+                Err(anyhow!(
+                    "{} is already written for {}",
+                    output.display(),
+                    other.display()
+                ))
+            } else if is_place(&input.path) {
+                process_place(&input.path, &output, &options)
+            } else {
+                process_file(&input.path, &output, &options)
+            };
+            (output, result, start.elapsed())
+        })
+        .collect::<Vec<_>>();
+
+    let mut failed = 0;
+    for (input, (output, result, elapsed)) in inputs.iter().zip(results) {
+        let elapsed = if args.verbose {
+            format!(" ({:?})", elapsed)
+        } else {
+            String::new()
+        };
+        match result {
+            Ok(None) => println!(
+                "ok     {} -> {}{}",
+                input.path.display(),
+                output.display(),
+                elapsed
+            ),
+            Ok(Some(reason)) => {
+                failed += 1;
+                println!(
+                    "failed {} -> {}: {}{}",
+                    input.path.display(),
+                    output.display(),
+                    reason,
+                    elapsed
+                );
+            }
+            Err(err) => {
+                failed += 1;
+                println!("failed {}: {:#}{}", input.path.display(), err, elapsed);
+            }
+        }
+    }
+    println!(
+        "{} {}/{} files in {:?}",
+        if args.disasm {
+            "disassembled"
+        } else {
+            "decompiled"
+        },
+        inputs.len() - failed,
+        inputs.len(),
+        start.elapsed()
+    );
+
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::prelude::*;
+
+    use super::*;
+
+    // a fresh directory for each test, cleared in case an earlier run left it behind
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("luau-lifter-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn touch(path: PathBuf) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, []).unwrap();
+    }
+
+    // the file name of each output and of the input it collides with
+    fn collisions(args: &[&str]) -> Vec<(String, Option<String>)> {
+        let args = Args::parse_from(["luau-lifter"].iter().chain(args));
+        let inputs = collect_inputs(&args).unwrap();
+        let file_name = |path: &Path| path.file_name().unwrap().to_string_lossy().into_owned();
+        output_paths(&inputs, args.out_dir.as_deref(), "luau")
+            .into_iter()
+            .map(|(output, collision)| (file_name(&output), collision.map(file_name)))
+            .collect()
+    }
+
+    #[test]
+    fn reports_output_collisions() {
+        let dir = temp_dir("collisions");
+        for file in ["A.dat", "a.bin", "a.txt", "b.luau", "x/c.bin", "y/c.bin"] {
+            touch(dir.join(file));
+        }
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let (x, y, out) = (path("x"), path("y"), path("out"));
+
+        // file systems may ignore case, and our own output isn't an input
+        assert_eq!(
+            collisions(&[&path("")]),
+            [
+                ("A.luau".to_string(), None),
+                ("a.luau".to_string(), Some("A.dat".to_string())),
+                ("a.luau".to_string(), Some("A.dat".to_string())),
+            ]
+        );
+        // the same name in different directories only collides in the output directory
+        assert_eq!(
+            collisions(&[&x, &y]),
+            [("c.luau".to_string(), None), ("c.luau".to_string(), None)]
+        );
+        assert_eq!(
+            collisions(&["-o", &out, &x, &y]),
+            [
+                ("c.luau".to_string(), None),
+                ("c.luau".to_string(), Some("c.bin".to_string())),
+            ]
+        );
+        // recursing keeps their directories in the output
+        assert_eq!(
+            collisions(&["-r", "-o", &out, &path("")])[3..],
+            [("c.luau".to_string(), None), ("c.luau".to_string(), None)]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn writes_manifest_and_source_maps() {
+        let dir = temp_dir("manifest");
+        let place = dir.join("model.rbxmx");
+        fs::write(
+            &place,
+            format!(
+                r#"<roblox version="4">
+                    <Item class="Folder" referent="RBX0">
+                        <Properties><string name="Name">say "hi"\</string></Properties>
+                        <Item class="ModuleScript" referent="RBX1">
+                            <Properties>
+                                <string name="Name">Source</string>
+                                <ProtectedString name="Source">return {{}}</ProtectedString>
+                            </Properties>
+                        </Item>
+                        <Item class="LocalScript" referent="RBX2">
+                            <Properties>
+                                <string name="Name">Bytecode</string>
+                                <BinaryString name="Source">{}</BinaryString>
+                            </Properties>
+                        </Item>
+                    </Item>
+                </roblox>"#,
+                BASE64_STANDARD.encode(include_bytes!("../fixtures/control_flow.bin"))
+            ),
+        )
+        .unwrap();
+        let options = Options {
+            decoder: Some(&MultiplicativeDecoder(1)),
+            limits: Limits::default(),
+            line_mode: LineMode::Compact,
+            source_map: true,
+            disasm: false,
+            lenient: false,
+        };
+        let output = dir.join("model");
+        // main fails to structure and falls back to the unstructured output
+        let failure = process_place(&place, &output, &options).unwrap();
+        assert!(failure.is_some_and(|f| f.starts_with("say \"hi\"\\.Bytecode: ")));
+
+        let read = |path: PathBuf| {
+            serde_json::from_str::<serde_json::Value>(&fs::read_to_string(path).unwrap()).unwrap()
+        };
+        let manifest = read(dir.join("model.manifest.json"));
+        let scripts = manifest["scripts"].as_array().unwrap();
+        assert_eq!(scripts[0]["path"], "say \"hi\"\\.Source");
+        assert_eq!(scripts[0]["class"], "ModuleScript");
+        assert_eq!(scripts[0]["file"], "model/say _hi__/Source.luau");
+        assert_eq!(scripts[0]["status"], "source");
+        assert_eq!(scripts[1]["file"], "model/say _hi__/Bytecode.luau");
+        assert_eq!(scripts[1]["status"], "partial");
+        assert!(scripts[1]["error"].is_string());
+
+        let source_map = read(output.join("say _hi__/Bytecode.luau.map.json"));
+        assert_eq!(source_map["version"], 1);
+        assert!(!source_map["mappings"].as_array().unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
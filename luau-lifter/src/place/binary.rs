@@ -0,0 +1,224 @@
+use nom::{
+    bytes::complete::{tag, take},
+    number::complete::{le_i32, le_u16, le_u32, le_u8},
+    IResult,
+};
+use rustc_hash::FxHashMap;
+use ruzstd::decoding::FrameDecoder;
+
+use super::{Instance, PlaceError};
+
+pub(super) const MAGIC: &[u8] = b"<roblox!\x89\xff\r\n\x1a\n";
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+const STRING_TYPE: u8 = 0x01;
+// chunk sizes come from the input, so don't trust them with more than this
+const MAX_CHUNK_SIZE: usize = 1 << 28;
+
+fn parse_string(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (input, length) = le_u32(input)?;
+    take(length)(input)
+}
+
+// referents are stored zigzag encoded, as deltas from the previous referent, and with the
+// bytes of each value interleaved. the most significant bytes of every value come first
+fn parse_referents(input: &[u8], count: usize) -> IResult<&[u8], Vec<i32>> {
+    let (input, bytes) = take(count.saturating_mul(4))(input)?;
+    let mut last = 0i32;
+    let referents = (0..count)
+        .map(|index| {
+            let value = u32::from_be_bytes(std::array::from_fn(|byte| bytes[byte * count + index]));
+            let delta = (value >> 1) as i32 ^ -((value & 1) as i32);
+            last = last.wrapping_add(delta);
+            last
+        })
+        .collect();
+    Ok((input, referents))
+}
+
+struct Chunk<'a> {
+    name: &'a [u8],
+    compressed_size: usize,
+    size: usize,
+    data: &'a [u8],
+}
+
+fn parse_chunk(input: &[u8]) -> IResult<&[u8], Chunk> {
+    let (input, name) = take(4usize)(input)?;
+    let (input, compressed_size) = le_u32(input)?;
+    let (input, size) = le_u32(input)?;
+    let (input, _) = take(4usize)(input)?;
+    // uncompressed chunks have a compressed size of 0
+    let stored_size = if compressed_size == 0 {
+        size
+    } else {
+        compressed_size
+    };
+    let (input, data) = take(stored_size)(input)?;
+    Ok((
+        input,
+        Chunk {
+            name,
+            compressed_size: compressed_size as usize,
+            size: size as usize,
+            data,
+        },
+    ))
+}
+
+impl Chunk<'_> {
+    fn name(&self) -> String {
+        String::from_utf8_lossy(self.name)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+
+    // chunks are compressed with lz4, or zstd in newer files
+    fn decompress(&self) -> Result<Vec<u8>, PlaceError> {
+        if self.compressed_size == 0 {
+            return Ok(self.data.to_vec());
+        }
+        if self.size > MAX_CHUNK_SIZE {
+            return Err(PlaceError::TooLarge(self.name(), self.size));
+        }
+        let error = |e: String| PlaceError::Decompress(self.name(), e);
+        if self.data.starts_with(ZSTD_MAGIC) {
+            let mut output = vec![0; self.size];
+            let size = FrameDecoder::new()
+                .decode_all(self.data, &mut output)
+                .map_err(|e| error(e.to_string()))?;
+            output.truncate(size);
+            Ok(output)
+        } else {
+            lz4_flex::block::decompress(self.data, self.size).map_err(|e| error(e.to_string()))
+        }
+    }
+}
+
+#[derive(Default)]
+struct Parser {
+    instances: Vec<Instance>,
+    referents: FxHashMap<i32, usize>,
+    // the instances of each class, in the order their properties are stored
+    classes: FxHashMap<u32, Vec<usize>>,
+}
+
+impl Parser {
+    fn instance(&self, referent: i32) -> Result<usize, PlaceError> {
+        self.referents
+            .get(&referent)
+            .copied()
+            .ok_or(PlaceError::InvalidReferent(referent))
+    }
+
+    fn parse_inst<'a>(&mut self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        let (input, class_id) = le_u32(input)?;
+        let (input, class) = parse_string(input)?;
+        let (input, _is_service) = le_u8(input)?;
+        let (input, count) = le_u32(input)?;
+        let (input, referents) = parse_referents(input, count as usize)?;
+        let class = String::from_utf8_lossy(class).into_owned();
+        let instances = self.classes.entry(class_id).or_default();
+        for referent in referents {
+            self.referents.insert(referent, self.instances.len());
+            instances.push(self.instances.len());
+            self.instances.push(Instance {
+                class: class.clone(),
+                name: String::new(),
+                source: None,
+                parent: None,
+            });
+        }
+        Ok((input, ()))
+    }
+
+    // only string properties are read, the rest are skipped without being parsed
+    fn parse_prop<'a>(&mut self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        let (input, class_id) = le_u32(input)?;
+        let (input, property) = parse_string(input)?;
+        let (mut input, r#type) = le_u8(input)?;
+        if r#type != STRING_TYPE || (property != b"Name" && property != b"Source") {
+            return Ok((input, ()));
+        }
+        let Some(instances) = self.classes.get(&class_id) else {
+            return Ok((input, ()));
+        };
+        for &instance in instances {
+            let (rest, value) = parse_string(input)?;
+            let instance = &mut self.instances[instance];
+            if property == b"Name" {
+                instance.name = String::from_utf8_lossy(value).into_owned();
+            } else {
+                instance.source = Some(value.to_vec());
+            }
+            input = rest;
+        }
+        Ok((input, ()))
+    }
+
+    fn parse_prnt<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], Vec<(i32, i32)>> {
+        let (input, _version) = le_u8(input)?;
+        let (input, count) = le_u32(input)?;
+        let (input, children) = parse_referents(input, count as usize)?;
+        let (input, parents) = parse_referents(input, count as usize)?;
+        Ok((input, children.into_iter().zip(parents).collect()))
+    }
+
+    fn parse_parents(&mut self, input: &[u8]) -> Result<(), PlaceError> {
+        let (_, parents) = self.parse_prnt(input).map_err(|e| malformed(input, e))?;
+        for (child, parent) in parents {
+            let child = self.instance(child)?;
+            // roots have a parent of -1
+            self.instances[child].parent = match parent {
+                -1 => None,
+                parent => Some(self.instance(parent)?),
+            };
+        }
+        Ok(())
+    }
+}
+
+// the offset in the failing chunk, which is all a nom error can tell us
+fn malformed(input: &[u8], err: nom::Err<nom::error::Error<&[u8]>>) -> PlaceError {
+    match err {
+        nom::Err::Error(e) | nom::Err::Failure(e) => {
+            PlaceError::Malformed(input.len() - e.input.len())
+        }
+        nom::Err::Incomplete(_) => PlaceError::Malformed(input.len()),
+    }
+}
+
+fn parse_header(input: &[u8]) -> IResult<&[u8], u16> {
+    let (input, _) = tag(MAGIC)(input)?;
+    let (input, version) = le_u16(input)?;
+    let (input, _class_count) = le_i32(input)?;
+    let (input, _instance_count) = le_i32(input)?;
+    let (input, _) = take(8usize)(input)?;
+    Ok((input, version))
+}
+
+pub(super) fn parse(file: &[u8]) -> Result<Vec<Instance>, PlaceError> {
+    let (mut input, version) = parse_header(file).map_err(|e| malformed(file, e))?;
+    if version != 0 {
+        return Err(PlaceError::UnsupportedVersion(version));
+    }
+
+    let mut parser = Parser::default();
+    loop {
+        let (rest, chunk) = parse_chunk(input).map_err(|e| malformed(file, e))?;
+        input = rest;
+        let data = chunk.decompress()?;
+        match chunk.name {
+            b"INST" => {
+                parser.parse_inst(&data).map_err(|e| malformed(&data, e))?;
+            }
+            b"PROP" => {
+                parser.parse_prop(&data).map_err(|e| malformed(&data, e))?;
+            }
+            b"PRNT" => parser.parse_parents(&data)?,
+            b"END\0" => break,
+            // META, SSTR and anything newer
+            _ => {}
+        }
+    }
+    Ok(parser.instances)
+}
@@ -0,0 +1,240 @@
+mod binary;
+mod xml;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PlaceError {
+    #[error("not a roblox model or place file")]
+    UnknownFormat,
+    #[error("unsupported binary format version {0}")]
+    UnsupportedVersion(u16),
+    #[error("malformed binary file at offset {0}")]
+    Malformed(usize),
+    #[error("{0} chunk declares {1} bytes, more than can be decompressed")]
+    TooLarge(String, usize),
+    #[error("failed to decompress {0} chunk: {1}")]
+    Decompress(String, String),
+    #[error("invalid referent {0}")]
+    InvalidReferent(i32),
+    #[error("instance {0} is its own ancestor")]
+    Cycle(String),
+    #[error("malformed xml: {0}")]
+    Xml(String),
+}
+
+// an instance as read from either format, only with the properties we care about
+struct Instance {
+    class: String,
+    name: String,
+    source: Option<Vec<u8>>,
+    parent: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptContents {
+    Source(String),
+    Bytecode(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Script {
+    // the names of the script's ancestors and the script itself, outermost first
+    pub path: Vec<String>,
+    pub class: String,
+    pub contents: ScriptContents,
+}
+
+impl Script {
+    // the path as `GetFullName` would return it
+    pub fn full_name(&self) -> String {
+        self.path.join(".")
+    }
+}
+
+const SCRIPT_CLASSES: [&str; 3] = ["Script", "LocalScript", "ModuleScript"];
+
+// bytecode starts with its version, a control character no script starts with, and rarely
+// happens to be valid utf-8
+fn contents(source: Vec<u8>) -> ScriptContents {
+    if source.first().is_some_and(|&b| b <= 6) {
+        return ScriptContents::Bytecode(source);
+    }
+    match String::from_utf8(source) {
+        Ok(source) => ScriptContents::Source(source),
+        Err(err) => ScriptContents::Bytecode(err.into_bytes()),
+    }
+}
+
+fn path(instances: &[Instance], mut index: usize) -> Result<Vec<String>, PlaceError> {
+    let mut path = vec![instances[index].name.clone()];
+    while let Some(parent) = instances[index].parent {
+        if path.len() > instances.len() {
+            return Err(PlaceError::Cycle(instances[index].name.clone()));
+        }
+        path.push(instances[parent].name.clone());
+        index = parent;
+    }
+    path.reverse();
+    Ok(path)
+}
+
+// finds the scripts in a binary (`.rbxm`, `.rbxl`) or xml (`.rbxmx`, `.rbxlx`) file,
+// in the order they appear in it
+pub fn extract_scripts(input: &[u8]) -> Result<Vec<Script>, PlaceError> {
+    let instances = if input.starts_with(binary::MAGIC) {
+        binary::parse(input)?
+    } else if xml::is_xml(input) {
+        xml::parse(input)?
+    } else {
+        return Err(PlaceError::UnknownFormat);
+    };
+
+    let mut scripts = Vec::new();
+    for (index, instance) in instances.iter().enumerate() {
+        if SCRIPT_CLASSES.contains(&instance.class.as_str()) {
+            scripts.push(Script {
+                path: path(&instances, index)?,
+                class: instance.class.clone(),
+                contents: contents(instance.source.clone().unwrap_or_default()),
+            });
+        }
+    }
+    Ok(scripts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BYTECODE: &[u8] = include_bytes!("../../fixtures/control_flow.bin");
+
+    fn string(output: &mut Vec<u8>, string: &[u8]) {
+        output.extend_from_slice(&(string.len() as u32).to_le_bytes());
+        output.extend_from_slice(string);
+    }
+
+    fn referents(output: &mut Vec<u8>, referents: &[i32]) {
+        let mut last = 0;
+        let encoded = referents
+            .iter()
+            .map(|&r| {
+                let delta = r - last;
+                last = r;
+                ((delta << 1) ^ (delta >> 31)) as u32
+            })
+            .collect::<Vec<_>>();
+        for byte in 0..4 {
+            for value in &encoded {
+                output.push(value.to_be_bytes()[byte]);
+            }
+        }
+    }
+
+    fn chunk(output: &mut Vec<u8>, name: &[u8; 4], data: &[u8], compress: bool) {
+        output.extend_from_slice(name);
+        if compress {
+            let compressed = lz4_flex::block::compress(data);
+            output.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            output.extend_from_slice(&[0; 4]);
+            output.extend(compressed);
+        } else {
+            output.extend_from_slice(&0u32.to_le_bytes());
+            output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            output.extend_from_slice(&[0; 4]);
+            output.extend_from_slice(data);
+        }
+    }
+
+    // a folder holding a module script, with a local script under the module. each instance
+    // has a class of its own, and its class id as its referent
+    fn binary_model() -> Vec<u8> {
+        let instances: [(&str, &str, &[u8]); 3] = [
+            ("Folder", "Shared", b""),
+            ("ModuleScript", "Util", b"return {}"),
+            ("LocalScript", "Client", BYTECODE),
+        ];
+        let mut output = binary::MAGIC.to_vec();
+        output.extend_from_slice(&0u16.to_le_bytes());
+        output.extend_from_slice(&3i32.to_le_bytes());
+        output.extend_from_slice(&3i32.to_le_bytes());
+        output.extend_from_slice(&[0; 8]);
+        for (class_id, &(class, _, _)) in instances.iter().enumerate() {
+            let mut data = (class_id as u32).to_le_bytes().to_vec();
+            string(&mut data, class.as_bytes());
+            data.push(0);
+            data.extend_from_slice(&1u32.to_le_bytes());
+            referents(&mut data, &[class_id as i32]);
+            chunk(&mut output, b"INST", &data, class_id == 0);
+        }
+        for (class_id, &(_, name, source)) in instances.iter().enumerate() {
+            for (property, value) in [("Name", name.as_bytes()), ("Source", source)] {
+                // folders have no source
+                if value.is_empty() {
+                    continue;
+                }
+                let mut data = (class_id as u32).to_le_bytes().to_vec();
+                string(&mut data, property.as_bytes());
+                data.push(0x01);
+                string(&mut data, value);
+                chunk(&mut output, b"PROP", &data, true);
+            }
+        }
+        let mut data = vec![0];
+        data.extend_from_slice(&3u32.to_le_bytes());
+        referents(&mut data, &[0, 1, 2]);
+        referents(&mut data, &[-1, 0, 1]);
+        chunk(&mut output, b"PRNT", &data, false);
+        chunk(&mut output, b"END\0", b"</roblox>", false);
+        output
+    }
+
+    fn check(scripts: &[Script]) {
+        assert_eq!(scripts.len(), 2);
+        assert_eq!(scripts[0].full_name(), "Shared.Util");
+        assert_eq!(scripts[0].class, "ModuleScript");
+        assert_eq!(
+            scripts[0].contents,
+            ScriptContents::Source("return {}".into())
+        );
+        assert_eq!(scripts[1].full_name(), "Shared.Util.Client");
+        assert_eq!(scripts[1].class, "LocalScript");
+        assert_eq!(
+            scripts[1].contents,
+            ScriptContents::Bytecode(BYTECODE.into())
+        );
+    }
+
+    #[test]
+    fn extracts_from_binary() {
+        check(&extract_scripts(&binary_model()).unwrap());
+    }
+
+    #[test]
+    fn extracts_from_xml() {
+        use base64::prelude::*;
+
+        let model = format!(
+            r#"<roblox version="4">
+                <Item class="Folder" referent="RBX0">
+                    <Properties><string name="Name">Shared</string></Properties>
+                    <Item class="ModuleScript" referent="RBX1">
+                        <Properties>
+                            <string name="Name">Util</string>
+                            <ProtectedString name="Source"><![CDATA[return {{}}]]></ProtectedString>
+                        </Properties>
+                        <Item class="LocalScript" referent="RBX2">
+                            <Properties>
+                                <string name="Name">Client</string>
+                                <BinaryString name="Source">{}</BinaryString>
+                            </Properties>
+                        </Item>
+                    </Item>
+                </Item>
+            </roblox>"#,
+            BASE64_STANDARD.encode(BYTECODE)
+        );
+        check(&extract_scripts(model.as_bytes()).unwrap());
+    }
+}
@@ -0,0 +1,69 @@
+use base64::prelude::*;
+use roxmltree::{Document, Node};
+
+use super::{Instance, PlaceError};
+
+pub(super) fn is_xml(input: &[u8]) -> bool {
+    let input = input.strip_prefix(b"\xef\xbb\xbf").unwrap_or(input);
+    input.trim_ascii_start().starts_with(b"<roblox")
+}
+
+// cdata and text can be split into several nodes
+fn text(node: Node) -> String {
+    node.children().filter_map(|c| c.text()).collect()
+}
+
+fn property(node: Node) -> Option<Vec<u8>> {
+    let text = text(node);
+    if node.has_tag_name("BinaryString") {
+        let text = text
+            .bytes()
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect::<Vec<_>>();
+        BASE64_STANDARD.decode(text).ok()
+    } else {
+        Some(text.into_bytes())
+    }
+}
+
+fn parse_item(node: Node, parent: Option<usize>, instances: &mut Vec<Instance>) {
+    let index = instances.len();
+    let mut instance = Instance {
+        class: node.attribute("class").unwrap_or_default().to_string(),
+        name: String::new(),
+        source: None,
+        parent,
+    };
+    let properties = node
+        .children()
+        .filter(|c| c.has_tag_name("Properties"))
+        .flat_map(|p| p.children())
+        .filter(|p| p.is_element());
+    for property_node in properties {
+        match property_node.attribute("name") {
+            Some("Name") => instance.name = text(property_node),
+            Some("Source") => instance.source = property(property_node),
+            _ => {}
+        }
+    }
+    instances.push(instance);
+
+    for child in node.children().filter(|c| c.has_tag_name("Item")) {
+        parse_item(child, Some(index), instances);
+    }
+}
+
+pub(super) fn parse(input: &[u8]) -> Result<Vec<Instance>, PlaceError> {
+    let input = std::str::from_utf8(input).map_err(|e| PlaceError::Xml(e.to_string()))?;
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let document = Document::parse(input).map_err(|e| PlaceError::Xml(e.to_string()))?;
+    let mut instances = Vec::new();
+    for item in document
+        .root_element()
+        .children()
+        .filter(|c| c.has_tag_name("Item"))
+    {
+        parse_item(item, None, &mut instances);
+    }
+    Ok(instances)
+}
@@ -0,0 +1,283 @@
+use thiserror::Error;
+
+use crate::{
+    decoder::OpcodeDecoder,
+    deserializer::{
+        chunk::Chunk,
+        constant::Constant,
+        function::{Function, TypeInfo},
+    },
+    op_code::OpCode,
+};
+
+#[derive(Debug, Error)]
+pub enum SerializeError {
+    #[error("unsupported bytecode version {0}")]
+    UnsupportedVersion(u8),
+    #[error("no byte decodes to {0:?}")]
+    UnencodableOpCode(OpCode),
+    #[error("function {0} is missing")]
+    MissingFunction(usize),
+}
+
+// the inverse of a decoder, `None` for opcodes no byte decodes to
+struct Encoder([Option<u8>; 256]);
+
+impl Encoder {
+    fn new(decoder: &dyn OpcodeDecoder) -> Self {
+        let mut table = [None; 256];
+        // prefer the lowest byte when several decode to the same opcode
+        for encoded in (0..=u8::MAX).rev() {
+            table[decoder.decode(encoded) as usize] = Some(encoded);
+        }
+        Self(table)
+    }
+
+    fn encode(&self, op_code: OpCode) -> Result<u8, SerializeError> {
+        self.0[op_code as usize].ok_or(SerializeError::UnencodableOpCode(op_code))
+    }
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+fn write_u32(output: &mut Vec<u8>, value: u32) {
+    output.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_list<T>(output: &mut Vec<u8>, list: &[T], write: impl Fn(&mut Vec<u8>, &T)) {
+    write_varint(output, list.len());
+    for item in list {
+        write(output, item);
+    }
+}
+
+fn write_constant(output: &mut Vec<u8>, constant: &Constant) {
+    match *constant {
+        Constant::Nil => output.push(0),
+        Constant::Boolean(value) => output.extend([1, value as u8]),
+        Constant::Number(value) => {
+            output.push(2);
+            output.extend_from_slice(&value.to_le_bytes());
+        }
+        Constant::String(index) => {
+            output.push(3);
+            write_varint(output, index);
+        }
+        Constant::Import(import) => {
+            output.push(4);
+            write_u32(output, import as u32);
+        }
+        Constant::Table(ref keys) => {
+            output.push(5);
+            write_list(output, keys, |output, &key| write_varint(output, key));
+        }
+        Constant::Closure(function) => {
+            output.push(6);
+            write_varint(output, function);
+        }
+        Constant::Vector(x, y, z, w) => {
+            output.push(7);
+            for component in [x, y, z, w] {
+                output.extend_from_slice(&component.to_le_bytes());
+            }
+        }
+    }
+}
+
+fn write_type_info(output: &mut Vec<u8>, type_info: &TypeInfo, types_version: u8) {
+    let mut data = Vec::new();
+    match types_version {
+        0 | 1 => data.extend_from_slice(&type_info.function),
+        // the compiler leaves the type info empty when nothing is typed
+        _ if type_info.function.is_empty()
+            && type_info.upvalues.is_empty()
+            && type_info.locals.is_empty() => {}
+        _ => {
+            write_varint(&mut data, type_info.function.len());
+            write_varint(&mut data, type_info.upvalues.len());
+            write_varint(&mut data, type_info.locals.len());
+            data.extend_from_slice(&type_info.function);
+            data.extend_from_slice(&type_info.upvalues);
+            for local in &type_info.locals {
+                data.extend([local.r#type, local.register]);
+                write_varint(&mut data, local.start_pc);
+                write_varint(&mut data, local.length);
+            }
+        }
+    }
+    write_varint(output, data.len());
+    output.extend(data);
+}
+
+fn write_function(
+    output: &mut Vec<u8>,
+    function: &Function,
+    encoder: &Encoder,
+    types_version: u8,
+) -> Result<(), SerializeError> {
+    output.extend([
+        function.max_stack_size,
+        function.num_parameters,
+        function.num_upvalues,
+        function.is_vararg as u8,
+        function.flags,
+    ]);
+    write_type_info(output, &function.type_info, types_version);
+
+    // the nops the deserializer put in place of aux words are written as the aux words again
+    write_varint(output, function.instructions.len());
+    let mut instructions = function.instructions.iter();
+    while let Some(instruction) = instructions.next() {
+        let op_code = instruction.op_code();
+        write_u32(output, instruction.encode(encoder.encode(op_code)?));
+        if op_code.has_aux() {
+            write_u32(output, instruction.aux());
+            instructions.next();
+        }
+    }
+
+    write_list(output, &function.constants, write_constant);
+    write_list(output, &function.functions, |output, &function| {
+        write_varint(output, function)
+    });
+    write_varint(output, function.line_defined);
+    write_varint(output, function.function_name);
+
+    match (
+        function.line_gap_log2,
+        &function.line_info_delta,
+        &function.abs_line_info_delta,
+    ) {
+        (Some(line_gap_log2), Some(line_info_delta), Some(abs_line_info_delta)) => {
+            output.extend([1, line_gap_log2]);
+            output.extend_from_slice(line_info_delta);
+            for &delta in abs_line_info_delta {
+                write_u32(output, delta);
+            }
+        }
+        _ => output.push(0),
+    }
+
+    output.push(function.has_debug_info as u8);
+    if function.has_debug_info {
+        write_list(output, &function.local_variables, |output, local| {
+            write_varint(output, local.name);
+            write_varint(output, local.start_pc);
+            write_varint(output, local.end_pc);
+            output.push(local.register);
+        });
+        write_list(output, &function.upvalue_names, |output, &name| {
+            write_varint(output, name)
+        });
+    }
+    Ok(())
+}
+
+// writes the chunk back as bytecode, encoding opcodes so that `decoder` decodes them
+pub fn serialize(chunk: &Chunk, decoder: &dyn OpcodeDecoder) -> Result<Vec<u8>, SerializeError> {
+    if !(4..=6).contains(&chunk.version) {
+        return Err(SerializeError::UnsupportedVersion(chunk.version));
+    }
+    if let Some(&function_id) = chunk.missing_functions.first() {
+        return Err(SerializeError::MissingFunction(function_id));
+    }
+    let encoder = Encoder::new(decoder);
+
+    let mut output = vec![chunk.version, chunk.types_version];
+    write_list(&mut output, &chunk.string_table, |output, string| {
+        write_varint(output, string.len());
+        output.extend_from_slice(string);
+    });
+    if chunk.types_version == 3 {
+        for &(index, name) in &chunk.userdata_types {
+            output.push(index);
+            write_varint(&mut output, name);
+        }
+        output.push(0);
+    }
+    write_varint(&mut output, chunk.functions.len());
+    for function in &chunk.functions {
+        write_function(&mut output, function, &encoder, chunk.types_version)?;
+    }
+    write_varint(&mut output, chunk.main);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+    use crate::{
+        decoder::{MultiplicativeDecoder, PermutationDecoder, XorDecoder},
+        deserializer::deserialize,
+    };
+
+    fn fixtures() -> Vec<(PathBuf, Vec<u8>)> {
+        let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let mut fixtures = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|e| e == "bin"))
+            .map(|path| {
+                let bytecode = fs::read(&path).unwrap();
+                (path, bytecode)
+            })
+            .collect::<Vec<_>>();
+        fixtures.sort();
+        assert!(!fixtures.is_empty());
+        fixtures
+    }
+
+    // versions 4 to 6 share a layout, so each fixture is checked as every version
+    #[test]
+    fn round_trip_is_byte_identical() {
+        for (path, bytecode) in fixtures() {
+            for version in 4..=6 {
+                let mut bytecode = bytecode.clone();
+                bytecode[0] = version;
+                let chunk = deserialize(&bytecode, &MultiplicativeDecoder(1)).unwrap();
+                let serialized = serialize(&chunk, &MultiplicativeDecoder(1)).unwrap();
+                assert!(
+                    serialized == bytecode,
+                    "{} as version {} changed when round-tripped",
+                    path.display(),
+                    version
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_through_encodings() {
+        // every opcode shifted by one
+        let permutation = PermutationDecoder::new(&(1..=u8::MAX).chain([0]).collect::<Vec<_>>());
+        let decoders: [&dyn OpcodeDecoder; 3] =
+            [&MultiplicativeDecoder(203), &XorDecoder(0x5a), &permutation];
+        for (path, bytecode) in fixtures() {
+            let chunk = deserialize(&bytecode, &MultiplicativeDecoder(1)).unwrap();
+            for decoder in decoders {
+                let encoded = serialize(&chunk, decoder).unwrap();
+                assert_eq!(encoded.len(), bytecode.len());
+                let decoded = deserialize(&encoded, decoder).unwrap();
+                let serialized = serialize(&decoded, &MultiplicativeDecoder(1)).unwrap();
+                assert!(
+                    serialized == bytecode,
+                    "{} changed when re-encoded with {:?}",
+                    path.display(),
+                    decoder
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,646 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::{
+    deserializer::{chunk::Chunk, constant::Constant, function::Function},
+    disassembler,
+    instruction::Instruction,
+    op_code::OpCode,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DiagnosticKind {
+    #[error("main function {0} does not exist")]
+    InvalidMain(usize),
+    #[error("child function {0} does not exist")]
+    InvalidChild(usize),
+    #[error("child function {0} is also an ancestor")]
+    Cycle(usize),
+    #[error("function {0} can't be reached from the main function")]
+    Unreachable(usize),
+    #[error("jump to {0} is outside the function")]
+    JumpOutOfBounds(isize),
+    #[error("jump to {0} lands on an aux word")]
+    JumpIntoAux(usize),
+    #[error("register {register} is not below the stack size {max_stack_size}")]
+    InvalidRegister { register: usize, max_stack_size: u8 },
+    #[error("upvalue {upvalue} is not below the upvalue count {num_upvalues}")]
+    InvalidUpvalue { upvalue: u8, num_upvalues: u8 },
+    #[error("constant {0} does not exist")]
+    InvalidConstant(usize),
+    #[error("string {0} does not exist")]
+    InvalidString(usize),
+    #[error("import {0:#x} does not refer to string constants")]
+    InvalidImport(u32),
+    #[error("missing aux word")]
+    TruncatedAux,
+    #[error("the function runs off its end")]
+    MissingTerminator,
+}
+
+// errors would make the lifter panic or emit wrong code, warnings don't affect lifting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl DiagnosticKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            // a function nothing refers to is lifted as if it didn't exist
+            Self::Unreachable(_) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub function: Option<usize>,
+    pub pc: Option<usize>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.kind.severity() == Severity::Warning {
+            write!(f, "warning: ")?;
+        }
+        write!(f, "{}", self.kind)?;
+        if let Some(function) = self.function {
+            write!(f, " in function {}", function)?;
+        }
+        if let Some(pc) = self.pc {
+            write!(f, " at pc {}", pc)?;
+        }
+        Ok(())
+    }
+}
+
+// the registers an instruction reads or writes, ranges are given by their ends
+fn registers(instruction: Instruction) -> Vec<usize> {
+    match instruction {
+        Instruction::BC {
+            op_code,
+            a,
+            b,
+            c,
+            aux,
+        } => {
+            let (a, b, c) = (a as usize, b as usize, c as usize);
+            match op_code {
+                OpCode::LOP_LOADNIL
+                | OpCode::LOP_LOADB
+                | OpCode::LOP_GETGLOBAL
+                | OpCode::LOP_SETGLOBAL
+                | OpCode::LOP_GETUPVAL
+                | OpCode::LOP_SETUPVAL
+                | OpCode::LOP_CLOSEUPVALS
+                | OpCode::LOP_NEWTABLE
+                | OpCode::LOP_LOADKX => vec![a],
+                OpCode::LOP_MOVE
+                | OpCode::LOP_GETTABLEKS
+                | OpCode::LOP_SETTABLEKS
+                | OpCode::LOP_GETTABLEN
+                | OpCode::LOP_SETTABLEN
+                | OpCode::LOP_NOT
+                | OpCode::LOP_MINUS
+                | OpCode::LOP_LENGTH
+                | OpCode::LOP_ADDK
+                | OpCode::LOP_SUBK
+                | OpCode::LOP_MULK
+                | OpCode::LOP_DIVK
+                | OpCode::LOP_MODK
+                | OpCode::LOP_POWK
+                | OpCode::LOP_ANDK
+                | OpCode::LOP_ORK
+                | OpCode::LOP_IDIVK => vec![a, b],
+                OpCode::LOP_GETTABLE
+                | OpCode::LOP_SETTABLE
+                | OpCode::LOP_ADD
+                | OpCode::LOP_SUB
+                | OpCode::LOP_MUL
+                | OpCode::LOP_DIV
+                | OpCode::LOP_MOD
+                | OpCode::LOP_POW
+                | OpCode::LOP_AND
+                | OpCode::LOP_OR
+                | OpCode::LOP_IDIV
+                | OpCode::LOP_CONCAT => vec![a, b, c],
+                OpCode::LOP_SUBRK | OpCode::LOP_DIVRK => vec![a, c],
+                // the method is written to a and the object to a + 1
+                OpCode::LOP_NAMECALL => vec![a, a + 1, b],
+                // b - 1 arguments after the function and c - 1 results in its place, 0 for multiple
+                OpCode::LOP_CALL => {
+                    let mut registers = vec![a, (a + b).saturating_sub(1)];
+                    if c >= 2 {
+                        registers.push(a + c - 2);
+                    }
+                    registers
+                }
+                OpCode::LOP_RETURN | OpCode::LOP_GETVARARGS => match b {
+                    1 => Vec::new(),
+                    0 => vec![a],
+                    _ => vec![a, a + b - 2],
+                },
+                OpCode::LOP_SETLIST => match c {
+                    0 | 1 => vec![a, b],
+                    _ => vec![a, b, b + c - 2],
+                },
+                // a is the builtin
+                OpCode::LOP_FASTCALL1 | OpCode::LOP_FASTCALL2K => vec![b],
+                OpCode::LOP_FASTCALL2 => vec![b, aux as usize],
+                OpCode::LOP_FASTCALL3 => vec![b, (aux & 0xFF) as usize, (aux >> 8 & 0xFF) as usize],
+                // LCT_VAL and LCT_REF capture a register, LCT_UPVAL an upvalue
+                OpCode::LOP_CAPTURE if a < 2 => vec![b],
+                _ => Vec::new(),
+            }
+        }
+        Instruction::AD {
+            op_code, a, aux, ..
+        } => {
+            let a = a as usize;
+            match op_code {
+                OpCode::LOP_LOADN
+                | OpCode::LOP_LOADK
+                | OpCode::LOP_GETIMPORT
+                | OpCode::LOP_NEWCLOSURE
+                | OpCode::LOP_DUPTABLE
+                | OpCode::LOP_DUPCLOSURE
+                | OpCode::LOP_JUMPIF
+                | OpCode::LOP_JUMPIFNOT
+                | OpCode::LOP_JUMPXEQKNIL
+                | OpCode::LOP_JUMPXEQKB
+                | OpCode::LOP_JUMPXEQKN
+                | OpCode::LOP_JUMPXEQKS => vec![a],
+                OpCode::LOP_JUMPIFEQ
+                | OpCode::LOP_JUMPIFLE
+                | OpCode::LOP_JUMPIFLT
+                | OpCode::LOP_JUMPIFNOTEQ
+                | OpCode::LOP_JUMPIFNOTLE
+                | OpCode::LOP_JUMPIFNOTLT => vec![a, aux as usize],
+                // the limit, step and index
+                OpCode::LOP_FORNPREP
+                | OpCode::LOP_FORNLOOP
+                | OpCode::LOP_FORGPREP
+                | OpCode::LOP_FORGPREP_INEXT
+                | OpCode::LOP_FORGPREP_NEXT => vec![a, a + 2],
+                // the generator, state and control followed by the variables
+                OpCode::LOP_FORGLOOP => vec![a, a + 2 + (aux & 0xFF) as usize],
+                _ => Vec::new(),
+            }
+        }
+        Instruction::E { .. } => Vec::new(),
+    }
+}
+
+// the constant an instruction refers to
+fn constant(instruction: Instruction) -> Option<usize> {
+    match instruction {
+        Instruction::BC {
+            op_code, b, c, aux, ..
+        } => match op_code {
+            OpCode::LOP_GETGLOBAL
+            | OpCode::LOP_SETGLOBAL
+            | OpCode::LOP_GETTABLEKS
+            | OpCode::LOP_SETTABLEKS
+            | OpCode::LOP_NAMECALL
+            | OpCode::LOP_LOADKX
+            | OpCode::LOP_FASTCALL2K => Some(aux as usize),
+            OpCode::LOP_ADDK
+            | OpCode::LOP_SUBK
+            | OpCode::LOP_MULK
+            | OpCode::LOP_DIVK
+            | OpCode::LOP_MODK
+            | OpCode::LOP_POWK
+            | OpCode::LOP_ANDK
+            | OpCode::LOP_ORK
+            | OpCode::LOP_IDIVK => Some(c as usize),
+            OpCode::LOP_SUBRK | OpCode::LOP_DIVRK => Some(b as usize),
+            _ => None,
+        },
+        Instruction::AD {
+            op_code, d, aux, ..
+        } => match op_code {
+            OpCode::LOP_LOADK
+            | OpCode::LOP_GETIMPORT
+            | OpCode::LOP_DUPTABLE
+            | OpCode::LOP_DUPCLOSURE => Some(d as u16 as usize),
+            OpCode::LOP_JUMPXEQKN | OpCode::LOP_JUMPXEQKS => Some((aux & 0xFFFFFF) as usize),
+            _ => None,
+        },
+        Instruction::E { .. } => None,
+    }
+}
+
+pub(crate) fn is_terminator(instruction: Instruction) -> bool {
+    matches!(
+        instruction.op_code(),
+        OpCode::LOP_RETURN | OpCode::LOP_JUMP | OpCode::LOP_JUMPBACK | OpCode::LOP_JUMPX
+    )
+}
+
+struct Validator<'a> {
+    chunk: &'a Chunk,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, kind: DiagnosticKind, function: Option<usize>, pc: Option<usize>) {
+        self.diagnostics.push(Diagnostic { kind, function, pc });
+    }
+
+    // string indices are 1-based, 0 means no string
+    fn check_string(&mut self, index: usize, function_id: usize) {
+        if index > self.chunk.string_table.len() {
+            self.report(
+                DiagnosticKind::InvalidString(index),
+                Some(function_id),
+                None,
+            );
+        }
+    }
+
+    // the compiler shares a child between functions when it inlines one into the other, so
+    // children form a dag rather than a tree. a cycle would make the decompiler recurse forever
+    fn check_children(&mut self) {
+        let function_count = self.chunk.functions.len();
+        if self.chunk.main >= function_count {
+            self.report(DiagnosticKind::InvalidMain(self.chunk.main), None, None);
+            return;
+        }
+
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum State {
+            Unvisited,
+            OnStack,
+            Done,
+        }
+        let mut states = vec![State::Unvisited; function_count];
+        states[self.chunk.main] = State::OnStack;
+        // each function on the path from main and the index of the next child to visit
+        let mut stack = vec![(self.chunk.main, 0)];
+        while let Some(&(parent, index)) = stack.last() {
+            let Some(&child) = self.chunk.functions[parent].functions.get(index) else {
+                states[parent] = State::Done;
+                stack.pop();
+                continue;
+            };
+            stack.last_mut().unwrap().1 += 1;
+            if child >= function_count {
+                self.report(DiagnosticKind::InvalidChild(child), Some(parent), None);
+                continue;
+            }
+            match states[child] {
+                State::Unvisited => {
+                    states[child] = State::OnStack;
+                    stack.push((child, 0));
+                }
+                State::OnStack => {
+                    self.report(DiagnosticKind::Cycle(child), Some(parent), None);
+                }
+                State::Done => {}
+            }
+        }
+
+        for (function_id, state) in states.into_iter().enumerate() {
+            if state == State::Unvisited {
+                self.report(
+                    DiagnosticKind::Unreachable(function_id),
+                    Some(function_id),
+                    None,
+                );
+            }
+        }
+    }
+
+    fn check_constants(&mut self, function_id: usize, function: &Function) {
+        self.check_string(function.function_name, function_id);
+        for local in &function.local_variables {
+            self.check_string(local.name, function_id);
+        }
+        for &name in &function.upvalue_names {
+            self.check_string(name, function_id);
+        }
+
+        let is_string =
+            |index: usize| matches!(function.constants.get(index), Some(Constant::String(_)));
+        for constant in &function.constants {
+            match *constant {
+                Constant::String(index) if index == 0 => self.report(
+                    DiagnosticKind::InvalidString(index),
+                    Some(function_id),
+                    None,
+                ),
+                Constant::String(index) => self.check_string(index, function_id),
+                Constant::Import(import) => {
+                    let import = import as u32;
+                    let count = (import >> 30) as usize;
+                    let indices = [import >> 20 & 1023, import >> 10 & 1023, import & 1023];
+                    if count == 0 || indices[..count].iter().any(|&k| !is_string(k as usize)) {
+                        self.report(
+                            DiagnosticKind::InvalidImport(import),
+                            Some(function_id),
+                            None,
+                        );
+                    }
+                }
+                Constant::Table(ref keys) => {
+                    for &key in keys {
+                        if key >= function.constants.len() {
+                            self.report(
+                                DiagnosticKind::InvalidConstant(key),
+                                Some(function_id),
+                                None,
+                            );
+                        }
+                    }
+                }
+                Constant::Closure(child) if child >= self.chunk.functions.len() => {
+                    self.report(DiagnosticKind::InvalidChild(child), Some(function_id), None)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_instructions(&mut self, function_id: usize, function: &Function) {
+        let instructions = &function.instructions;
+        // the deserializer places a nop after every instruction with an aux word
+        let is_aux = |pc: usize| pc > 0 && instructions[pc - 1].op_code().has_aux();
+
+        for (pc, &instruction) in instructions.iter().enumerate() {
+            if is_aux(pc) {
+                continue;
+            }
+            let report =
+                |validator: &mut Self, kind| validator.report(kind, Some(function_id), Some(pc));
+
+            if instruction.op_code().has_aux() && pc + 1 == instructions.len() {
+                report(self, DiagnosticKind::TruncatedAux);
+            }
+
+            if let Some(offset) = disassembler::jump_offset(instruction) {
+                let target = pc as isize + 1 + offset;
+                if target < 0 || target as usize >= instructions.len() {
+                    report(self, DiagnosticKind::JumpOutOfBounds(target));
+                } else if is_aux(target as usize) {
+                    report(self, DiagnosticKind::JumpIntoAux(target as usize));
+                }
+            }
+
+            for register in registers(instruction) {
+                if register >= function.max_stack_size as usize {
+                    report(
+                        self,
+                        DiagnosticKind::InvalidRegister {
+                            register,
+                            max_stack_size: function.max_stack_size,
+                        },
+                    );
+                }
+            }
+
+            match constant(instruction) {
+                Some(constant) if constant >= function.constants.len() => {
+                    report(self, DiagnosticKind::InvalidConstant(constant))
+                }
+                _ => {}
+            }
+
+            match instruction {
+                Instruction::AD {
+                    op_code: OpCode::LOP_NEWCLOSURE,
+                    d,
+                    ..
+                } if d as u16 as usize >= function.functions.len() => {
+                    report(self, DiagnosticKind::InvalidChild(d as u16 as usize))
+                }
+                // LCT_UPVAL captures an upvalue of the function creating the closure
+                Instruction::BC {
+                    op_code: OpCode::LOP_GETUPVAL | OpCode::LOP_SETUPVAL,
+                    b,
+                    ..
+                }
+                | Instruction::BC {
+                    op_code: OpCode::LOP_CAPTURE,
+                    a: 2,
+                    b,
+                    ..
+                } if b >= function.num_upvalues => report(
+                    self,
+                    DiagnosticKind::InvalidUpvalue {
+                        upvalue: b,
+                        num_upvalues: function.num_upvalues,
+                    },
+                ),
+                _ => {}
+            }
+        }
+
+        if !instructions
+            .last()
+            .is_some_and(|&instruction| is_terminator(instruction))
+        {
+            self.report(DiagnosticKind::MissingTerminator, Some(function_id), None);
+        }
+    }
+}
+
+// checks the things the lifter relies on, returns no errors if the chunk is well-formed
+pub fn validate(chunk: &Chunk) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        chunk,
+        diagnostics: Vec::new(),
+    };
+    validator.check_children();
+    for (function_id, function) in chunk.functions.iter().enumerate() {
+        if chunk.is_missing(function_id) {
+            continue;
+        }
+        validator.check_constants(function_id, function);
+        validator.check_instructions(function_id, function);
+    }
+    validator.diagnostics
+}
+
+pub fn errors(diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    diagnostics
+        .into_iter()
+        .filter(|d| d.kind.severity() == Severity::Error)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decoder::MultiplicativeDecoder, decompile_bytecode, deserializer::deserialize,
+        serializer::serialize, Limits,
+    };
+
+    const BYTECODE: &[u8] = include_bytes!("../fixtures/control_flow.bin");
+
+    // function 0 is `greet`, which has jumps and aux words, and function 1 is a leaf:
+    //   GETUPVAL 1 0; GETTABLEKS 0 1 [K0 "total"]; GETUPVAL 1 1; RETURN 0 3
+    fn fixture() -> Chunk {
+        deserialize(BYTECODE, &MultiplicativeDecoder(1)).unwrap()
+    }
+
+    fn kinds(chunk: &Chunk) -> Vec<DiagnosticKind> {
+        validate(chunk).into_iter().map(|d| d.kind).collect()
+    }
+
+    #[track_caller]
+    fn assert_reports(chunk: &Chunk, expected: DiagnosticKind) {
+        let kinds = kinds(chunk);
+        assert!(
+            kinds.contains(&expected),
+            "expected {:?} in {:?}",
+            expected,
+            kinds
+        );
+    }
+
+    #[test]
+    fn fixture_is_valid() {
+        assert_eq!(kinds(&fixture()), []);
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let mut chunk = fixture();
+        let main = chunk.main;
+        chunk.functions[main].functions.push(main);
+        assert_reports(&chunk, DiagnosticKind::Cycle(main));
+    }
+
+    // an unreachable function is only a warning, so the chunk still decompiles
+    #[test]
+    fn reports_unreachable_functions() {
+        let mut chunk = fixture();
+        let leaf = fixture().functions.swap_remove(1);
+        chunk.functions.push(leaf);
+        let unreachable = chunk.functions.len() - 1;
+        assert_eq!(kinds(&chunk), [DiagnosticKind::Unreachable(unreachable)]);
+        assert!(errors(validate(&chunk)).is_empty());
+
+        let bytecode = serialize(&chunk, &MultiplicativeDecoder(1)).unwrap();
+        decompile_bytecode(&bytecode, &MultiplicativeDecoder(1), &Limits::default()).unwrap();
+    }
+
+    #[test]
+    fn reports_invalid_registers() {
+        let mut chunk = fixture();
+        chunk.functions[1].max_stack_size = 1;
+        assert_reports(
+            &chunk,
+            DiagnosticKind::InvalidRegister {
+                register: 1,
+                max_stack_size: 1,
+            },
+        );
+    }
+
+    #[test]
+    fn reports_invalid_upvalues() {
+        let mut chunk = fixture();
+        chunk.functions[1].num_upvalues = 1;
+        assert_reports(
+            &chunk,
+            DiagnosticKind::InvalidUpvalue {
+                upvalue: 1,
+                num_upvalues: 1,
+            },
+        );
+
+        // function 2 is `count`, which captures its upvalue for the closure it returns
+        let mut chunk = fixture();
+        chunk.functions[2].num_upvalues = 0;
+        let capture = chunk.functions[2]
+            .instructions
+            .iter()
+            .position(|i| {
+                matches!(
+                    i,
+                    Instruction::BC {
+                        op_code: OpCode::LOP_CAPTURE,
+                        a: 2,
+                        ..
+                    }
+                )
+            })
+            .unwrap();
+        let diagnostics = validate(&chunk);
+        assert!(
+            diagnostics.contains(&Diagnostic {
+                kind: DiagnosticKind::InvalidUpvalue {
+                    upvalue: 0,
+                    num_upvalues: 0,
+                },
+                function: Some(2),
+                pc: Some(capture),
+            }),
+            "{:?}",
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn reports_invalid_constants() {
+        let mut chunk = fixture();
+        chunk.functions[1].constants.clear();
+        assert_reports(&chunk, DiagnosticKind::InvalidConstant(0));
+    }
+
+    #[test]
+    fn reports_invalid_strings() {
+        let mut chunk = fixture();
+        let index = chunk.string_table.len() + 1;
+        chunk.functions[1].constants[0] = Constant::String(index);
+        assert_reports(&chunk, DiagnosticKind::InvalidString(index));
+    }
+
+    #[test]
+    fn reports_invalid_imports() {
+        let mut chunk = fixture();
+        // an import of no names
+        chunk.functions[1].constants.push(Constant::Import(0));
+        assert_reports(&chunk, DiagnosticKind::InvalidImport(0));
+    }
+
+    #[test]
+    fn reports_jumps_into_aux() {
+        let mut chunk = fixture();
+        let instructions = &mut chunk.functions[0].instructions;
+        let aux = (1..instructions.len())
+            .find(|&pc| instructions[pc - 1].op_code().has_aux())
+            .unwrap();
+        let jump = instructions
+            .iter()
+            .position(|i| i.op_code() == OpCode::LOP_JUMP)
+            .unwrap();
+        let Instruction::AD { d, .. } = &mut instructions[jump] else {
+            unreachable!();
+        };
+        *d = (aux as isize - jump as isize - 1) as i16;
+        assert_reports(&chunk, DiagnosticKind::JumpIntoAux(aux));
+    }
+
+    #[test]
+    fn reports_truncated_aux() {
+        let mut chunk = fixture();
+        // cuts the aux word off the GETTABLEKS
+        chunk.functions[1].instructions.truncate(2);
+        assert_reports(&chunk, DiagnosticKind::TruncatedAux);
+    }
+
+    #[test]
+    fn reports_missing_terminators() {
+        let mut chunk = fixture();
+        chunk.functions[1].instructions.pop();
+        assert_eq!(kinds(&chunk), [DiagnosticKind::MissingTerminator]);
+    }
+}
@@ -1,11 +1,19 @@
 use futures_util::StreamExt;
+use std::time::Duration;
 extern crate console_error_panic_hook;
 
 use base64::prelude::*;
-use luau_lifter::decompile_bytecode;
+use luau_lifter::{decoder::MultiplicativeDecoder, decompile_bytecode, Limits};
 use serde::{Deserialize, Serialize};
 use worker::*;
 
+// timers don't advance while a worker is executing, so the deadlines only catch time spent
+// waiting and the iteration limit is what actually bounds structuring
+const LIMITS: Limits = Limits {
+    max_iterations: Some(1000),
+    function_timeout: Some(Duration::from_secs(5)),
+    chunk_timeout: Some(Duration::from_secs(20)),
+};
 const AUTH_SECRET: &str = "ymjKH2O3BbO3bDSsKmpo3ek3vHxIWYLQfj0";
 
 #[derive(Deserialize)]
@@ -55,7 +63,12 @@ pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Respo
                             .expect("bytecode must be base64 encoded");
                         let resp = DecompileResponse {
                             id: msg.id,
-                            decompilation: decompile_bytecode(&bytecode, 1),
+                            decompilation: decompile_bytecode(
+                                &bytecode,
+                                &MultiplicativeDecoder(1),
+                                &LIMITS,
+                            )
+                            .map_or_else(|e| e.to_string(), |o| o.source),
                         };
                         server
                             .send_with_str(serde_json::to_string(&resp).unwrap())
@@ -79,7 +92,12 @@ pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Respo
 
             let encoded_bytecode = req.bytes().await?;
             match BASE64_STANDARD.decode(encoded_bytecode) {
-                Ok(bytecode) => Response::ok(decompile_bytecode(&bytecode, 203)),
+                Ok(bytecode) => {
+                    match decompile_bytecode(&bytecode, &MultiplicativeDecoder(203), &LIMITS) {
+                        Ok(decompiled) => Response::ok(decompiled.source),
+                        Err(err) => Response::error(err.to_string(), 400),
+                    }
+                }
                 Err(_) => Response::error("invalid bytecode", 400),
             }
         })
@@ -0,0 +1,8 @@
+-- decompiled by Sentinel (took 3.985081ms)
+local a, b = ...
+print(a // b, a & b, a | b, a ~ b, ~a, a << 2, 1 << a, a >> 1, a - 1, 2 + a, 1.5, 3)
+local f <close> = setmetatable({}, {
+	["__close"] = print
+})
+print(f)
+return a > 5
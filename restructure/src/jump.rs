@@ -20,17 +20,20 @@ impl super::GraphStructurer {
             && then_edge.target() == else_edge.target()
         {
             let target = then_edge.target();
-            let cond = self
+            let ast::If {
+                condition: cond,
+                origin,
+                ..
+            } = self
                 .function
                 .block_mut(node)
                 .unwrap()
                 .pop()
                 .unwrap()
                 .into_if()
-                .unwrap()
-                .condition;
+                .unwrap();
 
-            let new_stat = match cond {
+            let mut new_stat: Option<ast::Statement> = match cond {
                 ast::RValue::Call(call) => Some(call.into()),
                 ast::RValue::MethodCall(method_call) => Some(method_call.into()),
                 cond if cond.has_side_effects() => Some(
@@ -38,12 +41,18 @@ impl super::GraphStructurer {
                         left: vec![ast::RcLocal::default().into()],
                         right: vec![cond],
                         prefix: true,
+                        attributes: Vec::new(),
                         parallel: false,
+                        origin: ast::Origin::default(),
                     }
                     .into(),
                 ),
                 _ => None,
             };
+            // what remains of the condition
+            if let Some(new_origin) = new_stat.as_mut().and_then(|s| s.origin_mut()) {
+                *new_origin = origin;
+            }
             self.function.block_mut(node).unwrap().extend(new_stat);
             self.function.set_edges(
                 node,
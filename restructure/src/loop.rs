@@ -110,7 +110,10 @@ impl GraphStructurer {
                 };
                 let init_ast = &mut self.function.block_mut(init_block).unwrap();
                 init_ast.extend(statements);
-                let new_stat = match statement {
+                // the loop is written where its init was and covers the instructions of both
+                let mut origin = init_ast[init_index].origin().cloned().unwrap_or_default();
+                origin.merge(statement.origin().cloned().unwrap_or_default());
+                let mut new_stat: ast::Statement = match statement {
                     ast::Statement::NumForNext(num_for_next) => {
                         let for_init = init_ast.remove(init_index).into_num_for_init().unwrap();
                         ast::NumericFor::new(
@@ -139,6 +142,7 @@ impl GraphStructurer {
                         unreachable!();
                     }
                 };
+                *new_stat.origin_mut().unwrap() = origin;
                 init_ast.push(new_stat);
                 self.function.remove_block(header);
 
@@ -176,14 +180,16 @@ impl GraphStructurer {
                     };
                     let header_block = self.function.block_mut(header).unwrap();
                     *header_block = if header_block.is_empty() {
-                        vec![ast::While::new(
+                        let mut r#while = ast::While::new(
                             ast::Unary::new(condition, ast::UnaryOperation::Not).reduce_condition(),
                             header_block.clone(),
-                        )
-                        .into()]
-                        .into()
+                        );
+                        r#while.origin = if_stat.origin;
+                        vec![r#while.into()].into()
                     } else {
-                        vec![ast::Repeat::new(condition, header_block.clone()).into()].into()
+                        let mut repeat = ast::Repeat::new(condition, header_block.clone());
+                        repeat.origin = if_stat.origin;
+                        vec![repeat.into()].into()
                     };
                     self.function.set_edges(
                         header,
@@ -220,7 +226,9 @@ impl GraphStructurer {
                 let body_ast: ast::Block = statements.to_vec().into();
                 let init_ast = &mut self.function.block_mut(init_block).unwrap();
                 init_ast.extend(statements);
-                let new_stat = match statement {
+                let mut origin = init_ast[init_index].origin().cloned().unwrap_or_default();
+                origin.merge(statement.origin().cloned().unwrap_or_default());
+                let mut new_stat: ast::Statement = match statement {
                     ast::Statement::NumForNext(num_for_next) => {
                         let for_init = init_ast.remove(init_index).into_num_for_init().unwrap();
                         ast::NumericFor::new(
@@ -249,6 +257,7 @@ impl GraphStructurer {
                         unreachable!();
                     }
                 };
+                *new_stat.origin_mut().unwrap() = origin;
                 init_ast.push(new_stat);
                 self.function.remove_block(header);
 
@@ -432,14 +441,13 @@ impl GraphStructurer {
                             if_condition = ast::Unary::new(if_condition, ast::UnaryOperation::Not)
                                 .reduce_condition();
                         }
-                        body_block.push(
-                            ast::If::new(
-                                if_condition,
-                                vec![ast::Break {}.into()].into(),
-                                ast::Block::default(),
-                            )
-                            .into(),
+                        let mut r#if = ast::If::new(
+                            if_condition,
+                            vec![ast::Break {}.into()].into(),
+                            ast::Block::default(),
                         );
+                        r#if.origin = if_stat.origin;
+                        body_block.push(r#if.into());
                         body_block.extend(block.0);
 
                         ast::While::new(ast::Literal::Boolean(true).into(), body_block)
@@ -449,7 +457,9 @@ impl GraphStructurer {
                                 .reduce_condition();
                         }
 
-                        ast::While::new(if_condition, block)
+                        let mut r#while = ast::While::new(if_condition, block);
+                        r#while.origin = if_stat.origin;
+                        r#while
                     };
 
                     self.function
@@ -471,7 +481,9 @@ impl GraphStructurer {
                     body_ast.extend(statements.iter().cloned());
                     let init_ast = &mut self.function.block_mut(init_block).unwrap();
                     init_ast.extend(statements);
-                    let new_stat = match statement {
+                    let mut origin = init_ast[init_index].origin().cloned().unwrap_or_default();
+                    origin.merge(statement.origin().cloned().unwrap_or_default());
+                    let mut new_stat: ast::Statement = match statement {
                         ast::Statement::NumForNext(num_for_next) => {
                             let for_init = init_ast.remove(init_index).into_num_for_init().unwrap();
                             ast::NumericFor::new(
@@ -501,6 +513,7 @@ impl GraphStructurer {
                             unreachable!();
                         }
                     };
+                    *new_stat.origin_mut().unwrap() = origin;
                     init_ast.push(new_stat);
                     self.function.remove_block(header);
 
@@ -0,0 +1,27 @@
+-- decompiled by Sentinel (took 8.05099ms)
+local t = {
+	["name"] = "t",
+	1,
+	2,
+	3
+}
+local greeting = "hello"
+for i = 10, 1, -2 do
+	t[#t + 1] = i
+end
+for k, v in pairs(t) do
+	print(k, v)
+end
+local function counter()
+	local count = 0
+	return function(...)
+		-- upvalues: (ref) count
+		count = count + select("#", ...)
+		return count
+	end
+end
+if t.name ~= greeting then
+	shared = counter()
+end
+_ENV["not a name"] = 1
+return counter, t
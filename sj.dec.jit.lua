@@ -0,0 +1,45 @@
+-- decompiled by Sentinel (took 15.57625ms)
+local t = {
+	1,
+	2,
+	3,
+	["x"] = "y"
+}
+local greeting = "hello"
+for i = 1, #t, 2 do
+	print(i, t[i])
+end
+for k, v in pairs(t) do
+	if k == "x" then
+		if v ~= nil then
+			print(k, v)
+		end
+	end
+end
+for k, v in ipairs(t) do
+	print(k, v)
+end
+local function counter()
+	local count = 0
+	return function(...)
+		-- upvalues: (ref) count
+		count = count + select("#", ...)
+		return count
+	end
+end
+shared = counter()
+local big = 9223372036854775807LL
+local unsigned = 18446744073709551615ULL
+if greeting < "z" then
+	print(big, unsigned, -3, true, nil)
+end
+while shared() < 10 do
+	local x = {}
+	for v1 = 1, 3 do
+		local i = v1
+		x[i] = function()
+			-- upvalues: (ref) i
+			return i
+		end
+	end
+end